@@ -0,0 +1,75 @@
+//! Collects the details `s3fcp --version` reports: the git commit, build date,
+//! target and the versions of the HTTP and AWS crates resolved in Cargo.lock
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+    for (name, var) in [
+        ("aws-sdk-s3", "S3FCP_AWS_SDK_S3_VERSION"),
+        ("aws-config", "S3FCP_AWS_CONFIG_VERSION"),
+        ("reqwest", "S3FCP_REQWEST_VERSION"),
+    ] {
+        let version = locked_version(&lock, name).unwrap_or("unknown");
+        println!("cargo:rustc-env={}={}", var, version);
+    }
+
+    println!("cargo:rustc-env=S3FCP_GIT_HASH={}", git_hash());
+    println!("cargo:rustc-env=S3FCP_BUILD_DATE={}", build_date());
+    println!(
+        "cargo:rustc-env=S3FCP_TARGET={}",
+        std::env::var("TARGET").unwrap_or_default()
+    );
+}
+
+/// Version of the first package called `name` in the lock file
+fn locked_version<'a>(lock: &'a str, name: &str) -> Option<&'a str> {
+    let entry = format!("name = \"{}\"\n", name);
+    let start = lock.find(&entry)? + entry.len();
+    lock[start..]
+        .strip_prefix("version = \"")?
+        .split('"')
+        .next()
+}
+
+/// Short hash of the checked-out commit, or `unknown` outside a git checkout
+fn git_hash() -> String {
+    Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// UTC date of the build as YYYY-MM-DD, honoring SOURCE_DATE_EPOCH for reproducible builds
+fn build_date() -> String {
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        });
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
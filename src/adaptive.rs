@@ -0,0 +1,178 @@
+use crate::progress::ProgressTracker;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+/// Workers allowed to download when an adaptive download starts
+pub const INITIAL_WORKERS: usize = 2;
+
+/// How often the controller checks whether enough chunks finished for a measurement
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Relative throughput gain that justifies the workers added by a probe
+const MIN_GAIN: f64 = 0.1;
+
+/// Relative drop below the settled throughput after which the controller probes again
+const MAX_DROP: f64 = 0.25;
+
+/// Hill-climbing choice of the number of active workers
+///
+/// The pool doubles while doing so raises the aggregate throughput by at least 10%.
+/// When a doubling doesn't pay off, the pool goes back to its previous size and stays
+/// there until throughput drops by a quarter, which starts a new probe.
+#[derive(Debug)]
+pub struct AdaptiveConcurrency {
+    workers: usize,
+    max: usize,
+    /// Worker count and throughput before the probe in progress
+    probe: Option<(usize, f64)>,
+    /// Throughput at which growing stopped paying off
+    settled: Option<f64>,
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            workers: INITIAL_WORKERS.min(max),
+            max,
+            probe: None,
+            settled: None,
+        }
+    }
+
+    /// Number of workers that should be downloading
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Record the throughput in bytes per second since the last call and return the
+    /// number of workers for the next measurement
+    pub fn observe(&mut self, rate: f64) -> usize {
+        if let Some((workers, before)) = self.probe.take() {
+            if rate < before * (1.0 + MIN_GAIN) {
+                self.workers = workers;
+                self.settled = Some(before.max(rate));
+                return self.workers;
+            }
+        }
+
+        if let Some(settled) = self.settled {
+            if rate >= settled * (1.0 - MAX_DROP) {
+                return self.workers;
+            }
+            self.settled = None;
+        }
+
+        if self.workers < self.max {
+            self.probe = Some((self.workers, rate));
+            self.workers = (self.workers * 2).min(self.max);
+        }
+        self.workers
+    }
+}
+
+/// Limits how many download workers hold a permit at once, resized by a background
+/// controller that measures throughput through the download's progress tracker
+///
+/// All workers are spawned up front; those without a permit stay parked.
+pub struct WorkerGate {
+    permits: Arc<Semaphore>,
+    controller: JoinHandle<()>,
+}
+
+impl WorkerGate {
+    /// Start with `INITIAL_WORKERS` permits and adapt up to `max`
+    pub fn start(max: usize, progress: Arc<ProgressTracker>) -> Self {
+        let permits = Arc::new(Semaphore::new(0));
+        let controller = tokio::spawn(control(permits.clone(), progress, max));
+        Self {
+            permits,
+            controller,
+        }
+    }
+
+    /// Semaphore a worker must hold a permit of while downloading a chunk
+    pub fn permits(&self) -> Arc<Semaphore> {
+        self.permits.clone()
+    }
+}
+
+impl Drop for WorkerGate {
+    fn drop(&mut self) {
+        self.controller.abort();
+    }
+}
+
+/// Measure once every active worker could finish a chunk, then grow or shrink the permits
+async fn control(permits: Arc<Semaphore>, progress: Arc<ProgressTracker>, max: usize) {
+    let mut concurrency = AdaptiveConcurrency::new(max);
+    permits.add_permits(concurrency.workers());
+
+    let mut window_start = Instant::now();
+    let mut bytes = progress.bytes_done();
+    let mut chunks = progress.chunks_done();
+    let mut ticker = tokio::time::interval(SAMPLE_INTERVAL);
+    loop {
+        ticker.tick().await;
+        // Chunks are counted when they complete, so shorter windows would be mostly noise
+        if progress.chunks_done() - chunks < concurrency.workers() {
+            continue;
+        }
+
+        let rate = (progress.bytes_done() - bytes) as f64 / window_start.elapsed().as_secs_f64();
+        let current = concurrency.workers();
+        let next = concurrency.observe(rate);
+        if next > current {
+            permits.add_permits(next - current);
+        } else if next < current {
+            // Parks workers as they finish their current chunk
+            if let Ok(parked) = permits.acquire_many((current - next) as u32).await {
+                parked.forget();
+            }
+        }
+
+        window_start = Instant::now();
+        bytes = progress.bytes_done();
+        chunks = progress.chunks_done();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grows_while_throughput_rises() {
+        let mut concurrency = AdaptiveConcurrency::new(16);
+        assert_eq!(concurrency.workers(), 2);
+
+        assert_eq!(concurrency.observe(100.0), 4);
+        assert_eq!(concurrency.observe(200.0), 8);
+        assert_eq!(concurrency.observe(400.0), 16);
+        // Capped at the maximum
+        assert_eq!(concurrency.observe(800.0), 16);
+    }
+
+    #[test]
+    fn test_backs_off_when_growth_does_not_pay() {
+        let mut concurrency = AdaptiveConcurrency::new(64);
+        assert_eq!(concurrency.observe(100.0), 4);
+        assert_eq!(concurrency.observe(200.0), 8);
+        // Doubling to 8 gained only 5%
+        assert_eq!(concurrency.observe(210.0), 4);
+        assert_eq!(concurrency.observe(205.0), 4);
+
+        // A large drop starts a new probe
+        assert_eq!(concurrency.observe(100.0), 8);
+    }
+
+    #[test]
+    fn test_small_maximum() {
+        let mut concurrency = AdaptiveConcurrency::new(1);
+        assert_eq!(concurrency.workers(), 1);
+        assert_eq!(concurrency.observe(100.0), 1);
+        assert_eq!(AdaptiveConcurrency::new(0).workers(), 1);
+    }
+}
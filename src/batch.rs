@@ -0,0 +1,174 @@
+use crate::error::{Result, S3FcpError};
+use crate::template::{OutputTemplate, TemplateFields};
+use crate::uri::{HttpUri, S3Uri};
+use indicatif::HumanBytes;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// One URI of a batch list
+#[derive(Debug, Clone)]
+pub enum BatchSource {
+    S3(S3Uri),
+    Http(HttpUri),
+}
+
+impl BatchSource {
+    /// Parse an `s3://` URI or an `http(s)://` URL
+    pub fn parse(uri: &str, skip_bucket_validation: bool) -> Result<Self> {
+        if uri.starts_with("s3://") {
+            let uri = if skip_bucket_validation {
+                S3Uri::parse_unvalidated(uri)?
+            } else {
+                S3Uri::parse(uri)?
+            };
+            Ok(Self::S3(uri))
+        } else {
+            Ok(Self::Http(HttpUri::parse(uri)?))
+        }
+    }
+
+    /// Bucket and key, or host and path of a URL
+    fn location(&self) -> (String, String) {
+        match self {
+            Self::S3(uri) => (uri.bucket.clone(), uri.key.clone()),
+            Self::Http(uri) => reqwest::Url::parse(&uri.url)
+                .map(|url| {
+                    let host = url.host_str().unwrap_or_default().to_string();
+                    (host, url.path().trim_start_matches('/').to_string())
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Name of the downloaded file: the last segment of the key or URL path
+    pub fn file_name(&self) -> Result<String> {
+        let (_, path) = self.location();
+        match path.rsplit('/').next() {
+            Some(name) if !name.is_empty() && name != "." && name != ".." => Ok(name.to_string()),
+            _ => Err(S3FcpError::InvalidUri(format!(
+                "'{}' doesn't end in a file name",
+                path
+            ))),
+        }
+    }
+
+    /// Path of the downloaded file relative to the output directory, by `template`
+    pub fn render(
+        &self,
+        template: &OutputTemplate,
+        last_modified: Option<SystemTime>,
+    ) -> Result<PathBuf> {
+        let (bucket, key) = self.location();
+        template.render(&TemplateFields {
+            bucket: &bucket,
+            key: &key,
+            path: &key,
+            last_modified,
+        })
+    }
+}
+
+/// Entry of a batch list: the trimmed line, unless it is blank or a `#` comment
+pub fn list_entry(line: &str) -> Option<&str> {
+    let line = line.trim();
+    (!line.is_empty() && !line.starts_with('#')).then_some(line)
+}
+
+/// Outcome of a batch download, printed when the list is exhausted
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub downloaded: usize,
+    pub bytes: u64,
+    /// Each failed URI with the reason
+    pub failed: Vec<(String, String)>,
+}
+
+impl BatchSummary {
+    /// Fail if any entry failed, so the exit code reflects the whole batch
+    pub fn into_result(self) -> Result<()> {
+        if self.failed.is_empty() {
+            return Ok(());
+        }
+        Err(S3FcpError::DownloadFailed(format!(
+            "{} of {} batch downloads failed",
+            self.failed.len(),
+            self.failed.len() + self.downloaded
+        )))
+    }
+}
+
+impl fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Downloaded {} files ({}), {} failed",
+            self.downloaded,
+            HumanBytes(self.bytes),
+            self.failed.len()
+        )?;
+        for (uri, reason) in &self.failed {
+            write!(f, "\n  {}: {}", uri, reason)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name() {
+        let name = |uri: &str| BatchSource::parse(uri, false).unwrap().file_name();
+        assert_eq!(name("s3://bucket/logs/app.log").unwrap(), "app.log");
+        assert_eq!(name("https://example.com/a/b.bin?x=1").unwrap(), "b.bin");
+        assert!(name("s3://bucket/logs/").is_err());
+        assert!(name("https://example.com/").is_err());
+        assert!(BatchSource::parse("ftp://example.com/f", false).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let template = OutputTemplate::parse("{bucket}/{key}").unwrap();
+        let render = |uri: &str| {
+            BatchSource::parse(uri, false)
+                .unwrap()
+                .render(&template, None)
+        };
+        assert_eq!(
+            render("s3://bucket/logs/app.log").unwrap(),
+            PathBuf::from("bucket/logs/app.log")
+        );
+        assert_eq!(
+            render("https://example.com/a/b.bin?x=1").unwrap(),
+            PathBuf::from("example.com/a/b.bin")
+        );
+        assert!(render("https://example.com/").is_err());
+    }
+
+    #[test]
+    fn test_list_entry() {
+        assert_eq!(list_entry("  s3://b/k \r"), Some("s3://b/k"));
+        assert_eq!(list_entry(""), None);
+        assert_eq!(list_entry("# comment"), None);
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = BatchSummary {
+            downloaded: 2,
+            bytes: 2048,
+            failed: vec![("s3://b/missing".to_string(), "Not found".to_string())],
+        };
+        assert_eq!(
+            summary.to_string(),
+            "Downloaded 2 files (2.00 KiB), 1 failed\n  s3://b/missing: Not found"
+        );
+        assert_eq!(
+            summary.into_result().unwrap_err().to_string(),
+            "Download failed: 1 of 3 batch downloads failed"
+        );
+        assert!(BatchSummary::default().into_result().is_ok());
+    }
+}
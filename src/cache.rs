@@ -0,0 +1,500 @@
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::error::Result;
+use crate::s3_client::{DownloadClient, ObjectMetadata};
+use crate::verify::to_hex;
+
+/// Size of the aligned blocks ranges are cached in
+pub const DEFAULT_BLOCK_SIZE: u64 = 1024 * 1024;
+
+/// Distinguishes the temporary files of concurrent stores within one process
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// `DownloadClient` wrapper that keeps downloaded ranges in a local directory
+///
+/// Ranges are cached as aligned blocks, so any later range, whether it overlaps an
+/// earlier one or comes from a different chunk size, is served from the blocks already
+/// on disk and only the missing blocks are fetched. Blocks are keyed by the source
+/// (bucket, key and version, or the URL) and the object's ETag, so a changed object
+/// never serves stale data and sources sharing a directory never serve each other's
+/// bytes. Blocks of outdated ETags are no longer read and are eventually evicted,
+/// least recently used first, once the cache grows beyond `max_size` bytes. Objects
+/// without an ETag, or with a weak one, are not cached.
+pub struct CachingClient {
+    inner: Arc<dyn DownloadClient>,
+    source: String,
+    dir: PathBuf,
+    max_size: u64,
+    block_size: u64,
+    object: Mutex<Option<CachedObject>>,
+    /// Bytes in the cache directory, counted on the first store and kept up to date
+    /// after that, so the directory is only scanned again when it must be evicted
+    size: tokio::sync::Mutex<Option<u64>>,
+}
+
+/// The object version whose blocks are read and written
+#[derive(Clone)]
+struct CachedObject {
+    dir: PathBuf,
+    content_length: u64,
+}
+
+impl CachingClient {
+    /// `source` identifies the object across runs, e.g. its S3 URI or its URL
+    pub fn new(
+        inner: Arc<dyn DownloadClient>,
+        source: String,
+        dir: PathBuf,
+        max_size: u64,
+    ) -> Self {
+        Self {
+            inner,
+            source,
+            dir,
+            max_size,
+            block_size: DEFAULT_BLOCK_SIZE,
+            object: Mutex::new(None),
+            size: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// Cache ranges in blocks of `block_size` bytes instead of `DEFAULT_BLOCK_SIZE`
+    pub fn with_block_size(mut self, block_size: u64) -> Self {
+        self.block_size = block_size.max(1);
+        self
+    }
+
+    /// Byte range covered by `block`, clamped to the object's end
+    fn block_range(&self, object: &CachedObject, block: u64) -> (u64, u64) {
+        let start = block * self.block_size;
+        let end = (start + self.block_size).min(object.content_length) - 1;
+        (start, end)
+    }
+
+    /// Read `block` from disk, if it is cached with the expected length
+    async fn read_block(&self, object: &CachedObject, block: u64) -> Option<Bytes> {
+        let path = object.dir.join(block.to_string());
+        let data = tokio::fs::read(&path).await.ok()?;
+        let (start, end) = self.block_range(object, block);
+        if data.len() as u64 != end - start + 1 {
+            return None;
+        }
+
+        // Mark the block as recently used for eviction
+        let _ = tokio::task::spawn_blocking(move || {
+            std::fs::File::options()
+                .write(true)
+                .open(&path)?
+                .set_modified(SystemTime::now())
+        })
+        .await;
+        Some(Bytes::from(data))
+    }
+
+    async fn store(&self, dir: &Path, block: u64, data: &[u8]) -> std::io::Result<()> {
+        tokio::fs::create_dir_all(dir).await?;
+
+        // Write to a name unique to this process and store first, so concurrent readers
+        // never see a partial block and concurrent writers never share a temporary file
+        let tmp = dir.join(format!(
+            "{}.{}.{}.tmp",
+            block,
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        tokio::fs::write(&tmp, data).await?;
+        if let Err(e) = tokio::fs::rename(&tmp, dir.join(block.to_string())).await {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Err(e);
+        }
+
+        let mut size = self.size.lock().await;
+        let total = match *size {
+            Some(total) => total + data.len() as u64,
+            None => directory_size(&self.dir).await?,
+        };
+        *size = Some(if total > self.max_size {
+            evict(&self.dir, low_watermark(self.max_size)).await?
+        } else {
+            total
+        });
+        Ok(())
+    }
+
+    /// Fetch blocks `first..=last` with one request and cache each complete block
+    /// Returns fewer bytes than the blocks span if the source did
+    async fn fetch_blocks(&self, object: &CachedObject, first: u64, last: u64) -> Result<Bytes> {
+        let (start, _) = self.block_range(object, first);
+        let (_, end) = self.block_range(object, last);
+        let data = self.inner.get_range(start, end).await?;
+
+        // Caching is best effort and never fails the download
+        for block in first..=last {
+            let (block_start, block_end) = self.block_range(object, block);
+            let offset = (block_start - start) as usize;
+            let len = (block_end - block_start + 1) as usize;
+            let Some(block_data) = data.get(offset..offset + len) else {
+                break;
+            };
+            let _ = self.store(&object.dir, block, block_data).await;
+        }
+
+        Ok(data)
+    }
+}
+
+/// Directory name for the blocks of one version of a source
+/// A hash keeps the name safe for the filesystem without merging distinct ETags
+fn entry_dir(source: &str, etag: &str) -> String {
+    let mut hasher = Sha256::new();
+    // Length-prefix the source so no two (source, ETag) pairs hash the same input
+    hasher.update((source.len() as u64).to_le_bytes());
+    hasher.update(source.as_bytes());
+    hasher.update(etag.as_bytes());
+    to_hex(&hasher.finalize())
+}
+
+/// Weak ETags only promise equivalent content, not identical bytes
+fn is_weak(etag: &str) -> bool {
+    etag.starts_with("W/")
+}
+
+/// Size the cache is shrunk to once it exceeds `max_size`
+/// Staying a tenth below it keeps a full cache from being rescanned on every store
+fn low_watermark(max_size: u64) -> u64 {
+    max_size - max_size / 10
+}
+
+/// Whether `path` is a block being written by some store
+fn is_temporary(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "tmp")
+}
+
+/// Cached blocks with their last use and size
+async fn cached_blocks(dir: &Path) -> std::io::Result<Vec<(SystemTime, u64, PathBuf)>> {
+    let mut blocks = Vec::new();
+    let mut objects = match tokio::fs::read_dir(dir).await {
+        Ok(objects) => objects,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(blocks),
+        Err(e) => return Err(e),
+    };
+    while let Some(object) = objects.next_entry().await? {
+        if !object.file_type().await?.is_dir() {
+            continue;
+        }
+        let mut entries = tokio::fs::read_dir(object.path()).await?;
+        while let Some(block) = entries.next_entry().await? {
+            let path = block.path();
+            if is_temporary(&path) {
+                continue;
+            }
+            // Another process may evict the block between listing and reading it
+            let Ok(metadata) = block.metadata().await else {
+                continue;
+            };
+            blocks.push((
+                metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                metadata.len(),
+                path,
+            ));
+        }
+    }
+    Ok(blocks)
+}
+
+/// Total size of the cached blocks
+async fn directory_size(dir: &Path) -> std::io::Result<u64> {
+    Ok(cached_blocks(dir)
+        .await?
+        .iter()
+        .map(|(_, len, _)| len)
+        .sum())
+}
+
+/// Remove least recently used blocks until the cache fits into `target` bytes
+/// Returns the remaining size
+async fn evict(dir: &Path, target: u64) -> std::io::Result<u64> {
+    let mut blocks = cached_blocks(dir).await?;
+    let mut total: u64 = blocks.iter().map(|(_, len, _)| len).sum();
+
+    blocks.sort();
+    for (_, len, path) in blocks {
+        if total <= target {
+            break;
+        }
+        match tokio::fs::remove_file(path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        total -= len;
+    }
+
+    Ok(total)
+}
+
+#[async_trait]
+impl DownloadClient for CachingClient {
+    async fn head(&self) -> Result<ObjectMetadata> {
+        let metadata = self.inner.head().await?;
+        *self.object.lock().unwrap() =
+            metadata
+                .etag
+                .as_deref()
+                .filter(|etag| !is_weak(etag))
+                .map(|etag| CachedObject {
+                    dir: self.dir.join(entry_dir(&self.source, etag)),
+                    content_length: metadata.content_length,
+                });
+        Ok(metadata)
+    }
+
+    async fn get_range(&self, start: u64, end: u64) -> Result<Bytes> {
+        let object = self.object.lock().unwrap().clone();
+        let Some(object) = object.filter(|object| end < object.content_length) else {
+            return self.inner.get_range(start, end).await;
+        };
+
+        let first = start / self.block_size;
+        let last = end / self.block_size;
+        let mut cached = Vec::new();
+        for block in first..=last {
+            cached.push(self.read_block(&object, block).await);
+        }
+
+        // Fetch each run of missing blocks with a single request
+        let mut data = BytesMut::new();
+        let mut block = first;
+        while block <= last {
+            if let Some(bytes) = cached[(block - first) as usize].take() {
+                data.extend_from_slice(&bytes);
+                block += 1;
+                continue;
+            }
+            let mut run_end = block;
+            while run_end < last && cached[(run_end + 1 - first) as usize].is_none() {
+                run_end += 1;
+            }
+
+            let fetched = self.fetch_blocks(&object, block, run_end).await?;
+            data.extend_from_slice(&fetched);
+            let (run_start, run_last) = (
+                self.block_range(&object, block).0,
+                self.block_range(&object, run_end).1,
+            );
+            // A short response ends the range here; the caller requests the rest again
+            if (fetched.len() as u64) < run_last - run_start + 1 {
+                break;
+            }
+            block = run_end + 1;
+        }
+
+        let offset = ((start - first * self.block_size) as usize).min(data.len());
+        let len = ((end - start + 1) as usize).min(data.len().saturating_sub(offset));
+        Ok(data.freeze().slice(offset..offset + len))
+    }
+
+    async fn get_full(&self) -> Result<Bytes> {
+        self.inner.get_full().await
+    }
+
+    async fn part_size(&self, part_number: u32) -> Result<Option<u64>> {
+        self.inner.part_size(part_number).await
+    }
+
+    async fn get_part(&self, part_number: u32) -> Result<Bytes> {
+        self.inner.get_part(part_number).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingClient {
+        content: Bytes,
+        etag: Mutex<String>,
+        requests: AtomicUsize,
+        ranges: Mutex<Vec<(u64, u64)>>,
+    }
+
+    #[async_trait]
+    impl DownloadClient for CountingClient {
+        async fn head(&self) -> Result<ObjectMetadata> {
+            Ok(ObjectMetadata {
+                content_length: self.content.len() as u64,
+                supports_range: true,
+                etag: Some(self.etag.lock().unwrap().clone()),
+                content_type: None,
+                content_md5: None,
+                last_modified: None,
+            })
+        }
+
+        async fn get_range(&self, start: u64, end: u64) -> Result<Bytes> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            self.ranges.lock().unwrap().push((start, end));
+            Ok(self.content.slice(start as usize..=end as usize))
+        }
+
+        async fn get_full(&self) -> Result<Bytes> {
+            Ok(self.content.clone())
+        }
+    }
+
+    fn counting_client(etag: &str) -> Arc<CountingClient> {
+        Arc::new(CountingClient {
+            content: Bytes::from((0..1000).map(|i| (i % 256) as u8).collect::<Vec<u8>>()),
+            etag: Mutex::new(etag.to_string()),
+            requests: AtomicUsize::new(0),
+            ranges: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn caching_client(
+        inner: Arc<CountingClient>,
+        source: &str,
+        dir: &Path,
+        max_size: u64,
+    ) -> CachingClient {
+        CachingClient::new(inner, source.to_string(), dir.to_path_buf(), max_size)
+            .with_block_size(100)
+    }
+
+    #[tokio::test]
+    async fn test_cache_serves_repeated_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = counting_client("\"abc\"");
+        let client = caching_client(inner.clone(), "s3://bucket/key", dir.path(), 1_000_000);
+
+        client.head().await.unwrap();
+        let first = client.get_range(10, 19).await.unwrap();
+        let second = client.get_range(10, 19).await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first, inner.content.slice(10..20));
+        assert_eq!(inner.requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cache_fetches_only_missing_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = counting_client("\"abc\"");
+        let client = caching_client(inner.clone(), "s3://bucket/key", dir.path(), 1_000_000);
+
+        client.head().await.unwrap();
+        client.get_range(0, 249).await.unwrap();
+        // Overlapping, but not identical to the first range
+        let overlapping = client.get_range(50, 199).await.unwrap();
+        // As a download with a different chunk size would request
+        let shifted = client.get_range(200, 399).await.unwrap();
+        // Up to the shorter last block
+        let tail = client.get_range(950, 999).await.unwrap();
+
+        assert_eq!(overlapping, inner.content.slice(50..200));
+        assert_eq!(shifted, inner.content.slice(200..400));
+        assert_eq!(tail, inner.content.slice(950..1000));
+        assert_eq!(
+            *inner.ranges.lock().unwrap(),
+            vec![(0, 299), (300, 399), (900, 999)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cache_invalidates_on_etag_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = counting_client("\"abc\"");
+        let client = caching_client(inner.clone(), "s3://bucket/key", dir.path(), 1_000_000);
+
+        client.head().await.unwrap();
+        client.get_range(0, 9).await.unwrap();
+
+        *inner.etag.lock().unwrap() = "\"def\"".to_string();
+        client.head().await.unwrap();
+        client.get_range(0, 9).await.unwrap();
+
+        assert_eq!(inner.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cache_evicts_to_max_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = counting_client("\"abc\"");
+        let client = caching_client(inner.clone(), "s3://bucket/key", dir.path(), 250);
+
+        client.head().await.unwrap();
+        for i in 0..5 {
+            client.get_range(i * 100, i * 100 + 99).await.unwrap();
+        }
+
+        let mut total = 0;
+        let mut entries =
+            std::fs::read_dir(dir.path().join(entry_dir("s3://bucket/key", "\"abc\""))).unwrap();
+        while let Some(Ok(entry)) = entries.next() {
+            total += entry.metadata().unwrap().len();
+        }
+        assert!(total <= 250);
+    }
+
+    #[tokio::test]
+    async fn test_cache_separates_sources_with_the_same_etag() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = counting_client("\"1700000000-1000\"");
+        let second = Arc::new(CountingClient {
+            content: Bytes::from(vec![0xff; 1000]),
+            etag: Mutex::new("\"1700000000-1000\"".to_string()),
+            requests: AtomicUsize::new(0),
+            ranges: Mutex::new(Vec::new()),
+        });
+
+        let client = caching_client(first.clone(), "https://a.example/f", dir.path(), 1_000_000);
+        client.head().await.unwrap();
+        client.get_range(0, 9).await.unwrap();
+
+        let client = caching_client(second.clone(), "https://b.example/f", dir.path(), 1_000_000);
+        client.head().await.unwrap();
+        let data = client.get_range(0, 9).await.unwrap();
+
+        assert_eq!(data, second.content.slice(0..10));
+        assert_eq!(second.requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cache_separates_etags_with_the_same_safe_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = counting_client("\"a.b\"");
+        let client = caching_client(inner.clone(), "s3://bucket/key", dir.path(), 1_000_000);
+
+        client.head().await.unwrap();
+        client.get_range(0, 9).await.unwrap();
+
+        for etag in ["\"a+b\"", "\"a_b\""] {
+            *inner.etag.lock().unwrap() = etag.to_string();
+            client.head().await.unwrap();
+            client.get_range(0, 9).await.unwrap();
+        }
+
+        assert_eq!(inner.requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_cache_skips_weak_etags() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = counting_client("W/\"abc\"");
+        let client = caching_client(inner.clone(), "s3://bucket/key", dir.path(), 1_000_000);
+
+        client.head().await.unwrap();
+        client.get_range(0, 9).await.unwrap();
+        client.get_range(0, 9).await.unwrap();
+
+        assert_eq!(inner.requests.load(Ordering::SeqCst), 2);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -1,10 +1,13 @@
 use bytes::Bytes;
+use serde::Deserialize;
 
 #[derive(Debug, Clone)]
 pub struct Chunk {
     pub index: usize,
     pub start: u64,
     pub end: u64,
+    /// Fetch the chunk as this part of the original multipart upload instead of by range
+    pub part_number: Option<u32>,
 }
 
 #[derive(Debug)]
@@ -13,15 +16,40 @@ pub struct DownloadedChunk {
     pub data: Bytes,
 }
 
+/// Smallest chunk size `--auto-chunk` shrinks to, so a file isn't split into many tiny requests
+pub const MIN_AUTO_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Most chunks `--auto-chunk` shrinks the chunk size for, however high the concurrency
+pub const MAX_AUTO_CHUNKS: usize = 64;
+
+/// Chunk size that splits `content_length` bytes into at least one chunk per worker
+/// (up to `MAX_AUTO_CHUNKS`), never larger than `chunk_size` and never smaller than
+/// `MIN_AUTO_CHUNK_SIZE` unless `chunk_size` itself is
+pub fn auto_chunk_size(content_length: u64, chunk_size: usize, concurrency: usize) -> usize {
+    let chunks = concurrency.clamp(1, MAX_AUTO_CHUNKS) as u64;
+    let fitted = usize::try_from(content_length.div_ceil(chunks)).unwrap_or(usize::MAX);
+    fitted.max(MIN_AUTO_CHUNK_SIZE).min(chunk_size)
+}
+
 /// Create chunks from content length and chunk size
+/// The chunks tile `[0, content_length)` exactly; a zero chunk size is treated as one byte
 pub fn create_chunks(content_length: u64, chunk_size: usize) -> Vec<Chunk> {
+    let span = (chunk_size as u64).max(1);
     let mut chunks = Vec::new();
     let mut start = 0u64;
     let mut index = 0;
 
+    // `start < content_length` keeps `content_length - 1` from underflowing, and
+    // saturating keeps huge chunk sizes from overflowing past the last byte
     while start < content_length {
-        let end = (start + chunk_size as u64 - 1).min(content_length - 1);
-        chunks.push(Chunk { index, start, end });
+        let end = start.saturating_add(span - 1).min(content_length - 1);
+        debug_assert!(start <= end, "empty chunk {}-{}", start, end);
+        chunks.push(Chunk {
+            index,
+            start,
+            end,
+            part_number: None,
+        });
         start = end + 1;
         index += 1;
     }
@@ -29,10 +57,191 @@ pub fn create_chunks(content_length: u64, chunk_size: usize) -> Vec<Chunk> {
     chunks
 }
 
+/// Inclusive byte interval requested with `--range start-end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// Parse a `start-end` spec where both bounds are inclusive byte offsets
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (start, end) = s
+            .trim()
+            .split_once('-')
+            .ok_or_else(|| format!("Invalid range '{}': expected start-end", s))?;
+        let start: u64 = start
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid range start in '{}'", s))?;
+        let end: u64 = end
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid range end in '{}'", s))?;
+        if start > end {
+            return Err(format!("Invalid range '{}': start is after end", s));
+        }
+        Ok(Self { start, end })
+    }
+
+    /// Number of bytes covered by the range
+    pub fn size(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Range from `--start-byte`/`--end-byte`, where a missing start means the
+    /// first byte and a missing end means the last byte of the object
+    pub fn open_ended(
+        start: Option<u64>,
+        end: Option<u64>,
+        content_length: u64,
+    ) -> Result<Self, String> {
+        let start = start.unwrap_or(0);
+        let Some(last) = content_length.checked_sub(1) else {
+            return Err("The object is empty".to_string());
+        };
+        let end = end.unwrap_or(last);
+
+        if start > end {
+            return Err(format!("Start byte {} is after end byte {}", start, end));
+        }
+        if end > last {
+            return Err(format!(
+                "Byte {} is outside the object ({} bytes)",
+                end, content_length
+            ));
+        }
+        Ok(Self { start, end })
+    }
+}
+
+/// Create chunks covering only the requested ranges, in the order given
+///
+/// Ranges must lie within the object and must not overlap each other.
+pub fn create_range_chunks(
+    ranges: &[ByteRange],
+    content_length: u64,
+    chunk_size: usize,
+) -> Result<Vec<Chunk>, String> {
+    for (i, range) in ranges.iter().enumerate() {
+        if range.end >= content_length {
+            return Err(format!(
+                "Range {}-{} is outside the object ({} bytes)",
+                range.start, range.end, content_length
+            ));
+        }
+        if let Some(other) = ranges[..i]
+            .iter()
+            .find(|other| range.start <= other.end && other.start <= range.end)
+        {
+            return Err(format!(
+                "Range {}-{} overlaps range {}-{}",
+                range.start, range.end, other.start, other.end
+            ));
+        }
+    }
+
+    let mut chunks = Vec::new();
+    for range in ranges {
+        for chunk in create_chunks(range.size(), chunk_size) {
+            chunks.push(Chunk {
+                index: chunks.len(),
+                start: range.start + chunk.start,
+                end: range.start + chunk.end,
+                part_number: None,
+            });
+        }
+    }
+
+    Ok(chunks)
+}
+
+/// Parse a range index such as `[{"start": 0, "end": 99}, …]`, as produced by tools
+/// that read file footers
+///
+/// The index must list at least one range, in ascending order and without overlaps.
+pub fn parse_range_index(json: &str) -> Result<Vec<ByteRange>, String> {
+    let ranges: Vec<ByteRange> =
+        serde_json::from_str(json).map_err(|e| format!("Invalid range index: {}", e))?;
+
+    if ranges.is_empty() {
+        return Err("The range index lists no ranges".to_string());
+    }
+    if let Some(range) = ranges.iter().find(|range| range.start > range.end) {
+        return Err(format!(
+            "Invalid range {}-{}: start is after end",
+            range.start, range.end
+        ));
+    }
+    if let Some(pair) = ranges.windows(2).find(|pair| pair[1].start <= pair[0].end) {
+        return Err(format!(
+            "Range {}-{} must start after range {}-{}; ranges must be ascending and must not overlap",
+            pair[1].start, pair[1].end, pair[0].start, pair[0].end
+        ));
+    }
+
+    Ok(ranges)
+}
+
+/// Number of parts encoded in a multipart upload ETag such as `"…-12"`
+pub fn multipart_part_count(etag: &str) -> Option<u32> {
+    let (_, count) = etag.trim_matches('"').rsplit_once('-')?;
+    count.parse().ok().filter(|&count| count > 0)
+}
+
+/// Create one chunk per part of a multipart upload, requested by part number
+///
+/// Parts before the last are assumed to be `part_size` bytes, as uploaded by the AWS
+/// SDKs and CLI. Returns `None` if `content_length` doesn't fit that layout.
+pub fn create_part_chunks(
+    content_length: u64,
+    part_count: u32,
+    part_size: u64,
+) -> Option<Vec<Chunk>> {
+    let leading = part_size.checked_mul(u64::from(part_count.checked_sub(1)?))?;
+    let last = content_length.checked_sub(leading)?;
+    if part_size == 0 || last == 0 || last > part_size {
+        return None;
+    }
+
+    let chunks = create_chunks(content_length, part_size as usize)
+        .into_iter()
+        .map(|chunk| Chunk {
+            part_number: Some(chunk.index as u32 + 1),
+            ..chunk
+        })
+        .collect();
+    Some(chunks)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_auto_chunk_size() {
+        const MIB: usize = 1024 * 1024;
+
+        // A 3MiB file with 8MiB chunks and 10 workers is cut down to the floor
+        assert_eq!(auto_chunk_size(3 * MIB as u64, 8 * MIB, 10), MIB);
+        // A 40MiB file gets one 4MiB chunk per worker
+        assert_eq!(auto_chunk_size(40 * MIB as u64, 8 * MIB, 10), 4 * MIB);
+        // Large files keep the configured size
+        assert_eq!(auto_chunk_size(1024 * MIB as u64, 8 * MIB, 10), 8 * MIB);
+        // Rounded up, so there are never more chunks than workers
+        assert_eq!(
+            auto_chunk_size(10 * MIB as u64 + 1, 8 * MIB, 5),
+            2 * MIB + 1
+        );
+        // High concurrency stops shrinking at MAX_AUTO_CHUNKS chunks
+        assert_eq!(auto_chunk_size(128 * MIB as u64, 8 * MIB, 1000), 2 * MIB);
+        // Chunks already below the floor are left alone
+        assert_eq!(auto_chunk_size(3 * MIB as u64, 512 * 1024, 10), 512 * 1024);
+        assert_eq!(auto_chunk_size(0, 8 * MIB, 0), MIB);
+    }
+
     #[test]
     fn test_create_chunks_empty_file() {
         let chunks = create_chunks(0, 100);
@@ -74,4 +283,170 @@ mod tests {
         assert_eq!(chunks[0].start, 0);
         assert_eq!(chunks[0].end, 49);
     }
+
+    /// Check that the chunks cover every byte once, in order, with consecutive indices
+    fn assert_tiles(content_length: u64, chunk_size: usize) {
+        let chunks = create_chunks(content_length, chunk_size);
+        let mut next = 0;
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.index, i);
+            assert_eq!(chunk.start, next, "gap or overlap before chunk {}", i);
+            assert!(chunk.start <= chunk.end, "empty chunk {}", i);
+            assert!(chunk.end - chunk.start < (chunk_size as u64).max(1));
+            next = chunk.end + 1;
+        }
+        assert_eq!(next, content_length);
+    }
+
+    #[test]
+    fn test_create_chunks_edge_sizes() {
+        // Chunk size equal to the length gives a single chunk
+        let chunks = create_chunks(100, 100);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!((chunks[0].start, chunks[0].end), (0, 99));
+
+        // One-byte chunks
+        let chunks = create_chunks(5, 1);
+        assert_eq!(chunks.len(), 5);
+        assert!(chunks.iter().all(|c| c.start == c.end));
+
+        // Very large chunk sizes neither overflow nor exceed the object
+        let chunks = create_chunks(u64::MAX, usize::MAX);
+        assert_eq!((chunks[0].start, chunks[0].end), (0, u64::MAX - 1));
+        assert_eq!(create_chunks(1, usize::MAX).len(), 1);
+
+        // A zero chunk size degrades to one-byte chunks instead of underflowing
+        assert_eq!(create_chunks(3, 0).len(), 3);
+        assert!(create_chunks(0, 0).is_empty());
+    }
+
+    #[test]
+    fn test_create_chunks_tile_the_object() {
+        for content_length in [0, 1, 2, 7, 99, 100, 101, 1000, 1023, 1024, 1025] {
+            for chunk_size in [1, 2, 3, 64, 99, 100, 101, 1024, 4096] {
+                assert_tiles(content_length, chunk_size);
+            }
+        }
+        assert_tiles(10 * 1024 * 1024 + 3, 8 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_parse_byte_range() {
+        assert_eq!(
+            ByteRange::parse("0-99").unwrap(),
+            ByteRange { start: 0, end: 99 }
+        );
+        assert_eq!(ByteRange::parse("5-5").unwrap().size(), 1);
+        assert!(ByteRange::parse("10-5").is_err());
+        assert!(ByteRange::parse("10").is_err());
+        assert!(ByteRange::parse("-5").is_err());
+        assert!(ByteRange::parse("a-b").is_err());
+    }
+
+    #[test]
+    fn test_open_ended_range() {
+        let range = ByteRange::open_ended(Some(900), None, 1000).unwrap();
+        assert_eq!(
+            range,
+            ByteRange {
+                start: 900,
+                end: 999
+            }
+        );
+        let range = ByteRange::open_ended(None, Some(99), 1000).unwrap();
+        assert_eq!(range, ByteRange { start: 0, end: 99 });
+
+        assert!(ByteRange::open_ended(Some(1000), None, 1000).is_err());
+        assert!(ByteRange::open_ended(None, Some(1000), 1000).is_err());
+        assert!(ByteRange::open_ended(Some(10), Some(5), 1000).is_err());
+        assert!(ByteRange::open_ended(None, None, 0).is_err());
+    }
+
+    #[test]
+    fn test_create_range_chunks_keeps_given_order() {
+        let ranges = [
+            ByteRange {
+                start: 900,
+                end: 949,
+            },
+            ByteRange { start: 0, end: 149 },
+        ];
+        let chunks = create_range_chunks(&ranges, 1000, 100).unwrap();
+        let bounds: Vec<_> = chunks.iter().map(|c| (c.index, c.start, c.end)).collect();
+        assert_eq!(bounds, vec![(0, 900, 949), (1, 0, 99), (2, 100, 149)]);
+    }
+
+    #[test]
+    fn test_create_range_chunks_rejects_invalid_ranges() {
+        let overlapping = [
+            ByteRange { start: 0, end: 99 },
+            ByteRange {
+                start: 50,
+                end: 149,
+            },
+        ];
+        assert!(create_range_chunks(&overlapping, 1000, 100).is_err());
+
+        let out_of_bounds = [ByteRange {
+            start: 900,
+            end: 1000,
+        }];
+        assert!(create_range_chunks(&out_of_bounds, 1000, 100).is_err());
+    }
+
+    #[test]
+    fn test_parse_range_index() {
+        let ranges =
+            parse_range_index(r#"[{"start": 0, "end": 99}, {"start": 500, "end": 599}]"#).unwrap();
+        assert_eq!(
+            ranges,
+            vec![
+                ByteRange { start: 0, end: 99 },
+                ByteRange {
+                    start: 500,
+                    end: 599
+                }
+            ]
+        );
+
+        assert!(parse_range_index("[]").is_err());
+        assert!(parse_range_index(r#"[{"start": 9, "end": 0}]"#).is_err());
+        assert!(parse_range_index(r#"[{"start": 0, "end": 9, "len": 10}]"#).is_err());
+        // Out of order and overlapping
+        assert!(
+            parse_range_index(r#"[{"start": 500, "end": 599}, {"start": 0, "end": 99}]"#).is_err()
+        );
+        assert!(
+            parse_range_index(r#"[{"start": 0, "end": 99}, {"start": 99, "end": 199}]"#).is_err()
+        );
+    }
+
+    #[test]
+    fn test_multipart_part_count() {
+        assert_eq!(
+            multipart_part_count("\"d41d8cd98f00b204e9800998ecf8427e-12\""),
+            Some(12)
+        );
+        assert_eq!(
+            multipart_part_count("\"d41d8cd98f00b204e9800998ecf8427e\""),
+            None
+        );
+        assert_eq!(multipart_part_count("\"abc-0\""), None);
+        assert_eq!(multipart_part_count("\"abc-x\""), None);
+    }
+
+    #[test]
+    fn test_create_part_chunks() {
+        let chunks = create_part_chunks(250, 3, 100).unwrap();
+        let parts: Vec<_> = chunks
+            .iter()
+            .map(|c| (c.part_number.unwrap(), c.start, c.end))
+            .collect();
+        assert_eq!(parts, vec![(1, 0, 99), (2, 100, 199), (3, 200, 249)]);
+
+        // Too short or too long for three parts of 100 bytes
+        assert!(create_part_chunks(200, 3, 100).is_none());
+        assert!(create_part_chunks(301, 3, 100).is_none());
+        assert!(create_part_chunks(100, 0, 100).is_none());
+    }
 }
@@ -1,11 +1,99 @@
-use clap::{Args, Parser, Subcommand};
+use crate::chunk::ByteRange;
+use crate::events::DownloadListener;
+use crate::http_client::{HeadMethod, HeaderArg};
+use crate::progress::{validate_template, BarTemplate, DEFAULT_PROGRESS_DELAY};
+use crate::ratelimit::RequestRateLimiter;
+use crate::resolve::ResolveOverride;
+use crate::template::OutputTemplate;
+use crate::timings::Timings;
+use crate::verify::DownloadStats;
+use clap::builder::BoolishValueParser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Printed by `--version`, with the details a bug report needs to pin down the build
+pub const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "\ncommit:     ",
+    env!("S3FCP_GIT_HASH"),
+    "\nbuilt:      ",
+    env!("S3FCP_BUILD_DATE"),
+    "\ntarget:     ",
+    env!("S3FCP_TARGET"),
+    "\naws-sdk-s3: ",
+    env!("S3FCP_AWS_SDK_S3_VERSION"),
+    "\naws-config: ",
+    env!("S3FCP_AWS_CONFIG_VERSION"),
+    "\nreqwest:    ",
+    env!("S3FCP_REQWEST_VERSION"),
+);
 
 #[derive(Parser, Debug)]
-#[command(name = "s3fcp")]
+#[command(name = "s3fcp", version, long_version = LONG_VERSION)]
 #[command(about = "Fast file downloader with multi-part support", long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Command,
+
+    /// Format used to report errors on stderr
+    #[arg(
+        long,
+        env = "S3FCP_ERROR_FORMAT",
+        global = true,
+        value_enum,
+        default_value_t = ErrorFormat::Text
+    )]
+    pub error_format: ErrorFormat,
+
+    /// TOML file with default options; flags and S3FCP_* variables take precedence
+    #[arg(long, env = "S3FCP_CONFIG", global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// Human-readable message
+    #[default]
+    Text,
+    /// Single-line JSON object with error kind, message and exit code
+    Json,
+}
+
+/// Format of the `--timings` breakdown
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingsFormat {
+    /// One line per phase and per worker
+    Text,
+    /// Single-line JSON object with durations in seconds
+    Json,
+}
+
+/// Format the output is compressed into while it is written
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+/// How `--text-normalize` rewrites a downloaded text file
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextNormalize {
+    /// Convert CRLF line endings to LF
+    Lf,
+    /// Drop a single newline (LF or CRLF) at the end of the file
+    StripTrailingNewline,
+}
+
+/// What a download of many objects does when one of them fails
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnError {
+    /// Stop at the first failed object
+    #[default]
+    Abort,
+    /// Download the remaining objects and report the failed ones at the end
+    Continue,
 }
 
 #[derive(Subcommand, Debug)]
@@ -14,6 +102,14 @@ pub enum Command {
     S3(S3Args),
     /// Download from HTTP/HTTPS URL
     Http(HttpArgs),
+    /// Copy a local file to S3 using a concurrent multipart upload
+    Cp(CpArgs),
+    /// Print a time-limited presigned GET URL for an S3 object
+    Presign(PresignArgs),
+    /// Download every S3 URI or HTTP URL in a newline-separated list into a directory
+    Batch(BatchArgs),
+    /// Check that an S3 bucket, S3 object or HTTP URL can be reached, and how fast
+    Ping(PingArgs),
 }
 
 #[derive(Args, Debug, Clone)]
@@ -25,17 +121,388 @@ pub struct S3Args {
     #[arg(long)]
     pub version_id: Option<String>,
 
+    /// Download the version N steps before the newest one (0 is the newest)
+    #[arg(long, value_name = "N", conflicts_with_all = ["version_id", "recursive"])]
+    pub versions_ago: Option<usize>,
+
+    /// Print the object's stored versions (ID, size, last modified) instead of downloading
+    #[arg(long, conflicts_with_all = ["version_id", "versions_ago", "recursive"])]
+    pub list_versions: bool,
+
+    /// Print the --list-versions output as JSON
+    #[arg(long, requires = "list_versions")]
+    pub json: bool,
+
+    /// Accept bucket names that don't follow the AWS naming rules
+    #[arg(long, env = "S3FCP_SKIP_BUCKET_VALIDATION", value_parser = BoolishValueParser::new())]
+    pub skip_bucket_validation: bool,
+
+    /// Send requests without credentials, for public buckets
+    #[arg(long, env = "S3FCP_NO_SIGN_REQUEST", value_parser = BoolishValueParser::new())]
+    pub no_sign_request: bool,
+
+    /// Use the dual-stack (IPv4 and IPv6) S3 endpoint; an explicit endpoint URL takes precedence
+    #[arg(long, env = "S3FCP_DUALSTACK", value_parser = BoolishValueParser::new())]
+    pub dualstack: bool,
+
+    /// Use the FIPS 140-validated S3 endpoint; an explicit endpoint URL takes precedence
+    #[arg(long, env = "S3FCP_FIPS", value_parser = BoolishValueParser::new())]
+    pub fips: bool,
+
+    /// Use S3 Transfer Acceleration, which must be enabled on the bucket; an explicit endpoint URL takes precedence
+    #[arg(
+        long,
+        env = "S3FCP_ACCELERATE",
+        value_parser = BoolishValueParser::new(),
+        conflicts_with = "fips"
+    )]
+    pub accelerate: bool,
+
+    /// Assume --role-arn with the web identity token in this file (e.g. an EKS service account token)
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "role_arn",
+        conflicts_with = "no_sign_request"
+    )]
+    pub web_identity_token_file: Option<PathBuf>,
+
+    /// Role to assume with --web-identity-token-file instead of the default credential chain
+    #[arg(long, value_name = "ARN", requires = "web_identity_token_file")]
+    pub role_arn: Option<String>,
+
+    /// Write to this file instead of stdout (the target directory with --recursive or a key pattern)
+    #[arg(short = 'o', long)]
+    pub output: Option<PathBuf>,
+
+    /// Write to stdout even if it is a terminal and the object isn't text
+    #[arg(long)]
+    pub force: bool,
+
+    /// Download every object below the URI's key prefix into the --output directory
+    #[arg(short = 'r', long, requires = "output", conflicts_with = "version_id")]
+    pub recursive: bool,
+
+    /// Treat *, ? and [ in the key literally instead of downloading all matching keys
+    #[arg(long, conflicts_with = "recursive")]
+    pub no_glob: bool,
+
+    /// Also write the object's tags to this file as JSON
+    #[arg(long, value_name = "PATH", conflicts_with = "recursive")]
+    pub save_tags: Option<PathBuf>,
+
+    /// Also write the object's content type, encoding, cache-control and user metadata to this file as JSON
+    #[arg(long, value_name = "PATH", conflicts_with = "recursive")]
+    pub save_metadata: Option<PathBuf>,
+
+    /// Content-Type S3 should send for the object instead of the stored one
+    #[arg(long, value_name = "TYPE", conflicts_with = "recursive")]
+    pub response_content_type: Option<String>,
+
+    /// Content-Disposition S3 should send instead of the stored one
+    #[arg(long, value_name = "VALUE", conflicts_with = "recursive")]
+    pub response_content_disposition: Option<String>,
+
+    /// Continue an interrupted recursive download from its saved listing position
+    #[arg(long, requires = "recursive")]
+    pub resume_listing: bool,
+
+    /// Verify each downloaded file against this `sha256sum` manifest of `<hexdigest>  <filename>` lines
+    #[arg(long, value_name = "PATH", requires = "recursive")]
+    pub checksum_file: Option<PathBuf>,
+
+    /// Send a HEAD request per object instead of planning it from the listed size and ETag
+    #[arg(long, requires = "recursive")]
+    pub head_each_object: bool,
+
+    /// List the objects that would be downloaded, with their sizes and paths, without downloading
+    #[arg(long, requires = "recursive")]
+    pub dry_run: bool,
+
+    /// With --recursive or a key pattern, whether a failed object stops the download
+    #[arg(long, env = "S3FCP_ON_ERROR", value_enum, default_value_t = OnError::Abort)]
+    pub on_error: OnError,
+
+    /// With --recursive or a key pattern, lay out the files by this template instead of
+    /// mirroring the keys: {key}, {path}, {basename}, {stem}, {ext}, {bucket}, {year}, {month}, {day}
+    #[arg(long, value_name = "TEMPLATE", value_parser = OutputTemplate::parse, requires = "output")]
+    pub output_template: Option<OutputTemplate>,
+
+    /// Download only if the object's Last-Modified is newer than the local file's mtime
+    #[arg(long, requires = "output", conflicts_with_all = ["list_versions", "split_size"])]
+    pub overwrite_if_newer: bool,
+
+    /// Set the downloaded file's mtime to the object's Last-Modified
+    #[arg(long, requires = "output", conflicts_with_all = ["list_versions", "split_size"])]
+    pub preserve_mtime: bool,
+
     /// Number of concurrent download workers
-    #[arg(short = 'c', long, default_value = "10")]
+    #[arg(
+        short = 'c',
+        long,
+        env = "S3FCP_CONCURRENCY",
+        default_value = "10",
+        value_parser = parse_concurrency
+    )]
     pub concurrency: usize,
 
-    /// Chunk size (supports human-readable sizes: 8MB, 16MiB, 1GB, etc.)
-    #[arg(long, default_value = "8MB", value_parser = parse_chunk_size)]
-    pub chunk_size: usize,
+    /// Start with a few workers and add more while throughput rises, up to --concurrency
+    #[arg(
+        long,
+        env = "S3FCP_ADAPTIVE_CONCURRENCY",
+        value_parser = BoolishValueParser::new(),
+        conflicts_with = "single_stream"
+    )]
+    pub adaptive_concurrency: bool,
+
+    /// Chunk size (supports human-readable sizes: 8MB, 16MiB, 1GB, etc., or a percentage of the object such as 5%)
+    #[arg(
+        long,
+        env = "S3FCP_CHUNK_SIZE",
+        default_value = "8MB",
+        value_parser = parse_download_chunk_size
+    )]
+    pub chunk_size: ChunkSize,
+
+    /// Use smaller chunks (down to 1MiB) when the object is too small to give every worker one
+    #[arg(long, env = "S3FCP_AUTO_CHUNK", value_parser = BoolishValueParser::new())]
+    pub auto_chunk: bool,
+
+    /// Download at most N chunks ahead of the one being written, bounding buffered memory
+    #[arg(
+        long,
+        env = "S3FCP_REORDER_WINDOW",
+        value_name = "N",
+        value_parser = parse_concurrency,
+        conflicts_with = "single_stream"
+    )]
+    pub reorder_window: Option<usize>,
+
+    /// Fail if a chunk reaches the writer twice, out of range, or not at all, to catch pipeline bugs
+    #[arg(long, conflicts_with = "single_stream")]
+    pub strict_ordering: bool,
 
     /// Quiet mode - suppress progress output
-    #[arg(short = 'q', long)]
+    #[arg(short = 'q', long, env = "S3FCP_QUIET", value_parser = BoolishValueParser::new())]
     pub quiet: bool,
+
+    /// Wait this long before showing the progress bar (e.g. 0, 200ms, 1s)
+    #[arg(
+        long,
+        env = "S3FCP_PROGRESS_DELAY",
+        value_name = "DELAY",
+        default_value = "200ms",
+        value_parser = parse_duration
+    )]
+    pub progress_delay: Duration,
+
+    /// Also show completed/total chunks and in-flight chunks on the progress bar
+    #[arg(long, env = "S3FCP_PROGRESS_DETAIL", value_parser = BoolishValueParser::new())]
+    pub progress_detail: bool,
+
+    /// Leave the completed progress bar on screen instead of clearing it
+    #[arg(long, env = "S3FCP_KEEP_PROGRESS", value_parser = BoolishValueParser::new())]
+    pub keep_progress: bool,
+
+    /// Hide the progress bar but print one line with the size, duration and rate when done
+    #[arg(long, conflicts_with = "quiet")]
+    pub summary_only: bool,
+
+    /// Progress bar layout as an indicatif template, e.g. "{bytes}/{total_bytes} {bytes_per_sec}"
+    #[arg(
+        long,
+        env = "S3FCP_PROGRESS_TEMPLATE",
+        value_name = "TEMPLATE",
+        value_parser = parse_progress_template,
+        conflicts_with = "no_eta"
+    )]
+    pub progress_template: Option<String>,
+
+    /// Leave the estimated time remaining off the progress bar
+    #[arg(
+        long,
+        visible_alias = "no-progress-eta",
+        env = "S3FCP_NO_ETA",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub no_eta: bool,
+
+    /// Download the whole object in a single request, even if ranges are supported
+    #[arg(long, visible_alias = "force-single")]
+    pub single_stream: bool,
+
+    /// Normalize the line endings of a small text file; requires --single-stream
+    #[arg(long, value_enum, value_name = "MODE", requires = "single_stream")]
+    pub text_normalize: Option<TextNormalize>,
+
+    /// Request the parts of a multipart-uploaded object by part number instead of byte ranges
+    #[arg(long, conflicts_with_all = ["single_stream", "ranges", "ranges_file"])]
+    pub by_part: bool,
+
+    /// Compress the output with gzip or zstd while writing it
+    #[arg(long, value_enum, conflicts_with = "recursive")]
+    pub compress: Option<Compression>,
+
+    /// Flush written data at least this often, so readers of a growing file see progress (0 disables)
+    #[arg(
+        long,
+        env = "S3FCP_FLUSH_INTERVAL",
+        value_name = "INTERVAL",
+        default_value = "5s",
+        value_parser = parse_duration
+    )]
+    pub flush_interval: Duration,
+
+    /// Buffer this much output before writing it to the file or stdout
+    #[arg(
+        long,
+        env = "S3FCP_WRITE_BUFFER_SIZE",
+        value_name = "SIZE",
+        default_value = "256KiB",
+        value_parser = parse_size
+    )]
+    pub write_buffer_size: usize,
+
+    /// Retry a failing chunk with growing backoff for up to this long (e.g. 10m) instead of 3 times
+    #[arg(
+        long,
+        env = "S3FCP_RETRY_DEADLINE",
+        value_name = "DURATION",
+        value_parser = parse_duration
+    )]
+    pub retry_deadline: Option<Duration>,
+
+    /// Fail and retry a request once no data has arrived for this long (e.g. 30s), so a
+    /// stalled connection doesn't hang the download
+    #[arg(
+        long,
+        env = "S3FCP_STALL_TIMEOUT",
+        value_name = "DURATION",
+        value_parser = parse_duration
+    )]
+    pub stall_timeout: Option<Duration>,
+
+    /// Fail if the object's size isn't known within this long (e.g. 5s), retries included
+    #[arg(
+        long,
+        env = "S3FCP_HEAD_TIMEOUT",
+        value_name = "DURATION",
+        value_parser = parse_duration
+    )]
+    pub head_timeout: Option<Duration>,
+
+    /// Fail before downloading anything unless the object is exactly this size (e.g. 1048576 or 1MiB)
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_expected_size,
+        conflicts_with = "recursive"
+    )]
+    pub expected_size: Option<u64>,
+
+    /// Start at most this many requests per second across all workers, to avoid S3 SlowDown
+    #[arg(
+        long,
+        visible_alias = "limit-requests-per-second",
+        env = "S3FCP_MAX_RPS",
+        value_name = "N",
+        value_parser = parse_rps
+    )]
+    pub max_rps: Option<f64>,
+
+    /// Sync the output file to disk before exiting
+    #[arg(long, requires = "output")]
+    pub fsync: bool,
+
+    /// Write straight to the output file instead of renaming a complete temporary file into place
+    #[arg(long, requires = "output")]
+    pub no_atomic: bool,
+
+    /// When the disk fills up, wait for space to be freed and keep writing instead of failing
+    #[arg(long, requires = "output", conflicts_with = "split_size")]
+    pub wait_on_diskfull: bool,
+
+    /// Print the number of bytes written to the output to stderr when done
+    #[arg(long, conflicts_with = "recursive")]
+    pub report_bytes: bool,
+
+    /// Print how long the HEAD request, first chunk, transfer, each worker and the chunks took to stderr
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        default_missing_value = "text",
+        conflicts_with = "recursive"
+    )]
+    pub timings: Option<TimingsFormat>,
+
+    /// Write the output as OUTPUT.000, OUTPUT.001, … of at most this size each
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_split_size,
+        requires = "output",
+        conflicts_with = "recursive"
+    )]
+    pub split_size: Option<u64>,
+
+    /// Download only this inclusive byte range; repeat to concatenate several in the given order
+    #[arg(
+        long = "range",
+        value_name = "START-END",
+        value_parser = ByteRange::parse,
+        conflicts_with_all = ["single_stream", "recursive"]
+    )]
+    pub ranges: Vec<ByteRange>,
+
+    /// Download the ranges listed in this JSON index, e.g. [{"start": 0, "end": 99}], concatenated
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["ranges", "start_byte", "end_byte", "single_stream", "recursive"]
+    )]
+    pub ranges_file: Option<PathBuf>,
+
+    /// Download from this byte offset to the end of the object
+    #[arg(long, value_name = "N", conflicts_with_all = ["ranges", "single_stream", "recursive"])]
+    pub start_byte: Option<u64>,
+
+    /// Download from the start of the object up to and including this byte offset
+    #[arg(long, value_name = "N", conflicts_with_all = ["ranges", "single_stream", "recursive"])]
+    pub end_byte: Option<u64>,
+
+    /// Connect to ADDR for requests to HOST, like curl (repeatable; S3 ignores PORT)
+    #[arg(long, value_name = "HOST:PORT:ADDR", value_parser = ResolveOverride::parse)]
+    pub resolve: Vec<ResolveOverride>,
+
+    /// Also request the object from this CDN URL ({key} is replaced with the key) and
+    /// download from whichever of S3 and the CDN serves the first chunk faster
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        conflicts_with_all = ["recursive", "by_part", "version_id", "versions_ago", "list_versions"]
+    )]
+    pub cdn_url: Option<String>,
+
+    /// Push download metrics to this Prometheus pushgateway URL
+    #[cfg(feature = "metrics")]
+    #[arg(long, env = "S3FCP_METRICS_ENDPOINT")]
+    pub metrics_endpoint: Option<String>,
+
+    /// Cache downloaded ranges in this directory as blocks keyed by source and ETag
+    #[arg(long, env = "S3FCP_CACHE_DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Maximum total size of the range cache; least recently used ranges are evicted
+    #[arg(
+        long,
+        env = "S3FCP_CACHE_MAX_SIZE",
+        default_value = "1GB",
+        value_parser = parse_size,
+        requires = "cache_dir"
+    )]
+    pub cache_max_size: usize,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -43,51 +510,905 @@ pub struct HttpArgs {
     /// HTTP/HTTPS URL to download
     pub url: String,
 
+    /// How to determine the file size and range support
+    #[arg(long, env = "S3FCP_HEAD_METHOD", value_enum, default_value_t = HeadMethod::Auto)]
+    pub head_method: HeadMethod,
+
+    /// Send this "Name: value" header with every request; a value of @PATH is read from that file
+    #[arg(short = 'H', long = "header", value_name = "HEADER", value_parser = HeaderArg::parse)]
+    pub headers: Vec<HeaderArg>,
+
+    /// Send the headers in this file, one "Name: value" per line, before those given with --header
+    #[arg(long, value_name = "PATH")]
+    pub header_from_file: Option<PathBuf>,
+
+    /// Send this Host header instead of the URL's host, e.g. to reach a virtual host (HTTP/1.1 only)
+    #[arg(long, value_name = "VALUE", conflicts_with = "http2")]
+    pub host: Option<String>,
+
+    /// Use HTTP/2 where possible, multiplexing all range requests over one connection
+    #[arg(long, env = "S3FCP_HTTP2", value_parser = BoolishValueParser::new())]
+    pub http2: bool,
+
+    /// Open at most this many connections to the server; extra workers wait for a free one
+    #[arg(
+        long,
+        env = "S3FCP_MAX_CONNECTIONS_PER_HOST",
+        value_name = "N",
+        value_parser = parse_concurrency
+    )]
+    pub max_connections_per_host: Option<usize>,
+
+    /// Plan the download with this length in bytes instead of the server's; a wrong value corrupts the output
+    #[arg(long, value_name = "N")]
+    pub content_length: Option<u64>,
+
+    /// Write to this file instead of stdout
+    #[arg(short = 'o', long)]
+    pub output: Option<PathBuf>,
+
+    /// Write to stdout even if it is a terminal and the object isn't text
+    #[arg(long)]
+    pub force: bool,
+
+    /// Object tags only exist in S3; accepted here to reject it with a clear message
+    #[arg(long, value_name = "PATH", hide = true, value_parser = reject_save_tags)]
+    pub save_tags: Option<PathBuf>,
+
+    /// Stored S3 metadata only exists in S3; accepted here to reject it with a clear message
+    #[arg(long, value_name = "PATH", hide = true, value_parser = reject_save_metadata)]
+    pub save_metadata: Option<PathBuf>,
+
     /// Number of concurrent download workers
-    #[arg(short = 'c', long, default_value = "10")]
+    #[arg(
+        short = 'c',
+        long,
+        env = "S3FCP_CONCURRENCY",
+        default_value = "10",
+        value_parser = parse_concurrency
+    )]
     pub concurrency: usize,
 
-    /// Chunk size (supports human-readable sizes: 8MB, 16MiB, 1GB, etc.)
-    #[arg(long, default_value = "8MB", value_parser = parse_chunk_size)]
+    /// Start with a few workers and add more while throughput rises, up to --concurrency
+    #[arg(
+        long,
+        env = "S3FCP_ADAPTIVE_CONCURRENCY",
+        value_parser = BoolishValueParser::new(),
+        conflicts_with = "single_stream"
+    )]
+    pub adaptive_concurrency: bool,
+
+    /// Chunk size (supports human-readable sizes: 8MB, 16MiB, 1GB, etc., or a percentage of the object such as 5%)
+    #[arg(
+        long,
+        env = "S3FCP_CHUNK_SIZE",
+        default_value = "8MB",
+        value_parser = parse_download_chunk_size
+    )]
+    pub chunk_size: ChunkSize,
+
+    /// Use smaller chunks (down to 1MiB) when the object is too small to give every worker one
+    #[arg(long, env = "S3FCP_AUTO_CHUNK", value_parser = BoolishValueParser::new())]
+    pub auto_chunk: bool,
+
+    /// Download at most N chunks ahead of the one being written, bounding buffered memory
+    #[arg(
+        long,
+        env = "S3FCP_REORDER_WINDOW",
+        value_name = "N",
+        value_parser = parse_concurrency,
+        conflicts_with = "single_stream"
+    )]
+    pub reorder_window: Option<usize>,
+
+    /// Fail if a chunk reaches the writer twice, out of range, or not at all, to catch pipeline bugs
+    #[arg(long, conflicts_with = "single_stream")]
+    pub strict_ordering: bool,
+
+    /// Quiet mode - suppress progress output
+    #[arg(short = 'q', long, env = "S3FCP_QUIET", value_parser = BoolishValueParser::new())]
+    pub quiet: bool,
+
+    /// Wait this long before showing the progress bar (e.g. 0, 200ms, 1s)
+    #[arg(
+        long,
+        env = "S3FCP_PROGRESS_DELAY",
+        value_name = "DELAY",
+        default_value = "200ms",
+        value_parser = parse_duration
+    )]
+    pub progress_delay: Duration,
+
+    /// Also show completed/total chunks and in-flight chunks on the progress bar
+    #[arg(long, env = "S3FCP_PROGRESS_DETAIL", value_parser = BoolishValueParser::new())]
+    pub progress_detail: bool,
+
+    /// Leave the completed progress bar on screen instead of clearing it
+    #[arg(long, env = "S3FCP_KEEP_PROGRESS", value_parser = BoolishValueParser::new())]
+    pub keep_progress: bool,
+
+    /// Hide the progress bar but print one line with the size, duration and rate when done
+    #[arg(long, conflicts_with = "quiet")]
+    pub summary_only: bool,
+
+    /// Progress bar layout as an indicatif template, e.g. "{bytes}/{total_bytes} {bytes_per_sec}"
+    #[arg(
+        long,
+        env = "S3FCP_PROGRESS_TEMPLATE",
+        value_name = "TEMPLATE",
+        value_parser = parse_progress_template,
+        conflicts_with = "no_eta"
+    )]
+    pub progress_template: Option<String>,
+
+    /// Leave the estimated time remaining off the progress bar
+    #[arg(
+        long,
+        visible_alias = "no-progress-eta",
+        env = "S3FCP_NO_ETA",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub no_eta: bool,
+
+    /// Download the whole object in a single request, even if ranges are supported
+    #[arg(long, visible_alias = "force-single")]
+    pub single_stream: bool,
+
+    /// Normalize the line endings of a small text file; requires --single-stream
+    #[arg(long, value_enum, value_name = "MODE", requires = "single_stream")]
+    pub text_normalize: Option<TextNormalize>,
+
+    /// Use chunked range requests even if the server doesn't advertise range support
+    #[arg(long, conflicts_with = "single_stream")]
+    pub force_chunked: bool,
+
+    /// When HEAD doesn't advertise range support or the size, probe with a one-byte range GET and read the size from Content-Range
+    #[arg(
+        long,
+        env = "S3FCP_PROBE_RANGES",
+        value_parser = BoolishValueParser::new(),
+        conflicts_with = "single_stream"
+    )]
+    pub probe_ranges: bool,
+
+    /// Compress the output with gzip or zstd while writing it
+    #[arg(long, value_enum)]
+    pub compress: Option<Compression>,
+
+    /// Flush written data at least this often, so readers of a growing file see progress (0 disables)
+    #[arg(
+        long,
+        env = "S3FCP_FLUSH_INTERVAL",
+        value_name = "INTERVAL",
+        default_value = "5s",
+        value_parser = parse_duration
+    )]
+    pub flush_interval: Duration,
+
+    /// Buffer this much output before writing it to the file or stdout
+    #[arg(
+        long,
+        env = "S3FCP_WRITE_BUFFER_SIZE",
+        value_name = "SIZE",
+        default_value = "256KiB",
+        value_parser = parse_size
+    )]
+    pub write_buffer_size: usize,
+
+    /// Retry a failing chunk with growing backoff for up to this long (e.g. 10m) instead of 3 times
+    #[arg(
+        long,
+        env = "S3FCP_RETRY_DEADLINE",
+        value_name = "DURATION",
+        value_parser = parse_duration
+    )]
+    pub retry_deadline: Option<Duration>,
+
+    /// Fail and retry a request once no data has arrived for this long (e.g. 30s), so a
+    /// stalled connection doesn't hang the download
+    #[arg(
+        long,
+        env = "S3FCP_STALL_TIMEOUT",
+        value_name = "DURATION",
+        value_parser = parse_duration
+    )]
+    pub stall_timeout: Option<Duration>,
+
+    /// Fail if the object's size isn't known within this long (e.g. 5s), retries included
+    #[arg(
+        long,
+        env = "S3FCP_HEAD_TIMEOUT",
+        value_name = "DURATION",
+        value_parser = parse_duration
+    )]
+    pub head_timeout: Option<Duration>,
+
+    /// Fail before downloading anything unless the object is exactly this size (e.g. 1048576 or 1MiB)
+    #[arg(long, value_name = "SIZE", value_parser = parse_expected_size)]
+    pub expected_size: Option<u64>,
+
+    /// Start at most this many requests per second across all workers, to avoid S3 SlowDown
+    #[arg(
+        long,
+        visible_alias = "limit-requests-per-second",
+        env = "S3FCP_MAX_RPS",
+        value_name = "N",
+        value_parser = parse_rps
+    )]
+    pub max_rps: Option<f64>,
+
+    /// Sync the output file to disk before exiting
+    #[arg(long, requires = "output")]
+    pub fsync: bool,
+
+    /// Write straight to the output file instead of renaming a complete temporary file into place
+    #[arg(long, requires = "output")]
+    pub no_atomic: bool,
+
+    /// When the disk fills up, wait for space to be freed and keep writing instead of failing
+    #[arg(long, requires = "output", conflicts_with = "split_size")]
+    pub wait_on_diskfull: bool,
+
+    /// Print the number of bytes written to the output to stderr when done
+    #[arg(long)]
+    pub report_bytes: bool,
+
+    /// Print how long the HEAD request, first chunk, transfer, each worker and the chunks took to stderr
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        default_missing_value = "text"
+    )]
+    pub timings: Option<TimingsFormat>,
+
+    /// Write the output as OUTPUT.000, OUTPUT.001, … of at most this size each
+    #[arg(long, value_name = "SIZE", value_parser = parse_split_size, requires = "output")]
+    pub split_size: Option<u64>,
+
+    /// Download only this inclusive byte range; repeat to concatenate several in the given order
+    #[arg(
+        long = "range",
+        value_name = "START-END",
+        value_parser = ByteRange::parse,
+        conflicts_with = "single_stream"
+    )]
+    pub ranges: Vec<ByteRange>,
+
+    /// Download the ranges listed in this JSON index, e.g. [{"start": 0, "end": 99}], concatenated
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["ranges", "start_byte", "end_byte", "single_stream"]
+    )]
+    pub ranges_file: Option<PathBuf>,
+
+    /// Download from this byte offset to the end of the object
+    #[arg(long, value_name = "N", conflicts_with_all = ["ranges", "single_stream"])]
+    pub start_byte: Option<u64>,
+
+    /// Download from the start of the object up to and including this byte offset
+    #[arg(long, value_name = "N", conflicts_with_all = ["ranges", "single_stream"])]
+    pub end_byte: Option<u64>,
+
+    /// Check the download against the server's Content-MD5 header, if it sends one
+    #[arg(long, conflicts_with_all = ["ranges", "ranges_file", "start_byte", "end_byte"])]
+    pub verify: bool,
+
+    /// Connect to ADDR for requests to HOST:PORT, like curl (repeatable)
+    #[arg(long, value_name = "HOST:PORT:ADDR", value_parser = ResolveOverride::parse)]
+    pub resolve: Vec<ResolveOverride>,
+
+    /// Push download metrics to this Prometheus pushgateway URL
+    #[cfg(feature = "metrics")]
+    #[arg(long, env = "S3FCP_METRICS_ENDPOINT")]
+    pub metrics_endpoint: Option<String>,
+
+    /// Cache downloaded ranges in this directory as blocks keyed by source and ETag
+    #[arg(long, env = "S3FCP_CACHE_DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Maximum total size of the range cache; least recently used ranges are evicted
+    #[arg(
+        long,
+        env = "S3FCP_CACHE_MAX_SIZE",
+        default_value = "1GB",
+        value_parser = parse_size,
+        requires = "cache_dir"
+    )]
+    pub cache_max_size: usize,
+}
+
+#[derive(Args, Debug, Clone)]
+#[command(group = clap::ArgGroup::new("input").required(true))]
+pub struct BatchArgs {
+    /// File listing one URI per line, or - for stdin; blank lines and # comments are skipped
+    #[arg(group = "input")]
+    pub list: Option<PathBuf>,
+
+    /// Read the list from stdin, starting each download as soon as its line arrives
+    #[arg(long, group = "input")]
+    pub from_stdin: bool,
+
+    /// Directory to download into, named after the last segment of each key or URL path
+    #[arg(short = 'o', long)]
+    pub output: PathBuf,
+
+    /// Name the files by this template instead: {key}, {path}, {basename}, {stem}, {ext},
+    /// {bucket} (the host for URLs), {year}, {month}, {day}
+    #[arg(long, value_name = "TEMPLATE", value_parser = OutputTemplate::parse)]
+    pub output_template: Option<OutputTemplate>,
+
+    /// Accept bucket names that don't follow the AWS naming rules
+    #[arg(long, env = "S3FCP_SKIP_BUCKET_VALIDATION", value_parser = BoolishValueParser::new())]
+    pub skip_bucket_validation: bool,
+
+    /// Send S3 requests without credentials, for public buckets
+    #[arg(long, env = "S3FCP_NO_SIGN_REQUEST", value_parser = BoolishValueParser::new())]
+    pub no_sign_request: bool,
+
+    /// Whether a failed entry stops the batch
+    #[arg(long, env = "S3FCP_ON_ERROR", value_enum, default_value_t = OnError::Abort)]
+    pub on_error: OnError,
+
+    /// Send every HEAD request instead of reusing recent responses for the same object
+    #[arg(long)]
+    pub no_head_cache: bool,
+
+    /// Number of concurrent download workers, shared by the whole batch
+    #[arg(
+        short = 'c',
+        long,
+        env = "S3FCP_CONCURRENCY",
+        default_value = "10",
+        value_parser = parse_concurrency
+    )]
+    pub concurrency: usize,
+
+    /// Chunk size (supports human-readable sizes: 8MB, 16MiB, 1GB, etc., or a percentage of each object such as 5%)
+    #[arg(
+        long,
+        env = "S3FCP_CHUNK_SIZE",
+        default_value = "8MB",
+        value_parser = parse_download_chunk_size
+    )]
+    pub chunk_size: ChunkSize,
+
+    /// Quiet mode - suppress progress output and the final summary
+    #[arg(short = 'q', long, env = "S3FCP_QUIET", value_parser = BoolishValueParser::new())]
+    pub quiet: bool,
+}
+
+impl BatchArgs {
+    /// Whether the list is read from stdin
+    pub fn reads_stdin(&self) -> bool {
+        self.from_stdin || self.list.as_deref() == Some(std::path::Path::new("-"))
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CpArgs {
+    /// Local file to upload
+    pub src: PathBuf,
+
+    /// Destination S3 URI in the format s3://bucket/key
+    pub dest: String,
+
+    /// Accept bucket names that don't follow the AWS naming rules
+    #[arg(long, env = "S3FCP_SKIP_BUCKET_VALIDATION", value_parser = BoolishValueParser::new())]
+    pub skip_bucket_validation: bool,
+
+    /// Use the dual-stack (IPv4 and IPv6) S3 endpoint; an explicit endpoint URL takes precedence
+    #[arg(long, env = "S3FCP_DUALSTACK", value_parser = BoolishValueParser::new())]
+    pub dualstack: bool,
+
+    /// Use the FIPS 140-validated S3 endpoint; an explicit endpoint URL takes precedence
+    #[arg(long, env = "S3FCP_FIPS", value_parser = BoolishValueParser::new())]
+    pub fips: bool,
+
+    /// Use S3 Transfer Acceleration, which must be enabled on the bucket; an explicit endpoint URL takes precedence
+    #[arg(
+        long,
+        env = "S3FCP_ACCELERATE",
+        value_parser = BoolishValueParser::new(),
+        conflicts_with = "fips"
+    )]
+    pub accelerate: bool,
+
+    /// Assume --role-arn with the web identity token in this file (e.g. an EKS service account token)
+    #[arg(long, value_name = "PATH", requires = "role_arn")]
+    pub web_identity_token_file: Option<PathBuf>,
+
+    /// Role to assume with --web-identity-token-file instead of the default credential chain
+    #[arg(long, value_name = "ARN", requires = "web_identity_token_file")]
+    pub role_arn: Option<String>,
+
+    /// Number of concurrent upload workers
+    #[arg(
+        short = 'c',
+        long,
+        env = "S3FCP_CONCURRENCY",
+        default_value = "10",
+        value_parser = parse_concurrency
+    )]
+    pub concurrency: usize,
+
+    // Not S3FCP_CHUNK_SIZE, which may hold a percentage meant for downloads
+    /// Part size (supports human-readable sizes; raised to the S3 minimum of 5MiB)
+    #[arg(
+        long,
+        env = "S3FCP_PART_SIZE",
+        default_value = "8MB",
+        value_parser = parse_chunk_size
+    )]
     pub chunk_size: usize,
 
     /// Quiet mode - suppress progress output
-    #[arg(short = 'q', long)]
+    #[arg(short = 'q', long, env = "S3FCP_QUIET", value_parser = BoolishValueParser::new())]
     pub quiet: bool,
+
+    /// Wait this long before showing the progress bar (e.g. 0, 200ms, 1s)
+    #[arg(
+        long,
+        env = "S3FCP_PROGRESS_DELAY",
+        value_name = "DELAY",
+        default_value = "200ms",
+        value_parser = parse_duration
+    )]
+    pub progress_delay: Duration,
+
+    /// Leave the completed progress bar on screen instead of clearing it
+    #[arg(long, env = "S3FCP_KEEP_PROGRESS", value_parser = BoolishValueParser::new())]
+    pub keep_progress: bool,
+
+    /// Progress bar layout as an indicatif template, e.g. "{bytes}/{total_bytes} {bytes_per_sec}"
+    #[arg(
+        long,
+        env = "S3FCP_PROGRESS_TEMPLATE",
+        value_name = "TEMPLATE",
+        value_parser = parse_progress_template,
+        conflicts_with = "no_eta"
+    )]
+    pub progress_template: Option<String>,
+
+    /// Leave the estimated time remaining off the progress bar
+    #[arg(
+        long,
+        visible_alias = "no-progress-eta",
+        env = "S3FCP_NO_ETA",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub no_eta: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct PresignArgs {
+    /// S3 URI in the format s3://bucket/key
+    pub uri: String,
+
+    /// S3 object version ID for versioned objects
+    #[arg(long)]
+    pub version_id: Option<String>,
+
+    /// How long the URL stays valid (e.g. 900, 30s, 15m, 12h, 7d; at most 7d)
+    #[arg(long, default_value = "15m", value_parser = parse_duration)]
+    pub expires_in: Duration,
+
+    /// Content-Type S3 should send for the object instead of the stored one
+    #[arg(long, value_name = "TYPE")]
+    pub response_content_type: Option<String>,
+
+    /// Content-Disposition S3 should send, e.g. 'attachment; filename="report.pdf"'
+    #[arg(long, value_name = "VALUE")]
+    pub response_content_disposition: Option<String>,
+
+    /// Accept bucket names that don't follow the AWS naming rules
+    #[arg(long, env = "S3FCP_SKIP_BUCKET_VALIDATION", value_parser = BoolishValueParser::new())]
+    pub skip_bucket_validation: bool,
+
+    /// Use the dual-stack (IPv4 and IPv6) S3 endpoint; an explicit endpoint URL takes precedence
+    #[arg(long, env = "S3FCP_DUALSTACK", value_parser = BoolishValueParser::new())]
+    pub dualstack: bool,
+
+    /// Use the FIPS 140-validated S3 endpoint; an explicit endpoint URL takes precedence
+    #[arg(long, env = "S3FCP_FIPS", value_parser = BoolishValueParser::new())]
+    pub fips: bool,
+
+    /// Use S3 Transfer Acceleration, which must be enabled on the bucket; an explicit endpoint URL takes precedence
+    #[arg(
+        long,
+        env = "S3FCP_ACCELERATE",
+        value_parser = BoolishValueParser::new(),
+        conflicts_with = "fips"
+    )]
+    pub accelerate: bool,
+
+    /// Assume --role-arn with the web identity token in this file (e.g. an EKS service account token)
+    #[arg(long, value_name = "PATH", requires = "role_arn")]
+    pub web_identity_token_file: Option<PathBuf>,
+
+    /// Role to assume with --web-identity-token-file instead of the default credential chain
+    #[arg(long, value_name = "ARN", requires = "web_identity_token_file")]
+    pub role_arn: Option<String>,
 }
 
+#[derive(Args, Debug, Clone)]
+pub struct PingArgs {
+    /// s3://bucket to check the bucket, s3://bucket/key to check an object, or an HTTP/HTTPS URL
+    pub target: String,
+
+    /// Fail if there is no answer within this time
+    #[arg(long, default_value = "10s", value_parser = parse_duration)]
+    pub timeout: Duration,
+
+    /// Print the result as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Accept bucket names that don't follow the AWS naming rules
+    #[arg(long, env = "S3FCP_SKIP_BUCKET_VALIDATION", value_parser = BoolishValueParser::new())]
+    pub skip_bucket_validation: bool,
+
+    /// Send requests without credentials, for public buckets
+    #[arg(long, env = "S3FCP_NO_SIGN_REQUEST", value_parser = BoolishValueParser::new())]
+    pub no_sign_request: bool,
+
+    /// Use the dual-stack (IPv4 and IPv6) S3 endpoint; an explicit endpoint URL takes precedence
+    #[arg(long, env = "S3FCP_DUALSTACK", value_parser = BoolishValueParser::new())]
+    pub dualstack: bool,
+
+    /// Use the FIPS 140-validated S3 endpoint; an explicit endpoint URL takes precedence
+    #[arg(long, env = "S3FCP_FIPS", value_parser = BoolishValueParser::new())]
+    pub fips: bool,
+
+    /// Use S3 Transfer Acceleration, which must be enabled on the bucket; an explicit endpoint URL takes precedence
+    #[arg(
+        long,
+        env = "S3FCP_ACCELERATE",
+        value_parser = BoolishValueParser::new(),
+        conflicts_with = "fips"
+    )]
+    pub accelerate: bool,
+
+    /// Assume --role-arn with the web identity token in this file (e.g. an EKS service account token)
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "role_arn",
+        conflicts_with = "no_sign_request"
+    )]
+    pub web_identity_token_file: Option<PathBuf>,
+
+    /// Role to assume with --web-identity-token-file instead of the default credential chain
+    #[arg(long, value_name = "ARN", requires = "web_identity_token_file")]
+    pub role_arn: Option<String>,
+
+    /// How to probe an HTTP URL
+    #[arg(long, env = "S3FCP_HEAD_METHOD", value_enum, default_value_t = HeadMethod::Auto)]
+    pub head_method: HeadMethod,
+
+    /// Send this "Name: value" header with an HTTP request; a value of @PATH is read from that file
+    #[arg(short = 'H', long = "header", value_name = "HEADER", value_parser = HeaderArg::parse)]
+    pub headers: Vec<HeaderArg>,
+
+    /// Send the headers in this file, one "Name: value" per line, before those given with --header
+    #[arg(long, value_name = "PATH")]
+    pub header_from_file: Option<PathBuf>,
+
+    /// Connect to ADDR for requests to HOST, like curl (repeatable; S3 ignores PORT)
+    #[arg(long, value_name = "HOST:PORT:ADDR", value_parser = ResolveOverride::parse)]
+    pub resolve: Vec<ResolveOverride>,
+}
+
+/// Default time between flushes of the output during chunked downloads
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Default capacity of the output buffer used by `download_to_file` and `download_to_stdout`
+pub const DEFAULT_WRITE_BUFFER_SIZE: usize = 256 * 1024;
+
+/// `--chunk-size` of a download
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChunkSize {
+    Bytes(usize),
+    /// Percentage of the object's size, only known once the HEAD request returns
+    Percent(f64),
+}
+
+impl ChunkSize {
+    /// The fixed size; a percentage leaves the `DownloadArgs` default in place
+    fn bytes(self) -> usize {
+        match self {
+            ChunkSize::Bytes(bytes) => bytes,
+            ChunkSize::Percent(_) => DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// The fixed size, if this isn't a percentage
+    pub fn fixed(self) -> Option<usize> {
+        match self {
+            ChunkSize::Bytes(bytes) => Some(bytes),
+            ChunkSize::Percent(_) => None,
+        }
+    }
+
+    fn percent(self) -> Option<f64> {
+        match self {
+            ChunkSize::Bytes(_) => None,
+            ChunkSize::Percent(percent) => Some(percent),
+        }
+    }
+}
+
+/// Default size of download chunks
+pub const DEFAULT_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
 /// Common download arguments shared between S3 and HTTP
 #[derive(Debug, Clone, bon::Builder)]
 pub struct DownloadArgs {
     #[builder(default = 10)]
     pub concurrency: usize,
-    #[builder(default = 8 * 1024 * 1024)]
+    /// Treat `concurrency` as a maximum and adapt the number of workers to the throughput
+    #[builder(default)]
+    pub adaptive_concurrency: bool,
+    #[builder(default = DEFAULT_CHUNK_SIZE)]
     pub chunk_size: usize,
+    /// Size chunks as this percentage of the object instead of `chunk_size`
+    pub chunk_percent: Option<f64>,
+    /// Shrink `chunk_size` for objects too small to give every worker a chunk
+    #[builder(default)]
+    pub auto_chunk: bool,
+    /// Download at most this many chunks ahead of the output; `None` is unlimited
+    pub reorder_window: Option<usize>,
+    /// Fail when the writer sees a duplicate, out-of-range or missing chunk
+    #[builder(default)]
+    pub strict_ordering: bool,
     #[builder(default)]
     pub quiet: bool,
+    #[builder(default = DEFAULT_PROGRESS_DELAY)]
+    pub progress_delay: Duration,
+    /// Show chunk counts on the progress bar of chunked downloads
+    #[builder(default)]
+    pub progress_detail: bool,
+    /// Leave the completed progress bar on screen
+    #[builder(default)]
+    pub keep_progress: bool,
+    /// Hide the progress bar and print a completion line instead
+    #[builder(default)]
+    pub summary_only: bool,
+    /// Layout of the progress bar
+    #[builder(default)]
+    pub progress_template: BarTemplate,
+    #[builder(default)]
+    pub single_stream: bool,
+    /// Try range requests even if the server doesn't advertise support for them
+    #[builder(default)]
+    pub force_chunked: bool,
+    /// Chunk multipart-uploaded objects along their original parts, requested by part number
+    #[builder(default)]
+    pub by_part: bool,
+    /// Compress the output while writing it; progress still counts downloaded bytes
+    pub compress: Option<Compression>,
+    /// Rewrite line endings of the downloaded text before compressing and writing it
+    pub text_normalize: Option<TextNormalize>,
+    /// Flush the output at least this often while chunks are written; zero flushes only at the end
+    #[builder(default = DEFAULT_FLUSH_INTERVAL)]
+    pub flush_interval: Duration,
+    /// Per-chunk time budget for retries; `None` uses a fixed number of retries
+    pub retry_deadline: Option<Duration>,
+    /// Time budget for the HEAD request, retries included
+    pub head_timeout: Option<Duration>,
+    /// Size the object must have; the download fails before any chunk if it differs
+    pub expected_size: Option<u64>,
+    /// Refuse binary output before any chunk, for stdout on a terminal
+    #[builder(default)]
+    pub check_terminal: bool,
+    /// Spaces out requests; clones of the args share it
+    pub rate_limit: Option<Arc<RequestRateLimiter>>,
+    /// Capacity of the buffer in front of file and stdout output
+    #[builder(default = DEFAULT_WRITE_BUFFER_SIZE)]
+    pub write_buffer_size: usize,
+    /// Sync file output to disk once the download completes
+    #[builder(default)]
+    pub fsync: bool,
+    /// Write file output to a temporary file and rename it into place once complete
+    #[builder(default = true)]
+    pub atomic: bool,
+    /// Pause file output while the disk is full instead of failing
+    #[builder(default)]
+    pub wait_on_diskfull: bool,
+    /// Print the number of bytes written to the output to stderr when done
+    #[builder(default)]
+    pub report_bytes: bool,
+    /// Format of the errors reported for objects skipped under `OnError::Continue`
+    #[builder(default)]
+    pub error_format: ErrorFormat,
+    /// Split file output into numbered segments of at most this many bytes
+    pub split_size: Option<u64>,
+    /// Check the whole object against the server's `Content-MD5`, when it sends one
+    #[builder(default)]
+    pub verify: bool,
+    /// Fail unless the whole object has this hex SHA-256
+    pub sha256: Option<String>,
+    /// Download only these byte ranges, concatenated in order; empty means the whole object
+    #[builder(default)]
+    pub ranges: Vec<ByteRange>,
+    /// Download from this offset; combined with `end_byte`, missing bounds are the object's ends
+    pub start_byte: Option<u64>,
+    /// Download up to and including this offset
+    pub end_byte: Option<u64>,
+    /// Receives chunk lifecycle events; not available from the CLI
+    pub listener: Option<Arc<dyn DownloadListener>>,
+    /// Collects the time spent in the HEAD request, the transfer and each worker
+    pub timings: Option<Arc<Timings>>,
+    /// Receives the digests computed while writing; not available from the CLI
+    pub stats: Option<Arc<DownloadStats>>,
+}
+
+impl DownloadArgs {
+    /// Whether only part of the object is downloaded
+    pub fn is_partial(&self) -> bool {
+        !self.ranges.is_empty() || self.start_byte.is_some() || self.end_byte.is_some()
+    }
+
+    /// Chunk size for an object of `content_length` bytes, resolving `chunk_percent`
+    /// A percentage is rounded up to whole bytes
+    pub fn chunk_size_for(&self, content_length: u64) -> Result<usize, String> {
+        let Some(percent) = self.chunk_percent else {
+            return Ok(self.chunk_size);
+        };
+        let size = (content_length as f64 * percent / 100.0).ceil() as usize;
+        if size == 0 {
+            return Err(format!(
+                "a chunk size of {}% of {} bytes is less than one byte",
+                percent, content_length
+            ));
+        }
+        Ok(size)
+    }
 }
 
 impl From<&S3Args> for DownloadArgs {
     fn from(args: &S3Args) -> Self {
         Self {
             concurrency: args.concurrency,
-            chunk_size: args.chunk_size,
+            adaptive_concurrency: args.adaptive_concurrency,
+            chunk_size: args.chunk_size.bytes(),
+            chunk_percent: args.chunk_size.percent(),
+            auto_chunk: args.auto_chunk,
+            reorder_window: args.reorder_window,
+            strict_ordering: args.strict_ordering,
             quiet: args.quiet,
+            progress_delay: args.progress_delay,
+            progress_detail: args.progress_detail,
+            keep_progress: args.keep_progress,
+            summary_only: args.summary_only,
+            progress_template: bar_template(&args.progress_template, args.no_eta),
+            single_stream: args.single_stream,
+            force_chunked: false,
+            by_part: args.by_part,
+            compress: args.compress,
+            text_normalize: args.text_normalize,
+            flush_interval: args.flush_interval,
+            write_buffer_size: args.write_buffer_size,
+            retry_deadline: args.retry_deadline,
+            head_timeout: args.head_timeout,
+            expected_size: args.expected_size,
+            check_terminal: false,
+            rate_limit: args
+                .max_rps
+                .map(|rps| Arc::new(RequestRateLimiter::new(rps))),
+            fsync: args.fsync,
+            atomic: !args.no_atomic,
+            wait_on_diskfull: args.wait_on_diskfull,
+            report_bytes: args.report_bytes,
+            error_format: ErrorFormat::Text,
+            split_size: args.split_size,
+            verify: false,
+            sha256: None,
+            ranges: args.ranges.clone(),
+            start_byte: args.start_byte,
+            end_byte: args.end_byte,
+            listener: None,
+            timings: args.timings.map(|_| Arc::new(Timings::new())),
+            stats: None,
         }
     }
 }
 
 impl From<&HttpArgs> for DownloadArgs {
     fn from(args: &HttpArgs) -> Self {
+        Self {
+            concurrency: args.concurrency,
+            adaptive_concurrency: args.adaptive_concurrency,
+            chunk_size: args.chunk_size.bytes(),
+            chunk_percent: args.chunk_size.percent(),
+            auto_chunk: args.auto_chunk,
+            reorder_window: args.reorder_window,
+            strict_ordering: args.strict_ordering,
+            quiet: args.quiet,
+            progress_delay: args.progress_delay,
+            progress_detail: args.progress_detail,
+            keep_progress: args.keep_progress,
+            summary_only: args.summary_only,
+            progress_template: bar_template(&args.progress_template, args.no_eta),
+            single_stream: args.single_stream,
+            force_chunked: args.force_chunked,
+            by_part: false,
+            compress: args.compress,
+            text_normalize: args.text_normalize,
+            flush_interval: args.flush_interval,
+            write_buffer_size: args.write_buffer_size,
+            retry_deadline: args.retry_deadline,
+            head_timeout: args.head_timeout,
+            expected_size: args.expected_size,
+            check_terminal: false,
+            rate_limit: args
+                .max_rps
+                .map(|rps| Arc::new(RequestRateLimiter::new(rps))),
+            fsync: args.fsync,
+            atomic: !args.no_atomic,
+            wait_on_diskfull: args.wait_on_diskfull,
+            report_bytes: args.report_bytes,
+            error_format: ErrorFormat::Text,
+            split_size: args.split_size,
+            verify: args.verify,
+            sha256: None,
+            ranges: args.ranges.clone(),
+            start_byte: args.start_byte,
+            end_byte: args.end_byte,
+            listener: None,
+            timings: args.timings.map(|_| Arc::new(Timings::new())),
+            stats: None,
+        }
+    }
+}
+
+/// Upload arguments for the `cp` subcommand
+#[derive(Debug, Clone, bon::Builder)]
+pub struct UploadArgs {
+    #[builder(default = 10)]
+    pub concurrency: usize,
+    #[builder(default = 8 * 1024 * 1024)]
+    pub chunk_size: usize,
+    #[builder(default)]
+    pub quiet: bool,
+    #[builder(default = DEFAULT_PROGRESS_DELAY)]
+    pub progress_delay: Duration,
+    /// Leave the completed progress bar on screen
+    #[builder(default)]
+    pub keep_progress: bool,
+    /// Layout of the progress bar
+    #[builder(default)]
+    pub progress_template: BarTemplate,
+}
+
+impl From<&BatchArgs> for DownloadArgs {
+    fn from(args: &BatchArgs) -> Self {
+        Self::builder()
+            .concurrency(args.concurrency)
+            .chunk_size(args.chunk_size.bytes())
+            .maybe_chunk_percent(args.chunk_size.percent())
+            .quiet(args.quiet)
+            .build()
+    }
+}
+
+impl From<&CpArgs> for UploadArgs {
+    fn from(args: &CpArgs) -> Self {
         Self {
             concurrency: args.concurrency,
             chunk_size: args.chunk_size,
             quiet: args.quiet,
+            progress_delay: args.progress_delay,
+            keep_progress: args.keep_progress,
+            progress_template: bar_template(&args.progress_template, args.no_eta),
         }
     }
 }
 
-fn parse_chunk_size(s: &str) -> Result<usize, String> {
+/// Parse a human-readable size such as `8MB`, `16MiB` or a plain byte count
+fn parse_size(s: &str) -> Result<usize, String> {
     let s = s.trim().to_uppercase();
 
     // Try to parse as plain number first
@@ -107,6 +1428,13 @@ fn parse_chunk_size(s: &str) -> Result<usize, String> {
         .parse()
         .map_err(|_| format!("Invalid number: {}", num_str))?;
 
+    if !num.is_finite() || num < 0.0 {
+        return Err(format!(
+            "Size must be a positive number, got {}",
+            num_str.trim()
+        ));
+    }
+
     let multiplier: u64 = match suffix.trim() {
         "B" => 1,
         "KB" | "K" => 1_000,
@@ -120,12 +1448,171 @@ fn parse_chunk_size(s: &str) -> Result<usize, String> {
         _ => return Err(format!("Unknown size suffix: {}", suffix)),
     };
 
-    Ok((num * multiplier as f64) as usize)
+    let bytes = num * multiplier as f64;
+    if bytes.fract() != 0.0 {
+        return Err(format!(
+            "Size must be a whole number of bytes, {} is {} bytes",
+            s, bytes
+        ));
+    }
+    if bytes >= usize::MAX as f64 {
+        return Err(format!("Size {} is too large for this platform", s));
+    }
+
+    Ok(bytes as usize)
+}
+
+pub(crate) fn parse_chunk_size(s: &str) -> Result<usize, String> {
+    validate_chunk_size(parse_size(s)?)
+}
+
+/// Parse a download `--chunk-size`, which may also be a percentage of the object, e.g. `5%`
+pub(crate) fn parse_download_chunk_size(s: &str) -> Result<ChunkSize, String> {
+    let Some(percent) = s.trim().strip_suffix('%') else {
+        return parse_chunk_size(s).map(ChunkSize::Bytes);
+    };
+    let percent: f64 = percent
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid chunk size percentage: {}", s))?;
+    if !(percent > 0.0 && percent <= 100.0) {
+        return Err(format!(
+            "Chunk size percentage must be greater than 0% and at most 100%, got {}",
+            s
+        ));
+    }
+    Ok(ChunkSize::Percent(percent))
+}
+
+/// Parse a segment size, which must be at least one byte
+fn parse_split_size(s: &str) -> Result<u64, String> {
+    match parse_size(s)? {
+        0 => Err("Split size must be greater than 0".to_string()),
+        size => Ok(size as u64),
+    }
+}
+
+/// Parse an exact object size, in bytes or with a size suffix
+fn parse_expected_size(s: &str) -> Result<u64, String> {
+    parse_size(s).map(|size| size as u64)
+}
+
+/// Parse a progress bar template, rejecting it at startup instead of drawing a broken bar
+fn parse_progress_template(s: &str) -> Result<String, String> {
+    validate_template(s)?;
+    Ok(s.to_string())
+}
+
+/// Bar layout selected by `--progress-template` and `--no-eta`
+fn bar_template(template: &Option<String>, no_eta: bool) -> BarTemplate {
+    match template {
+        Some(template) => BarTemplate::Custom(template.clone()),
+        None if no_eta => BarTemplate::NoEta,
+        None => BarTemplate::Default,
+    }
+}
+
+fn reject_save_tags(_: &str) -> Result<PathBuf, String> {
+    Err("object tags are only available for S3 downloads (s3fcp s3 ... --save-tags)".to_string())
+}
+
+fn reject_save_metadata(_: &str) -> Result<PathBuf, String> {
+    Err(
+        "stored object metadata is only available for S3 downloads (s3fcp s3 ... --save-metadata)"
+            .to_string(),
+    )
+}
+
+/// Parse a request rate, which must be a positive number
+/// Rates so low that the gap between requests isn't a representable duration are rejected
+fn parse_rps(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(rps) if rps.is_finite() && rps > 0.0 => {
+            if Duration::try_from_secs_f64(1.0 / rps).is_err() {
+                return Err(format!(
+                    "Request rate '{}' is too low: requests would never start",
+                    s
+                ));
+            }
+            Ok(rps)
+        }
+        _ => Err(format!(
+            "Invalid request rate '{}': expected a positive number",
+            s
+        )),
+    }
+}
+
+/// Parse a worker count, which must be at least 1
+fn parse_concurrency(s: &str) -> Result<usize, String> {
+    let concurrency: usize = s
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid concurrency: {}", s))?;
+    if concurrency == 0 {
+        return Err("Concurrency must be at least 1".to_string());
+    }
+    Ok(concurrency)
+}
+
+/// Parse a duration such as `900` (seconds), `500ms`, `30s`, `15m`, `12h` or `7d`
+pub(crate) fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim().to_lowercase();
+
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num_str, unit) = s.split_at(split);
+
+    let num: u64 = num_str
+        .parse()
+        .map_err(|_| format!("Invalid duration: {}", s))?;
+
+    let multiplier = match unit.trim() {
+        "ms" => return Ok(Duration::from_millis(num)),
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        unit => return Err(format!("Unknown duration unit: {}", unit)),
+    };
+
+    num.checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Duration too large: {}", s))
+}
+
+fn validate_chunk_size(size: usize) -> Result<usize, String> {
+    if size == 0 {
+        return Err(
+            "Chunk size must be greater than 0 (use --single-stream to disable chunking)"
+                .to_string(),
+        );
+    }
+    Ok(size)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_parse_concurrency() {
+        assert_eq!(parse_concurrency("1").unwrap(), 1);
+        assert_eq!(parse_concurrency("16").unwrap(), 16);
+        assert!(parse_concurrency("0").is_err());
+        assert!(parse_concurrency("-1").is_err());
+        assert!(parse_concurrency("many").is_err());
+    }
+
+    #[test]
+    fn test_parse_rps() {
+        assert_eq!(parse_rps("10").unwrap(), 10.0);
+        assert_eq!(parse_rps("0.5").unwrap(), 0.5);
+        assert!(parse_rps("0").is_err());
+        assert!(parse_rps("-1").is_err());
+        assert!(parse_rps("inf").is_err());
+        assert!(parse_rps("1e-20").is_err());
+    }
 
     #[test]
     fn test_parse_chunk_size() {
@@ -135,5 +1622,187 @@ mod tests {
         assert_eq!(parse_chunk_size("1GB").unwrap(), 1_000_000_000);
         assert_eq!(parse_chunk_size("1GiB").unwrap(), 1_073_741_824);
         assert_eq!(parse_chunk_size("16 MB").unwrap(), 16_000_000);
+        assert_eq!(parse_chunk_size("1.5KB").unwrap(), 1_500);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("900").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("12H").unwrap(), Duration::from_secs(43_200));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604_800));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("-5s").is_err());
+        assert!(parse_duration("5w").is_err());
+        assert_eq!(
+            parse_duration("999999999999999999d").unwrap_err(),
+            "Duration too large: 999999999999999999d"
+        );
+        assert!(parse_duration(&format!("{}m", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_parse_download_chunk_size() {
+        assert_eq!(
+            parse_download_chunk_size("16MiB").unwrap(),
+            ChunkSize::Bytes(16 * 1024 * 1024)
+        );
+        assert_eq!(
+            parse_download_chunk_size("5%").unwrap(),
+            ChunkSize::Percent(5.0)
+        );
+        assert_eq!(
+            parse_download_chunk_size(" 2.5 %").unwrap(),
+            ChunkSize::Percent(2.5)
+        );
+        assert!(parse_download_chunk_size("0%").is_err());
+        assert!(parse_download_chunk_size("101%").is_err());
+        assert!(parse_download_chunk_size("NaN%").is_err());
+        assert!(parse_download_chunk_size("five%").is_err());
+    }
+
+    #[test]
+    fn test_no_head_cache_is_a_batch_flag() {
+        let cli = Cli::try_parse_from(["s3fcp", "batch", "list", "-o", "out", "--no-head-cache"])
+            .unwrap();
+        let Command::Batch(args) = cli.command else {
+            panic!("expected batch command");
+        };
+        assert!(args.no_head_cache);
+
+        // It is not another name for --head-each-object
+        assert!(Cli::try_parse_from([
+            "s3fcp",
+            "s3",
+            "s3://bucket/p/",
+            "-r",
+            "-o",
+            "out",
+            "--no-head-cache"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_batch_accepts_chunk_percent() {
+        let cli =
+            Cli::try_parse_from(["s3fcp", "batch", "list", "-o", "out", "--chunk-size", "10%"])
+                .unwrap();
+        let Command::Batch(args) = cli.command else {
+            panic!("expected batch command");
+        };
+        let args = DownloadArgs::from(&args);
+        assert_eq!(args.chunk_size_for(1000), Ok(100));
+
+        assert!(
+            Cli::try_parse_from(["s3fcp", "cp", "f", "s3://b/k", "--chunk-size", "10%"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_chunk_size_for_resolves_percentages() {
+        let args = DownloadArgs::builder().chunk_size(100).build();
+        assert_eq!(args.chunk_size_for(1_000_000), Ok(100));
+
+        let args = DownloadArgs::builder().chunk_percent(5.0).build();
+        assert_eq!(args.chunk_size_for(1_000_000), Ok(50_000));
+        // Rounded up, so small objects still get whole-byte chunks
+        assert_eq!(args.chunk_size_for(10), Ok(1));
+        assert!(args.chunk_size_for(0).is_err());
+    }
+
+    #[test]
+    fn test_parse_chunk_size_rejects_zero() {
+        assert!(parse_chunk_size("0")
+            .unwrap_err()
+            .contains("greater than 0"));
+        assert!(parse_chunk_size("0MB")
+            .unwrap_err()
+            .contains("greater than 0"));
+    }
+
+    #[test]
+    fn test_parse_chunk_size_rejects_invalid_numbers() {
+        assert!(parse_chunk_size("-5MB").unwrap_err().contains("positive"));
+        assert!(parse_chunk_size("1e999MB").is_err());
+        assert!(parse_chunk_size("inf").is_err());
+        assert!(parse_chunk_size("1.5B")
+            .unwrap_err()
+            .contains("whole number"));
+        assert!(parse_chunk_size("100000000TB")
+            .unwrap_err()
+            .contains("too large"));
+    }
+
+    #[test]
+    fn test_web_identity_flags_go_together() {
+        let parse = |args: &[&str]| {
+            let mut full = vec!["s3fcp", "presign", "s3://bucket/key"];
+            full.extend_from_slice(args);
+            Cli::try_parse_from(full)
+        };
+        let token = ["--web-identity-token-file", "/token"];
+        let role = ["--role-arn", "arn:aws:iam::123456789012:role/reader"];
+
+        assert!(parse(&token).is_err());
+        assert!(parse(&role).is_err());
+        let Command::Presign(args) = parse(&[token, role].concat()).unwrap().command else {
+            panic!("expected presign command");
+        };
+        assert_eq!(args.web_identity_token_file, Some(PathBuf::from("/token")));
+    }
+
+    #[test]
+    fn test_long_version_lists_dependencies() {
+        assert!(LONG_VERSION.starts_with(env!("CARGO_PKG_VERSION")));
+        assert!(LONG_VERSION.contains("\naws-sdk-s3: 1."));
+        assert!(LONG_VERSION.contains("\nreqwest:    0."));
+
+        let error = Cli::try_parse_from(["s3fcp", "--version"]).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::DisplayVersion);
+        assert!(error.to_string().contains(LONG_VERSION));
+    }
+
+    #[test]
+    fn test_env_defaults_are_wired() {
+        // Flags win over environment variables by clap's own precedence, so only the
+        // wiring is checked; setting variables would race with tests running in parallel
+        let command = Cli::command();
+        let env = |subcommand: &str, arg: &str| {
+            command
+                .find_subcommand(subcommand)
+                .unwrap()
+                .get_arguments()
+                .find(|a| a.get_id() == arg)
+                .and_then(|a| a.get_env())
+                .map(|env| env.to_string_lossy().into_owned())
+        };
+
+        for subcommand in ["s3", "http"] {
+            assert_eq!(
+                env(subcommand, "concurrency").as_deref(),
+                Some("S3FCP_CONCURRENCY")
+            );
+            assert_eq!(env(subcommand, "quiet").as_deref(), Some("S3FCP_QUIET"));
+        }
+        // Every subcommand reading S3FCP_CHUNK_SIZE must accept the percentages it may hold
+        for subcommand in ["s3", "http", "batch"] {
+            assert_eq!(
+                env(subcommand, "chunk_size").as_deref(),
+                Some("S3FCP_CHUNK_SIZE")
+            );
+        }
+        assert_eq!(env("cp", "chunk_size").as_deref(), Some("S3FCP_PART_SIZE"));
+        for arg in command
+            .get_subcommands()
+            .flat_map(|s| s.get_arguments())
+            .chain(command.get_arguments())
+        {
+            if let Some(env) = arg.get_env() {
+                assert!(env.to_string_lossy().starts_with("S3FCP_"), "{:?}", env);
+            }
+        }
     }
 }
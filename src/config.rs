@@ -0,0 +1,259 @@
+use crate::cli::{parse_chunk_size, parse_download_chunk_size, parse_duration, ChunkSize, Command};
+use crate::error::{Result, S3FcpError};
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+/// Defaults read from the `--config` TOML file
+/// They apply only where neither a flag nor an S3FCP_* variable set the option
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Config {
+    /// S3 endpoint, used unless the AWS environment or profile configures one
+    pub endpoint_url: Option<String>,
+    /// AWS region, used unless the AWS environment or profile configures one
+    pub region: Option<String>,
+    pub concurrency: Option<usize>,
+    /// Chunk size of downloads; `cp` uses it as its part size unless it's a percentage
+    pub chunk_size: Option<ChunkSize>,
+    pub retry_deadline: Option<Duration>,
+}
+
+/// The file as written, before sizes and durations are parsed
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct ConfigFile {
+    endpoint_url: Option<String>,
+    region: Option<String>,
+    concurrency: Option<usize>,
+    chunk_size: Option<Size>,
+    retry_deadline: Option<String>,
+}
+
+/// A size given either as a byte count or a human-readable string like `16MiB`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Size {
+    Bytes(usize),
+    Text(String),
+}
+
+impl Config {
+    /// Read and validate the config file at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            S3FcpError::InvalidConfig(format!("cannot read {}: {}", path.display(), e))
+        })?;
+        Self::parse(&text)
+            .map_err(|e| S3FcpError::InvalidConfig(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Parse the TOML contents of a config file
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let file: ConfigFile = toml::from_str(text).map_err(|e| e.message().to_string())?;
+
+        if file.concurrency == Some(0) {
+            return Err("concurrency must be at least 1".to_string());
+        }
+        let chunk_size = match file.chunk_size {
+            Some(Size::Bytes(bytes)) => {
+                Some(ChunkSize::Bytes(parse_chunk_size(&bytes.to_string())?))
+            }
+            Some(Size::Text(text)) => Some(parse_download_chunk_size(&text)?),
+            None => None,
+        };
+        let retry_deadline = file
+            .retry_deadline
+            .as_deref()
+            .map(parse_duration)
+            .transpose()?;
+
+        Ok(Self {
+            endpoint_url: file.endpoint_url,
+            region: file.region,
+            concurrency: file.concurrency,
+            chunk_size,
+            retry_deadline,
+        })
+    }
+
+    /// Fill in the options of `command` that were left at their built-in defaults
+    /// `matches` are the matches `command` was parsed from
+    pub fn apply(&self, command: &mut Command, matches: &ArgMatches) {
+        let Some((_, matches)) = matches.subcommand() else {
+            return;
+        };
+
+        match command {
+            Command::S3(args) => {
+                merge(
+                    matches,
+                    "concurrency",
+                    &mut args.concurrency,
+                    self.concurrency,
+                );
+                merge(matches, "chunk_size", &mut args.chunk_size, self.chunk_size);
+                merge(
+                    matches,
+                    "retry_deadline",
+                    &mut args.retry_deadline,
+                    self.retry_deadline.map(Some),
+                );
+            }
+            Command::Http(args) => {
+                merge(
+                    matches,
+                    "concurrency",
+                    &mut args.concurrency,
+                    self.concurrency,
+                );
+                merge(matches, "chunk_size", &mut args.chunk_size, self.chunk_size);
+                merge(
+                    matches,
+                    "retry_deadline",
+                    &mut args.retry_deadline,
+                    self.retry_deadline.map(Some),
+                );
+            }
+            Command::Cp(args) => {
+                merge(
+                    matches,
+                    "concurrency",
+                    &mut args.concurrency,
+                    self.concurrency,
+                );
+                // A percentage has no object to resolve against before an upload
+                merge(
+                    matches,
+                    "chunk_size",
+                    &mut args.chunk_size,
+                    self.chunk_size.and_then(ChunkSize::fixed),
+                );
+            }
+            Command::Batch(args) => {
+                merge(
+                    matches,
+                    "concurrency",
+                    &mut args.concurrency,
+                    self.concurrency,
+                );
+                merge(matches, "chunk_size", &mut args.chunk_size, self.chunk_size);
+            }
+            Command::Presign(_) | Command::Ping(_) => {}
+        }
+    }
+}
+
+/// Overwrite `slot` with the file's `value` unless the user set option `id`
+fn merge<T>(matches: &ArgMatches, id: &str, slot: &mut T, value: Option<T>) {
+    let explicit = matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    );
+    if let (Some(value), false) = (value, explicit) {
+        *slot = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn parse_with(config: &Config, args: &[&str]) -> Command {
+        let matches = Cli::command().try_get_matches_from(args).unwrap();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap();
+        config.apply(&mut cli.command, &matches);
+        cli.command
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(
+            r#"
+            endpoint-url = "http://localhost:9000"
+            region = "eu-west-1"
+            concurrency = 32
+            chunk-size = "16MiB"
+            retry-deadline = "10m"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.endpoint_url.as_deref(),
+            Some("http://localhost:9000")
+        );
+        assert_eq!(config.region.as_deref(), Some("eu-west-1"));
+        assert_eq!(config.concurrency, Some(32));
+        assert_eq!(config.chunk_size, Some(ChunkSize::Bytes(16 * 1024 * 1024)));
+        assert_eq!(config.retry_deadline, Some(Duration::from_secs(600)));
+
+        assert_eq!(
+            Config::parse("chunk-size = 1048576").unwrap().chunk_size,
+            Some(ChunkSize::Bytes(1048576))
+        );
+        assert_eq!(
+            Config::parse("chunk-size = \"5%\"").unwrap().chunk_size,
+            Some(ChunkSize::Percent(5.0))
+        );
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_parse_config_rejects_invalid_values() {
+        assert!(Config::parse("concurency = 4")
+            .unwrap_err()
+            .contains("concurency"));
+        assert!(Config::parse("concurrency = 0").is_err());
+        assert!(Config::parse("chunk-size = \"0\"").is_err());
+        assert!(Config::parse("chunk-size = \"0%\"").is_err());
+        assert!(Config::parse("retry-deadline = \"soon\"").is_err());
+        assert!(Config::parse("concurrency = \"many\"").is_err());
+    }
+
+    #[test]
+    fn test_flags_override_config() {
+        let config = Config {
+            concurrency: Some(32),
+            chunk_size: Some(ChunkSize::Bytes(1024)),
+            retry_deadline: Some(Duration::from_secs(60)),
+            ..Config::default()
+        };
+
+        let Command::Http(args) = parse_with(
+            &config,
+            &["s3fcp", "http", "http://example.com/f", "-c", "4"],
+        ) else {
+            panic!("expected http command");
+        };
+        assert_eq!(args.concurrency, 4);
+        assert_eq!(args.chunk_size, ChunkSize::Bytes(1024));
+        assert_eq!(args.retry_deadline, Some(Duration::from_secs(60)));
+
+        let Command::Presign(_) = parse_with(&config, &["s3fcp", "presign", "s3://b/k"]) else {
+            panic!("expected presign command");
+        };
+    }
+
+    #[test]
+    fn test_config_chunk_percent_applies_to_downloads_only() {
+        let config = Config {
+            chunk_size: Some(ChunkSize::Percent(5.0)),
+            ..Config::default()
+        };
+
+        let Command::Batch(args) = parse_with(&config, &["s3fcp", "batch", "list", "-o", "out"])
+        else {
+            panic!("expected batch command");
+        };
+        assert_eq!(args.chunk_size, ChunkSize::Percent(5.0));
+
+        let Command::Cp(args) = parse_with(&config, &["s3fcp", "cp", "f", "s3://b/k"]) else {
+            panic!("expected cp command");
+        };
+        assert_eq!(args.chunk_size, 8_000_000);
+    }
+}
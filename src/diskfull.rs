@@ -0,0 +1,207 @@
+use std::future::Future;
+use std::io::{self, SeekFrom};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncSeek, AsyncWrite};
+use tokio::time::Sleep;
+
+/// First pause of `WaitOnDiskFull`, doubled after every failed retry
+pub const DISK_FULL_INITIAL_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest pause between two attempts to write to a full disk
+const DISK_FULL_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Whether `error` means the device has no space left
+pub fn is_disk_full(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::StorageFull
+}
+
+/// Writer over a new file that waits out a full disk instead of failing, for
+/// `--wait-on-diskfull`
+///
+/// Files may report a failed write only on a later call, after an unknown part of it
+/// reached the disk. So every write is flushed before it counts as written, and on
+/// `ENOSPC` the writer pauses, seeks back to the end of the confirmed bytes and writes
+/// the same buffer again. The pause doubles up to a minute, until an operator frees
+/// space. Other errors are returned as usual.
+pub struct WaitOnDiskFull<W> {
+    inner: W,
+    /// Bytes confirmed on disk, which is where the next write starts
+    position: u64,
+    initial_delay: Duration,
+    delay: Duration,
+    state: State,
+}
+
+enum State {
+    Idle,
+    /// This many bytes were handed to the inner writer and are being flushed
+    Flushing(usize),
+    Paused(Pin<Box<Sleep>>),
+    Seeking,
+}
+
+impl<W> WaitOnDiskFull<W> {
+    /// Wrap `inner`, which must be positioned at the start of an empty file
+    pub fn new(inner: W, initial_delay: Duration) -> Self {
+        Self {
+            inner,
+            position: 0,
+            initial_delay,
+            delay: initial_delay,
+            state: State::Idle,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: AsyncWrite + AsyncSeek + Unpin> WaitOnDiskFull<W> {
+    /// Pause before writing again, or return `error` if it isn't a full disk
+    fn pause_or_fail(&mut self, error: io::Error) -> io::Result<()> {
+        self.state = State::Idle;
+        if !is_disk_full(&error) {
+            return Err(error);
+        }
+        eprintln!(
+            "Warning: disk full, retrying the write in {}s; free some space to continue",
+            self.delay.as_secs_f64()
+        );
+        self.state = State::Paused(Box::pin(tokio::time::sleep(self.delay)));
+        self.delay = (self.delay * 2).min(DISK_FULL_MAX_DELAY);
+        Ok(())
+    }
+}
+
+impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncWrite for WaitOnDiskFull<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            match &mut this.state {
+                State::Idle => match ready!(Pin::new(&mut this.inner).poll_write(cx, buf)) {
+                    Ok(written) => this.state = State::Flushing(written),
+                    Err(e) => this.pause_or_fail(e)?,
+                },
+                State::Flushing(written) => {
+                    let written = *written;
+                    match ready!(Pin::new(&mut this.inner).poll_flush(cx)) {
+                        Ok(()) => {
+                            this.state = State::Idle;
+                            this.position += written as u64;
+                            this.delay = this.initial_delay;
+                            return Poll::Ready(Ok(written));
+                        }
+                        Err(e) => this.pause_or_fail(e)?,
+                    }
+                }
+                State::Paused(sleep) => {
+                    ready!(sleep.as_mut().poll(cx));
+                    Pin::new(&mut this.inner).start_seek(SeekFrom::Start(this.position))?;
+                    this.state = State::Seeking;
+                }
+                State::Seeking => {
+                    let result = ready!(Pin::new(&mut this.inner).poll_complete(cx));
+                    this.state = State::Idle;
+                    result?;
+                }
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Every completed write was already flushed
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tokio::io::AsyncWriteExt;
+
+    /// In-memory file whose flushes after the first `successes` report a full disk
+    /// `failures` times, after only half of the pending bytes were stored, like a file
+    /// writing in the background
+    struct FullDisk {
+        file: Cursor<Vec<u8>>,
+        pending: Vec<u8>,
+        successes: usize,
+        failures: usize,
+    }
+
+    impl AsyncWrite for FullDisk {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.pending.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let pending = std::mem::take(&mut self.pending);
+            if self.successes > 0 {
+                self.successes -= 1;
+            } else if self.failures > 0 {
+                self.failures -= 1;
+                std::io::Write::write_all(&mut self.file, &pending[..pending.len() / 2])?;
+                return Poll::Ready(Err(io::ErrorKind::StorageFull.into()));
+            }
+            std::io::Write::write_all(&mut self.file, &pending)?;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.poll_flush(cx)
+        }
+    }
+
+    impl AsyncSeek for FullDisk {
+        fn start_seek(mut self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+            std::io::Seek::seek(&mut self.file, position).map(|_| ())
+        }
+
+        fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+            Poll::Ready(Ok(self.file.position()))
+        }
+    }
+
+    fn full_disk(successes: usize, failures: usize) -> FullDisk {
+        FullDisk {
+            file: Cursor::new(Vec::new()),
+            pending: Vec::new(),
+            successes,
+            failures,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rewrites_lost_bytes_once_space_is_freed() {
+        let mut writer = WaitOnDiskFull::new(full_disk(1, 2), Duration::from_millis(10));
+        writer.write_all(b"hello ").await.unwrap();
+        writer.write_all(b"world").await.unwrap();
+
+        assert_eq!(writer.into_inner().file.into_inner(), b"hello world");
+    }
+
+    #[tokio::test]
+    async fn test_full_disk_fails_without_wrapper() {
+        let mut disk = full_disk(0, 1);
+        disk.write_all(b"hello").await.unwrap();
+        let error = disk.flush().await.unwrap_err();
+        assert!(is_disk_full(&error));
+    }
+}
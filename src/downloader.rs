@@ -1,42 +1,310 @@
-use crate::chunk::{create_chunks, Chunk, DownloadedChunk};
-use crate::cli::DownloadArgs;
-use crate::error::Result;
+use crate::adaptive::WorkerGate;
+use crate::chunk::{
+    auto_chunk_size, create_chunks, create_part_chunks, create_range_chunks, multipart_part_count,
+    ByteRange, Chunk, DownloadedChunk,
+};
+use crate::cli::{Compression, DownloadArgs};
+use crate::diskfull::{is_disk_full, WaitOnDiskFull, DISK_FULL_INITIAL_DELAY};
+use crate::error::{Result, S3FcpError};
+use crate::events::{emit, ChunkEvent, DownloadListener};
 use crate::progress::ProgressTracker;
-use crate::s3_client::DownloadClient;
+use crate::s3_client::{DownloadClient, ObjectMetadata};
+use crate::segment::{remove_segments, rename_segments, SegmentingSink};
+use crate::terminal::check_terminal_output;
+use crate::text::TextWriter;
+use crate::verify::{
+    check_content_md5, check_sha256, to_hex, CountingWriter, DigestAlgorithm, Md5Writer,
+    Sha256Writer,
+};
+use async_compression::tokio::write::{GzipEncoder, ZstdEncoder};
+use async_trait::async_trait;
 use backon::{ExponentialBuilder, Retryable};
+use bytes::{Bytes, BytesMut};
 use std::collections::BTreeMap;
-use std::sync::Arc;
-use tokio::io::{self, AsyncWriteExt};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{self, AsyncWriteExt, BufWriter};
+use tokio::sync::{watch, Semaphore};
+use tokio::task::JoinSet;
+
+/// Limit on how far ahead of the output chunks may be downloaded
+///
+/// Chunk `i` is only queued once `i < next + size`, where `next` is the index the
+/// ordered writer is waiting for, so at most `size` chunks are downloading or
+/// waiting to be written at any time.
+pub(crate) struct ReorderWindow {
+    size: usize,
+    next: watch::Receiver<usize>,
+}
+
+impl ReorderWindow {
+    /// Window of `size` chunks (at least one) and the sender the writer reports its
+    /// next expected index through
+    fn new(size: usize) -> (Self, watch::Sender<usize>) {
+        let (tx, next) = watch::channel(0);
+        let window = Self {
+            size: size.max(1),
+            next,
+        };
+        (window, tx)
+    }
+
+    /// Wait until chunk `index` is inside the window
+    async fn admit(&mut self, index: usize) {
+        let size = self.size;
+        // A dropped writer means the download is failing; the queue is aborted with it
+        let _ = self
+            .next
+            .wait_for(|next| index < next.saturating_add(size))
+            .await;
+    }
+}
 
 /// Stage 1: Queue up download jobs
 /// Sends chunks to a bounded channel, providing natural backpressure
-async fn queue_chunks(chunks: Vec<Chunk>, tx: flume::Sender<Chunk>) -> Result<()> {
+/// With a `window`, each chunk waits for the output to come within reach first
+pub(crate) async fn queue_chunks(
+    chunks: Vec<Chunk>,
+    tx: flume::Sender<Chunk>,
+    listener: Option<Arc<dyn DownloadListener>>,
+    mut window: Option<ReorderWindow>,
+) -> Result<()> {
     for chunk in chunks {
-        tx.send_async(chunk).await.map_err(|e| {
-            crate::error::S3FcpError::DownloadFailed(format!("Failed to queue chunk: {}", e))
-        })?;
+        if let Some(window) = &mut window {
+            window.admit(chunk.index).await;
+        }
+        emit(listener.as_deref(), || ChunkEvent::Queued {
+            index: chunk.index,
+            start: chunk.start,
+            end: chunk.end,
+        });
+        tx.send_async(chunk)
+            .await
+            .map_err(|e| S3FcpError::DownloadFailed(format!("Failed to queue chunk: {}", e)))?;
     }
     Ok(())
 }
 
+pub(crate) fn retry_policy() -> ExponentialBuilder {
+    ExponentialBuilder::default()
+        .with_max_times(3)
+        .with_min_delay(std::time::Duration::from_millis(100))
+        .with_max_delay(std::time::Duration::from_secs(5))
+}
+
+/// Backoff for `--retry-deadline`: no attempt limit, and the delay keeps growing
+/// up to a quarter of the budget, so long throttling periods are waited out
+fn deadline_retry_policy(budget: Duration) -> ExponentialBuilder {
+    ExponentialBuilder::default()
+        .without_max_times()
+        .with_min_delay(Duration::from_millis(100))
+        .with_max_delay((budget / 4).max(Duration::from_secs(5)))
+        .with_total_delay(Some(budget))
+}
+
+/// Retry state shared by all requests for one chunk
+struct ChunkRetry {
+    attempts: AtomicUsize,
+    policy: ExponentialBuilder,
+    /// No retry starts after this; only set with a retry deadline
+    deadline: Option<Instant>,
+}
+
+impl ChunkRetry {
+    fn new(retry_deadline: Option<Duration>) -> Self {
+        Self {
+            attempts: AtomicUsize::new(0),
+            policy: retry_deadline.map_or_else(retry_policy, deadline_retry_policy),
+            deadline: retry_deadline.and_then(|budget| Instant::now().checked_add(budget)),
+        }
+    }
+
+    /// Whether the chunk's time budget leaves room for another attempt
+    fn allows_retry(&self) -> bool {
+        self.deadline
+            .is_none_or(|deadline| Instant::now() < deadline)
+    }
+}
+
+/// Request an inclusive byte range with retries, reporting every attempt
+/// A retry after a mid-transfer failure only requests the bytes not yet received
+async fn request_range(
+    client: &dyn DownloadClient,
+    index: usize,
+    start: u64,
+    end: u64,
+    retry: &ChunkRetry,
+    listener: Option<&dyn DownloadListener>,
+) -> Result<Bytes> {
+    let received = Mutex::new(BytesMut::new());
+
+    (|| async {
+        let offset = start + received.lock().unwrap().len() as u64;
+        if offset > end {
+            return Ok(());
+        }
+
+        let attempt = retry.attempts.fetch_add(1, Ordering::Relaxed) + 1;
+        emit(listener, || ChunkEvent::Started { index, attempt });
+
+        let mut part = BytesMut::new();
+        let result = client.get_range_into(offset, end, &mut part).await;
+        received.lock().unwrap().extend_from_slice(&part);
+        result
+    })
+    .retry(retry.policy)
+    .when(|e| e.is_retryable() && retry.allows_retry())
+    .notify(|e, delay| {
+        emit(listener, || ChunkEvent::Retried {
+            index,
+            attempt: retry.attempts.load(Ordering::Relaxed),
+            delay,
+            error: e.to_string(),
+        })
+    })
+    .await?;
+
+    Ok(received.into_inner().unwrap().freeze())
+}
+
+/// Request a part of the original multipart upload with retries, reporting every attempt
+/// Parts can't be combined with ranges, so a retry fetches the whole part again
+async fn request_part(
+    client: &dyn DownloadClient,
+    index: usize,
+    part_number: u32,
+    retry: &ChunkRetry,
+    listener: Option<&dyn DownloadListener>,
+) -> Result<Bytes> {
+    (|| async {
+        let attempt = retry.attempts.fetch_add(1, Ordering::Relaxed) + 1;
+        emit(listener, || ChunkEvent::Started { index, attempt });
+        client.get_part(part_number).await
+    })
+    .retry(retry.policy)
+    .when(|e| e.is_retryable() && retry.allows_retry())
+    .notify(|e, delay| {
+        emit(listener, || ChunkEvent::Retried {
+            index,
+            attempt: retry.attempts.load(Ordering::Relaxed),
+            delay,
+            error: e.to_string(),
+        })
+    })
+    .await
+}
+
+/// Download a chunk, tolerating short reads
+/// Some S3-compatible gateways return fewer bytes than requested, so the missing
+/// tail of the range is re-requested until the range is complete
+pub(crate) async fn fetch_range(
+    client: &dyn DownloadClient,
+    chunk: &Chunk,
+    retry_deadline: Option<Duration>,
+    listener: Option<&dyn DownloadListener>,
+) -> Result<Bytes> {
+    let retry = ChunkRetry::new(retry_deadline);
+    let result = fetch_range_inner(client, chunk, &retry, listener).await;
+
+    match &result {
+        Ok(data) => emit(listener, || ChunkEvent::Completed {
+            index: chunk.index,
+            bytes: data.len() as u64,
+        }),
+        Err(e) => emit(listener, || ChunkEvent::Failed {
+            index: chunk.index,
+            error: e.to_string(),
+        }),
+    }
+
+    result
+}
+
+async fn fetch_range_inner(
+    client: &dyn DownloadClient,
+    chunk: &Chunk,
+    retry: &ChunkRetry,
+    listener: Option<&dyn DownloadListener>,
+) -> Result<Bytes> {
+    let (start, end) = (chunk.start, chunk.end);
+    let expected = end - start + 1;
+
+    if let Some(part_number) = chunk.part_number {
+        let data = request_part(client, chunk.index, part_number, retry, listener).await?;
+        if data.len() as u64 != expected {
+            return Err(S3FcpError::DownloadFailed(format!(
+                "Part {} has {} bytes, expected {}; the object's parts differ in size",
+                part_number,
+                data.len(),
+                expected
+            )));
+        }
+        return Ok(data);
+    }
+
+    let data = request_range(client, chunk.index, start, end, retry, listener).await?;
+
+    if data.len() as u64 == expected {
+        return Ok(data);
+    }
+
+    let mut buffer = BytesMut::from(data);
+    while (buffer.len() as u64) < expected {
+        let tail_start = start + buffer.len() as u64;
+        let tail = request_range(client, chunk.index, tail_start, end, retry, listener).await?;
+
+        if tail.is_empty() {
+            return Err(S3FcpError::DownloadFailed(format!(
+                "Server returned no data for range {}-{}",
+                tail_start, end
+            )));
+        }
+
+        buffer.extend_from_slice(&tail);
+    }
+
+    if buffer.len() as u64 != expected {
+        return Err(S3FcpError::DownloadFailed(format!(
+            "Expected {} bytes for range {}-{}, got {}",
+            expected,
+            start,
+            end,
+            buffer.len()
+        )));
+    }
+
+    Ok(buffer.freeze())
+}
+
 /// Stage 2: Download worker
 /// Pulls chunks from the queue and downloads them with retry logic
+/// With a `gate`, the worker holds one of its permits while it downloads a chunk
 async fn download_worker(
     client: Arc<dyn DownloadClient>,
     rx: flume::Receiver<Chunk>,
     output_tx: flume::Sender<DownloadedChunk>,
     progress: Arc<ProgressTracker>,
+    retry_deadline: Option<Duration>,
+    listener: Option<Arc<dyn DownloadListener>>,
+    gate: Option<Arc<Semaphore>>,
 ) -> Result<()> {
-    while let Ok(chunk) = rx.recv_async().await {
+    loop {
+        let _permit = match &gate {
+            Some(gate) => Some(gate.acquire().await.expect("worker gate is never closed")),
+            None => None,
+        };
+        let Ok(chunk) = rx.recv_async().await else {
+            break;
+        };
+
         // Download with retry logic using backon
-        let data = (|| async { client.get_range(chunk.start, chunk.end).await })
-            .retry(
-                ExponentialBuilder::default()
-                    .with_max_times(3)
-                    .with_min_delay(std::time::Duration::from_millis(100))
-                    .with_max_delay(std::time::Duration::from_secs(5)),
-            )
-            .await?;
+        progress.chunk_started();
+        let data =
+            fetch_range(client.as_ref(), &chunk, retry_deadline, listener.as_deref()).await?;
+        progress.chunk_finished();
 
         let data_len = data.len() as u64;
         progress.increment(data_len);
@@ -48,10 +316,7 @@ async fn download_worker(
             })
             .await
             .map_err(|e| {
-                crate::error::S3FcpError::DownloadFailed(format!(
-                    "Failed to send downloaded chunk: {}",
-                    e
-                ))
+                S3FcpError::DownloadFailed(format!("Failed to send downloaded chunk: {}", e))
             })?;
     }
 
@@ -59,19 +324,28 @@ async fn download_worker(
 }
 
 /// Stage 3: Ordered output writer
-/// Receives chunks (potentially out of order) and writes them in correct order
+/// Receives chunks (potentially out of order) and writes them in correct order,
+/// flushing at least every `flush_interval` so concurrent readers see progress
+/// The index of the next chunk to write is published on `next_tx` for the reorder window
 async fn ordered_output_writer<W>(
     rx: flume::Receiver<DownloadedChunk>,
     total_chunks: usize,
     mut writer: W,
+    flush_interval: Duration,
+    next_tx: Option<watch::Sender<usize>>,
+    strict: bool,
 ) -> Result<W>
 where
     W: AsyncWriteExt + Unpin,
 {
     let mut buffer: BTreeMap<usize, DownloadedChunk> = BTreeMap::new();
     let mut next_expected = 0;
+    let mut last_flush = Instant::now();
 
     while let Ok(chunk) = rx.recv_async().await {
+        if strict {
+            check_chunk_order(&chunk, next_expected, total_chunks, &buffer)?;
+        }
         // Insert the chunk into the buffer
         buffer.insert(chunk.index, chunk);
 
@@ -79,6 +353,14 @@ where
         while let Some(chunk) = buffer.remove(&next_expected) {
             writer.write_all(&chunk.data).await?;
             next_expected += 1;
+            if let Some(next_tx) = &next_tx {
+                next_tx.send_replace(next_expected);
+            }
+
+            if !flush_interval.is_zero() && last_flush.elapsed() >= flush_interval {
+                writer.flush().await?;
+                last_flush = Instant::now();
+            }
 
             // If we've written all chunks, we're done
             if next_expected == total_chunks {
@@ -88,11 +370,48 @@ where
         }
     }
 
+    if strict && next_expected < total_chunks {
+        // A stage that fails closes the channel too, and its error takes precedence
+        // over this one when the pipeline is joined
+        return Err(ordering_violation(format!(
+            "the writer stopped at chunk {} of {}, with {} chunks buffered",
+            next_expected,
+            total_chunks,
+            buffer.len()
+        )));
+    }
+
     // Ensure all data is flushed
     writer.flush().await?;
     Ok(writer)
 }
 
+/// For `--strict-ordering`: every chunk reaches the writer exactly once, within range
+fn check_chunk_order(
+    chunk: &DownloadedChunk,
+    next_expected: usize,
+    total_chunks: usize,
+    buffer: &BTreeMap<usize, DownloadedChunk>,
+) -> Result<()> {
+    if chunk.index >= total_chunks {
+        return Err(ordering_violation(format!(
+            "chunk {} is out of range, the download has {} chunks",
+            chunk.index, total_chunks
+        )));
+    }
+    if chunk.index < next_expected || buffer.contains_key(&chunk.index) {
+        return Err(ordering_violation(format!(
+            "chunk {} arrived twice",
+            chunk.index
+        )));
+    }
+    Ok(())
+}
+
+fn ordering_violation(reason: String) -> S3FcpError {
+    S3FcpError::DownloadFailed(format!("ordering invariant violated: {}", reason))
+}
+
 /// Download using chunked parallel requests
 pub async fn download_chunked<W>(
     client: Arc<dyn DownloadClient>,
@@ -108,71 +427,159 @@ where
         return Ok(writer);
     }
 
-    // Create chunks
-    let chunks = create_chunks(content_length, args.chunk_size);
+    let chunk_size = args
+        .chunk_size_for(content_length)
+        .map_err(S3FcpError::InvalidRange)?;
+    let chunk_size = if args.auto_chunk {
+        auto_chunk_size(content_length, chunk_size, args.concurrency)
+    } else {
+        chunk_size
+    };
+    let chunks = create_chunks(content_length, chunk_size);
+    download_chunks(client, args, chunks, writer).await
+}
+
+/// Run the queue/worker/writer pipeline over an explicit list of chunks,
+/// writing them in index order
+async fn download_chunks<W>(
+    client: Arc<dyn DownloadClient>,
+    args: DownloadArgs,
+    chunks: Vec<Chunk>,
+    writer: W,
+) -> Result<W>
+where
+    W: AsyncWriteExt + Unpin + Send + 'static,
+{
     let total_chunks = chunks.len();
+    let total_bytes = chunks.iter().map(|c| c.end - c.start + 1).sum();
 
     // Setup progress tracker
-    let progress = ProgressTracker::new(content_length, args.quiet);
+    let progress = progress_tracker(total_bytes, &args);
+    if args.progress_detail {
+        progress.show_chunk_detail(total_chunks);
+    }
+
+    // Zero workers would leave the queue undrained forever
+    let concurrency = args.concurrency.max(1);
+
+    // In adaptive mode all workers are spawned, but only those with a permit download
+    let gate = args
+        .adaptive_concurrency
+        .then(|| WorkerGate::start(concurrency, progress.clone()));
 
     // Setup channels for the 3 stages
-    let (chunk_tx, chunk_rx) = flume::bounded(args.concurrency);
-    let (output_tx, output_rx) = flume::bounded(args.concurrency * 2);
+    let (chunk_tx, chunk_rx) = flume::bounded(concurrency);
+    let (output_tx, output_rx) = flume::bounded(concurrency * 2);
+
+    // Every stage runs in one set, so a failure or panic in any of them aborts the rest
+    // instead of leaving a stage blocked on a channel nobody drains
+    let mut stages = JoinSet::new();
+
+    let (window, next_tx) = match args.reorder_window {
+        Some(size) => {
+            let (window, next_tx) = ReorderWindow::new(size);
+            (Some(window), Some(next_tx))
+        }
+        None => (None, None),
+    };
+
+    let listener = match &args.timings {
+        Some(timings) => Some(timings.chunk_listener(args.listener.clone())),
+        None => args.listener.clone(),
+    };
 
     // Spawn Stage 1: Queue
-    let queue_handle = tokio::spawn(queue_chunks(chunks, chunk_tx));
+    let queue_listener = listener.clone();
+    stages.spawn(async move {
+        queue_chunks(chunks, chunk_tx, queue_listener, window).await?;
+        Ok(None)
+    });
 
     // Spawn Stage 2: Download workers (worker pool)
-    let mut download_handles = vec![];
-    for _ in 0..args.concurrency {
-        let worker_handle = tokio::spawn(download_worker(
-            client.clone(),
+    for _ in 0..concurrency {
+        let client = match &args.timings {
+            Some(timings) => timings.worker_client(client.clone()),
+            None => client.clone(),
+        };
+        let worker = download_worker(
+            client,
             chunk_rx.clone(),
             output_tx.clone(),
             progress.clone(),
-        ));
-        download_handles.push(worker_handle);
+            args.retry_deadline,
+            listener.clone(),
+            gate.as_ref().map(WorkerGate::permits),
+        );
+        stages.spawn(async move {
+            worker.await?;
+            Ok(None)
+        });
     }
 
-    // Spawn Stage 3: Ordered output
-    let output_handle = tokio::spawn(ordered_output_writer(output_rx, total_chunks, writer));
-
-    // Await Stage 1 completion and drop sender
-    queue_handle.await??;
-    // Sender is dropped here, so workers will receive Err and exit
+    // Spawn Stage 3: Ordered output, which hands the writer back
+    let output = ordered_output_writer(
+        output_rx,
+        total_chunks,
+        writer,
+        args.flush_interval,
+        next_tx,
+        args.strict_ordering,
+    );
+    stages.spawn(async move { output.await.map(Some) });
 
-    // Await all workers
-    for handle in download_handles {
-        handle.await??;
-    }
-    // Drop output sender so output writer knows when to stop
+    // Only the tasks may hold channel ends, so each stage sees its peers exit
+    drop(chunk_rx);
     drop(output_tx);
 
-    // Await output stage completion and get writer back
-    let writer = output_handle.await??;
+    let mut writer = None;
+    while let Some(result) = stages.join_next().await {
+        match result {
+            Ok(Ok(Some(output))) => writer = Some(output),
+            Ok(Ok(None)) => {}
+            Ok(Err(e)) => {
+                stages.abort_all();
+                return Err(e);
+            }
+            Err(e) => {
+                stages.abort_all();
+                return Err(e.into());
+            }
+        }
+    }
 
     // Finish progress
     progress.finish();
 
-    Ok(writer)
+    Ok(writer.expect("output stage returns the writer"))
+}
+
+/// Progress tracker for `total_bytes`, drawn and finished the way `args` ask
+pub(crate) fn progress_tracker(total_bytes: u64, args: &DownloadArgs) -> Arc<ProgressTracker> {
+    let quiet = args.quiet || args.summary_only;
+    let progress = ProgressTracker::new(total_bytes, quiet, args.progress_delay);
+    progress.use_template(args.progress_template.clone());
+    if args.keep_progress {
+        progress.keep_on_finish();
+    }
+    if args.summary_only {
+        progress.summarize_on_finish();
+    }
+    progress
 }
 
 /// Download using a single stream (for servers without Range support)
+/// Only the progress settings of `args` apply
 pub async fn download_single_stream<W>(
     client: Arc<dyn DownloadClient>,
     content_length: u64,
-    quiet: bool,
+    args: &DownloadArgs,
     mut writer: W,
 ) -> Result<W>
 where
     W: AsyncWriteExt + Unpin,
 {
-    // Handle edge case: empty file
-    if content_length == 0 {
-        return Ok(writer);
-    }
-
-    let progress = ProgressTracker::new(content_length, quiet);
+    // A zero length may also mean the source doesn't know it, so the body is fetched anyway
+    let progress = progress_tracker(content_length, args);
 
     // Download entire file in a single request
     let data = client.get_full().await?;
@@ -185,7 +592,50 @@ where
     Ok(writer)
 }
 
+/// Fetch the object's size, range support and other metadata without downloading it
+/// Transient failures are retried like chunk requests
+pub async fn probe(client: &dyn DownloadClient) -> Result<ObjectMetadata> {
+    (|| client.head())
+        .retry(retry_policy())
+        .when(S3FcpError::is_retryable)
+        .await
+}
+
+/// `probe`, failing once `timeout` passes without an answer
+/// The HEAD request of an unreachable endpoint fails fast this way, while chunk requests
+/// keep their own retries
+pub async fn probe_within(
+    client: &dyn DownloadClient,
+    timeout: Option<Duration>,
+) -> Result<ObjectMetadata> {
+    let Some(timeout) = timeout else {
+        return probe(client).await;
+    };
+    tokio::time::timeout(timeout, probe(client))
+        .await
+        .map_err(|_| {
+            S3FcpError::DownloadFailed(format!(
+                "no answer to the HEAD request within {:?}",
+                timeout
+            ))
+        })?
+}
+
+/// Fail unless the object is the size given with `--expected-size`
+pub fn check_expected_size(metadata: &ObjectMetadata, expected: Option<u64>) -> Result<()> {
+    match expected {
+        Some(expected) if metadata.content_length != expected => {
+            Err(S3FcpError::IntegrityError(format!(
+                "the object is {} bytes, expected {}",
+                metadata.content_length, expected
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
 /// Main download function - chooses strategy based on server capabilities
+/// and whether a single request was explicitly requested
 pub async fn download<W>(
     client: Arc<dyn DownloadClient>,
     args: DownloadArgs,
@@ -194,17 +644,1395 @@ pub async fn download<W>(
 where
     W: AsyncWriteExt + Unpin + Send + 'static,
 {
+    let client = match &args.rate_limit {
+        Some(limiter) => limiter.client(client),
+        None => client,
+    };
+
     // HEAD request to get content_length and check Range support
-    let metadata = client.head().await?;
+    let head_started = Instant::now();
+    let mut metadata = probe_within(client.as_ref(), args.head_timeout).await?;
+    if let Some(timings) = &args.timings {
+        timings.record_head(head_started.elapsed());
+    }
+    check_expected_size(&metadata, args.expected_size)?;
+    if args.check_terminal {
+        check_terminal_output(&metadata, args.compress.is_some())?;
+    }
+
+    if args.force_chunked && !metadata.supports_range && metadata.content_length > 0 {
+        metadata.supports_range = probe_range_support(client.as_ref()).await?;
+    }
+
+    match args.compress {
+        None => download_normalized(client, args, &metadata, writer).await,
+        Some(Compression::Gzip) => {
+            let encoder = GzipEncoder::new(writer);
+            let mut encoder = download_normalized(client, args, &metadata, encoder).await?;
+            encoder.shutdown().await?;
+            Ok(encoder.into_inner())
+        }
+        Some(Compression::Zstd) => {
+            let encoder = ZstdEncoder::new(writer);
+            let mut encoder = download_normalized(client, args, &metadata, encoder).await?;
+            encoder.shutdown().await?;
+            Ok(encoder.into_inner())
+        }
+    }
+}
+
+/// Download the object, rewriting its line endings first when `--text-normalize` is given
+async fn download_normalized<W>(
+    client: Arc<dyn DownloadClient>,
+    args: DownloadArgs,
+    metadata: &ObjectMetadata,
+    writer: W,
+) -> Result<W>
+where
+    W: AsyncWriteExt + Unpin + Send + 'static,
+{
+    match args.text_normalize {
+        Some(mode) => {
+            let writer = TextWriter::new(writer, mode);
+            let writer = download_verified(client, args, metadata, writer).await?;
+            Ok(writer.finish().await?)
+        }
+        None => download_verified(client, args, metadata, writer).await,
+    }
+}
+
+/// Download the object, checking it against an expected SHA-256 and its `Content-MD5`
+/// when requested
+/// The digests are updated as the ordered chunks are written and cover the downloaded
+/// bytes, before any output compression; they are also recorded in `args.stats`
+async fn download_verified<W>(
+    client: Arc<dyn DownloadClient>,
+    mut args: DownloadArgs,
+    metadata: &ObjectMetadata,
+    writer: W,
+) -> Result<W>
+where
+    W: AsyncWriteExt + Unpin + Send + 'static,
+{
+    // Digests cover the whole object, so partial downloads can't be verified
+    if args.is_partial() {
+        return download_object(client, args, metadata, writer).await;
+    }
+
+    let expected = args.sha256.take();
+    let stats = args.stats.clone();
+    if expected.is_none()
+        && !stats
+            .as_ref()
+            .is_some_and(|s| s.wants(DigestAlgorithm::Sha256))
+    {
+        return download_md5_verified(client, args, metadata, writer).await;
+    }
+
+    let writer = download_md5_verified(client, args, metadata, Sha256Writer::new(writer)).await?;
+    let (writer, digest) = writer.finish();
+    let actual = to_hex(&digest);
+    if let Some(stats) = &stats {
+        stats.record(DigestAlgorithm::Sha256, actual.clone());
+    }
+    if let Some(expected) = expected {
+        check_sha256(&actual, &expected)?;
+    }
+    Ok(writer)
+}
+
+async fn download_md5_verified<W>(
+    client: Arc<dyn DownloadClient>,
+    args: DownloadArgs,
+    metadata: &ObjectMetadata,
+    writer: W,
+) -> Result<W>
+where
+    W: AsyncWriteExt + Unpin + Send + 'static,
+{
+    let expected = metadata.content_md5.clone().filter(|_| args.verify);
+    let stats = args.stats.clone();
+    if expected.is_none()
+        && !stats
+            .as_ref()
+            .is_some_and(|s| s.wants(DigestAlgorithm::Md5))
+    {
+        return download_object(client, args, metadata, writer).await;
+    }
+
+    let writer = download_object(client, args, metadata, Md5Writer::new(writer)).await?;
+    let (writer, digest) = writer.finish();
+    if let Some(stats) = &stats {
+        stats.record(DigestAlgorithm::Md5, to_hex(&digest));
+    }
+    if let Some(expected) = expected {
+        check_content_md5(&digest, &expected)?;
+    }
+    Ok(writer)
+}
+
+/// Check with a one-byte range request whether the server honors ranges it
+/// doesn't advertise; a full `200` response means it doesn't
+async fn probe_range_support(client: &dyn DownloadClient) -> Result<bool> {
+    match client.get_range(0, 0).await {
+        Ok(_) => Ok(true),
+        Err(S3FcpError::RangeNotSupported(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
 
-    if metadata.supports_range {
+async fn download_object<W>(
+    client: Arc<dyn DownloadClient>,
+    args: DownloadArgs,
+    metadata: &ObjectMetadata,
+    writer: W,
+) -> Result<W>
+where
+    W: AsyncWriteExt + Unpin + Send + 'static,
+{
+    if args.is_partial() {
+        if !metadata.supports_range {
+            return Err(S3FcpError::InvalidRange(
+                "the server does not support range requests".to_string(),
+            ));
+        }
+        let ranges = if args.ranges.is_empty() {
+            let range =
+                ByteRange::open_ended(args.start_byte, args.end_byte, metadata.content_length)
+                    .map_err(S3FcpError::InvalidRange)?;
+            vec![range]
+        } else {
+            args.ranges.clone()
+        };
+        let chunk_size = args
+            .chunk_size_for(metadata.content_length)
+            .map_err(S3FcpError::InvalidRange)?;
+        let chunks = create_range_chunks(&ranges, metadata.content_length, chunk_size)
+            .map_err(S3FcpError::InvalidRange)?;
+        return download_chunks(client, args, chunks, writer).await;
+    }
+
+    if metadata.supports_range && !args.single_stream {
+        if args.by_part {
+            if let Some(chunks) = part_chunks(client.as_ref(), metadata).await? {
+                return download_chunks(client, args, chunks, writer).await;
+            }
+        }
         download_chunked(client, args, metadata.content_length, writer).await
     } else {
-        download_single_stream(client, metadata.content_length, args.quiet, writer).await
+        let client = match &args.timings {
+            Some(timings) => timings.worker_client(client),
+            None => client,
+        };
+        download_single_stream(client, metadata.content_length, &args, writer).await
     }
 }
 
+/// One chunk per part if the ETag shows a multipart upload whose parts can be requested
+/// `None` means the object should be downloaded by byte ranges instead
+async fn part_chunks(
+    client: &dyn DownloadClient,
+    metadata: &ObjectMetadata,
+) -> Result<Option<Vec<Chunk>>> {
+    let Some(part_count) = metadata.etag.as_deref().and_then(multipart_part_count) else {
+        return Ok(None);
+    };
+    let Some(part_size) = client.part_size(1).await? else {
+        return Ok(None);
+    };
+    Ok(create_part_chunks(
+        metadata.content_length,
+        part_count,
+        part_size,
+    ))
+}
+
+/// Download through a `BufWriter` of `args.write_buffer_size` bytes, so small
+/// chunks are written with fewer syscalls
+/// With `args.report_bytes` the number of bytes written is printed to stderr
+async fn download_buffered<W>(
+    client: Arc<dyn DownloadClient>,
+    args: DownloadArgs,
+    writer: W,
+) -> Result<W>
+where
+    W: AsyncWriteExt + Unpin + Send + 'static,
+{
+    download_counted(client, args, CountingWriter::new(writer))
+        .await
+        .map(CountingWriter::into_inner)
+}
+
+/// `download_buffered` into a writer whose count outlives a failed download
+async fn download_counted<W>(
+    client: Arc<dyn DownloadClient>,
+    args: DownloadArgs,
+    writer: CountingWriter<W>,
+) -> Result<CountingWriter<W>>
+where
+    W: AsyncWriteExt + Unpin + Send + 'static,
+{
+    let report_bytes = args.report_bytes;
+    let writer = BufWriter::with_capacity(args.write_buffer_size, writer);
+    let mut writer = download(client, args, writer).await?;
+    writer.flush().await?;
+
+    let writer = writer.into_inner();
+    if report_bytes {
+        eprintln!("wrote {} bytes", writer.count());
+    }
+    Ok(writer)
+}
+
+/// How much output a failed download had already written to a stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IncompleteOutput {
+    pub written: u64,
+    /// Size of the complete output, when it is the whole object as stored
+    pub expected: Option<u64>,
+}
+
+impl fmt::Display for IncompleteOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.expected {
+            Some(expected) => write!(
+                f,
+                "output is incomplete, {} of {} bytes written",
+                self.written, expected
+            ),
+            None => write!(
+                f,
+                "output is incomplete, only {} bytes written",
+                self.written
+            ),
+        }
+    }
+}
+
+/// Download into a stream that can't be removed on failure, like a pipe
+/// A failed download that had written some output also returns how much, since the
+/// consumer has received a prefix of the object it can't tell from the whole
+pub async fn download_to_stream<W>(
+    client: Arc<dyn DownloadClient>,
+    args: DownloadArgs,
+    writer: W,
+) -> std::result::Result<W, (S3FcpError, Option<IncompleteOutput>)>
+where
+    W: AsyncWriteExt + Unpin + Send + 'static,
+{
+    // Compressed, normalized or partial output has no size known up front
+    let whole_object =
+        !args.is_partial() && args.compress.is_none() && args.text_normalize.is_none();
+    let client = Arc::new(HeadRecorder {
+        inner: client,
+        metadata: OnceLock::new(),
+    });
+    let writer = CountingWriter::new(writer);
+    let written = writer.counter();
+
+    match download_counted(client.clone(), args, writer).await {
+        Ok(writer) => Ok(writer.into_inner()),
+        Err(e) => {
+            let written = written.load(Ordering::Relaxed);
+            let incomplete = (written > 0).then(|| IncompleteOutput {
+                written,
+                expected: client
+                    .metadata
+                    .get()
+                    .filter(|_| whole_object)
+                    .map(|metadata| metadata.content_length),
+            });
+            Err((e, incomplete))
+        }
+    }
+}
+
+/// Download to stdout, warning on stderr when a failure leaves the output incomplete
 pub async fn download_to_stdout(client: Arc<dyn DownloadClient>, args: DownloadArgs) -> Result<()> {
-    download(client, args, io::stdout()).await?;
+    match download_to_stream(client, args, io::stdout()).await {
+        Ok(_) => Ok(()),
+        Err((e, incomplete)) => {
+            if let Some(incomplete) = incomplete {
+                eprintln!("Warning: {}", incomplete);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Keeps the metadata of the HEAD request, so a failed download can still tell the
+/// size of the object
+struct HeadRecorder {
+    inner: Arc<dyn DownloadClient>,
+    metadata: OnceLock<ObjectMetadata>,
+}
+
+#[async_trait]
+impl DownloadClient for HeadRecorder {
+    async fn head(&self) -> Result<ObjectMetadata> {
+        let metadata = self.inner.head().await?;
+        let _ = self.metadata.set(metadata.clone());
+        Ok(metadata)
+    }
+
+    async fn get_range(&self, start: u64, end: u64) -> Result<Bytes> {
+        self.inner.get_range(start, end).await
+    }
+
+    async fn get_full(&self) -> Result<Bytes> {
+        self.inner.get_full().await
+    }
+
+    async fn get_range_into(&self, start: u64, end: u64, buffer: &mut BytesMut) -> Result<()> {
+        self.inner.get_range_into(start, end, buffer).await
+    }
+
+    async fn part_size(&self, part_number: u32) -> Result<Option<u64>> {
+        self.inner.part_size(part_number).await
+    }
+
+    async fn get_part(&self, part_number: u32) -> Result<Bytes> {
+        self.inner.get_part(part_number).await
+    }
+}
+
+/// Download into the file at `path`, creating or truncating it
+///
+/// With `args.split_size` the output goes to `path.000`, `path.001`, … instead; with
+/// `args.atomic` they are written under temporary names and renamed once all are complete.
+/// With `args.fsync` the file is synced to disk before returning.
+/// A full disk fails with `DiskFull`, unless `args.wait_on_diskfull` pauses the
+/// output until space is freed; split output always fails.
+pub async fn download_to_file(
+    client: Arc<dyn DownloadClient>,
+    args: DownloadArgs,
+    path: &Path,
+) -> Result<()> {
+    download_to_file_inner(client, args, path)
+        .await
+        .map_err(|e| match e {
+            S3FcpError::IoError(e) if is_disk_full(&e) => S3FcpError::DiskFull(format!(
+                "while writing {}; free some space and retry, or pass --wait-on-diskfull",
+                path.display()
+            )),
+            e => e,
+        })
+}
+
+async fn download_to_file_inner(
+    client: Arc<dyn DownloadClient>,
+    args: DownloadArgs,
+    path: &Path,
+) -> Result<()> {
+    // Only the directory sync below needs it, and that sync only exists on unix
+    #[cfg(unix)]
+    let fsync = args.fsync;
+    if let Some(split_size) = args.split_size {
+        let Some(temp) = args.atomic.then(|| temp_path(path)).flatten() else {
+            return write_segments(client, args, path, split_size)
+                .await
+                .map(|_| ());
+        };
+        let result = match write_segments(client, args, &temp, split_size).await {
+            Ok(count) => rename_segments(&temp, path, count)
+                .await
+                .map_err(S3FcpError::from),
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            remove_segments(&temp).await;
+        }
+        result?;
+    } else {
+        let Some(temp) = atomic_temp_path(&args, path).await else {
+            return write_file(client, args, path).await;
+        };
+        let result = match write_file(client, args, &temp).await {
+            Ok(()) => tokio::fs::rename(&temp, path)
+                .await
+                .map_err(S3FcpError::from),
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&temp).await;
+        }
+        result?;
+    }
+
+    // Persist the rename itself, not just the file's contents
+    #[cfg(unix)]
+    if fsync {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            tokio::fs::File::open(dir).await?.sync_all().await?;
+        }
+    }
+    Ok(())
+}
+
+/// Temporary file next to `path` that an atomic download writes and then renames to `path`
+/// Only regular files are replaced this way: devices, pipes and symlinks are written in place
+async fn atomic_temp_path(args: &DownloadArgs, path: &Path) -> Option<PathBuf> {
+    if !args.atomic {
+        return None;
+    }
+    match tokio::fs::symlink_metadata(path).await {
+        Ok(metadata) if metadata.is_file() => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        _ => return None,
+    }
+    temp_path(path)
+}
+
+/// Hidden name next to `path` for output that is renamed to `path` once complete
+fn temp_path(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy();
+    Some(path.with_file_name(format!(".{}.s3fcp-{}.tmp", name, std::process::id())))
+}
+
+/// Write the output as segments of `base`, returning how many were created
+async fn write_segments(
+    client: Arc<dyn DownloadClient>,
+    args: DownloadArgs,
+    base: &Path,
+    split_size: u64,
+) -> Result<usize> {
+    let fsync = args.fsync;
+    let sink = SegmentingSink::create(base, split_size).await?;
+    let mut sink = download_buffered(client, args, sink).await?;
+    sink.flush().await?;
+    if fsync {
+        for segment in sink.segments() {
+            tokio::fs::File::open(segment).await?.sync_all().await?;
+        }
+    }
+    Ok(sink.segments().len())
+}
+
+async fn write_file(
+    client: Arc<dyn DownloadClient>,
+    args: DownloadArgs,
+    path: &Path,
+) -> Result<()> {
+    let fsync = args.fsync;
+    let file = tokio::fs::File::create(path).await?;
+    let mut file = if args.wait_on_diskfull {
+        let file = WaitOnDiskFull::new(file, DISK_FULL_INITIAL_DELAY);
+        download_buffered(client, args, file).await?.into_inner()
+    } else {
+        download_buffered(client, args, file).await?
+    };
+    file.flush().await?;
+    if fsync {
+        file.sync_all().await?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::TextNormalize;
+    use crate::testing::{Fault, MockClient};
+    use async_trait::async_trait;
+
+    /// In-memory multipart object with uniform parts, counting part and range requests
+    struct PartClient {
+        content: Bytes,
+        etag: &'static str,
+        part_size: usize,
+        parts: Mutex<Vec<u32>>,
+        ranges: AtomicUsize,
+    }
+
+    impl PartClient {
+        fn new(content: Vec<u8>, etag: &'static str, part_size: usize) -> Self {
+            Self {
+                content: Bytes::from(content),
+                etag,
+                part_size,
+                parts: Mutex::new(Vec::new()),
+                ranges: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl DownloadClient for PartClient {
+        async fn head(&self) -> Result<ObjectMetadata> {
+            Ok(ObjectMetadata {
+                content_length: self.content.len() as u64,
+                supports_range: true,
+                etag: Some(self.etag.to_string()),
+                content_type: None,
+                content_md5: None,
+                last_modified: None,
+            })
+        }
+
+        async fn get_range(&self, start: u64, end: u64) -> Result<Bytes> {
+            self.ranges.fetch_add(1, Ordering::SeqCst);
+            Ok(self.content.slice(start as usize..=end as usize))
+        }
+
+        async fn get_full(&self) -> Result<Bytes> {
+            Ok(self.content.clone())
+        }
+
+        async fn part_size(&self, _part_number: u32) -> Result<Option<u64>> {
+            Ok(Some(self.part_size as u64))
+        }
+
+        async fn get_part(&self, part_number: u32) -> Result<Bytes> {
+            self.parts.lock().unwrap().push(part_number);
+            let start = (part_number as usize - 1) * self.part_size;
+            let end = (start + self.part_size).min(self.content.len());
+            Ok(self.content.slice(start..end))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_by_part_requests_original_parts() {
+        let content: Vec<u8> = (0..250).map(|i| i as u8).collect();
+        let client = Arc::new(PartClient::new(content.clone(), "\"abc-3\"", 100));
+        let args = DownloadArgs::builder()
+            .concurrency(1)
+            .chunk_size(30)
+            .quiet(true)
+            .by_part(true)
+            .build();
+
+        let output = download(client.clone(), args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, content);
+        assert_eq!(*client.parts.lock().unwrap(), vec![1, 2, 3]);
+        assert_eq!(client.ranges.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_by_part_falls_back_to_ranges_for_single_part_objects() {
+        let content: Vec<u8> = (0..250).map(|i| i as u8).collect();
+        let client = Arc::new(PartClient::new(content.clone(), "\"abc\"", 100));
+        let args = DownloadArgs::builder()
+            .chunk_size(50)
+            .quiet(true)
+            .by_part(true)
+            .build();
+
+        let output = download(client.clone(), args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, content);
+        assert!(client.parts.lock().unwrap().is_empty());
+        assert_eq!(client.ranges.load(Ordering::SeqCst), 5);
+    }
+
+    /// Client whose range requests panic or fail, depending on `panic`
+    struct BrokenClient {
+        panic: bool,
+    }
+
+    #[async_trait]
+    impl DownloadClient for BrokenClient {
+        async fn head(&self) -> Result<ObjectMetadata> {
+            Ok(ObjectMetadata {
+                content_length: 1000,
+                supports_range: true,
+                etag: None,
+                content_type: None,
+                content_md5: None,
+                last_modified: None,
+            })
+        }
+
+        async fn get_range(&self, _start: u64, _end: u64) -> Result<Bytes> {
+            if self.panic {
+                panic!("injected panic");
+            }
+            Err(S3FcpError::DownloadFailed("injected failure".to_string()))
+        }
+
+        async fn get_full(&self) -> Result<Bytes> {
+            unreachable!("range requests are supported")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_worker_panic_fails_download() {
+        let client = Arc::new(BrokenClient { panic: true });
+        let args = DownloadArgs::builder()
+            .concurrency(2)
+            .chunk_size(100)
+            .quiet(true)
+            .build();
+
+        let result =
+            tokio::time::timeout(Duration::from_secs(10), download(client, args, Vec::new()))
+                .await
+                .expect("download must not hang after a worker panics");
+
+        assert!(matches!(result, Err(S3FcpError::JoinError(e)) if e.is_panic()));
+    }
+
+    #[tokio::test]
+    async fn test_failed_workers_do_not_block_queue() {
+        // With every worker gone, the queue must not wait on a full channel forever
+        let client = Arc::new(BrokenClient { panic: false });
+        let args = DownloadArgs::builder()
+            .concurrency(1)
+            .chunk_size(100)
+            .quiet(true)
+            .build();
+
+        let result =
+            tokio::time::timeout(Duration::from_secs(10), download(client, args, Vec::new()))
+                .await
+                .expect("download must not hang after workers fail");
+
+        assert!(matches!(result, Err(S3FcpError::DownloadFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_short_read_is_completed() {
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let client = Arc::new(MockClient::new(content.clone()).with_fault(0, Fault::ShortRead));
+        let args = DownloadArgs::builder()
+            .concurrency(1)
+            .chunk_size(1000)
+            .quiet(true)
+            .build();
+
+        let output = download(client.clone(), args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, content);
+        assert_eq!(client.range_requests(), vec![(0, 999), (500, 999)]);
+    }
+
+    #[tokio::test]
+    async fn test_zero_concurrency_still_downloads() {
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let client = Arc::new(MockClient::new(content.clone()));
+        let args = DownloadArgs::builder()
+            .concurrency(0)
+            .chunk_size(100)
+            .quiet(true)
+            .build();
+
+        let output = download(client, args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, content);
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_concurrency_downloads() {
+        let content: Vec<u8> = (0..10_000).map(|i| (i % 256) as u8).collect();
+        let client = (0..3).fold(MockClient::new(content.clone()), |client, i| {
+            client.with_fault(i * 100, Fault::ShortRead)
+        });
+        let client = Arc::new(client);
+        let args = DownloadArgs::builder()
+            .concurrency(8)
+            .adaptive_concurrency(true)
+            .chunk_size(100)
+            .quiet(true)
+            .build();
+
+        let output = download(client, args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, content);
+    }
+
+    #[tokio::test]
+    async fn test_auto_chunk_splits_small_objects() {
+        let content: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let client = Arc::new(MockClient::new(content.clone()));
+        let args = DownloadArgs::builder()
+            .concurrency(10)
+            .chunk_size(8 * 1024 * 1024)
+            .auto_chunk(true)
+            .quiet(true)
+            .build();
+
+        let output = download(client.clone(), args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, content);
+        assert_eq!(client.range_requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_chunk_percent_resolves_against_object_size() {
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let client = Arc::new(MockClient::new(content.clone()));
+        let args = DownloadArgs::builder()
+            .chunk_percent(12.5)
+            .quiet(true)
+            .build();
+
+        let output = download(client.clone(), args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, content);
+        let mut requests = client.range_requests();
+        requests.sort();
+        assert_eq!(requests.len(), 8);
+        assert_eq!(requests[0], (0, 124));
+    }
+
+    #[tokio::test]
+    async fn test_ranges_are_written_in_given_order() {
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let client = Arc::new(MockClient::new(content.clone()));
+        let args = DownloadArgs::builder()
+            .chunk_size(64)
+            .quiet(true)
+            .ranges(vec![
+                ByteRange {
+                    start: 800,
+                    end: 999,
+                },
+                ByteRange { start: 10, end: 19 },
+            ])
+            .build();
+
+        let output = download(client, args, Vec::new()).await.unwrap();
+
+        let mut expected = content[800..1000].to_vec();
+        expected.extend_from_slice(&content[10..20]);
+        assert_eq!(output, expected);
+    }
+
+    #[tokio::test]
+    async fn test_start_and_end_byte() {
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let partial = |start, end| {
+            let client = Arc::new(MockClient::new(content.clone()));
+            let args = DownloadArgs::builder()
+                .chunk_size(64)
+                .quiet(true)
+                .maybe_start_byte(start)
+                .maybe_end_byte(end)
+                .build();
+            download(client, args, Vec::new())
+        };
+
+        assert_eq!(partial(Some(900), None).await.unwrap(), &content[900..]);
+        assert_eq!(partial(None, Some(99)).await.unwrap(), &content[..100]);
+        assert_eq!(
+            partial(Some(100), Some(199)).await.unwrap(),
+            &content[100..200]
+        );
+        assert!(matches!(
+            partial(Some(1000), None).await,
+            Err(S3FcpError::InvalidRange(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stats_record_digests_computed_while_writing() {
+        use crate::verify::DownloadStats;
+
+        let client = Arc::new(MockClient::new(b"hello world".to_vec()));
+        let stats = Arc::new(DownloadStats::new([DigestAlgorithm::Md5]));
+        let args = DownloadArgs::builder()
+            .chunk_size(4)
+            .quiet(true)
+            .sha256("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9".to_string())
+            .stats(stats.clone())
+            .build();
+
+        let output = download(client, args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, b"hello world");
+        assert_eq!(
+            stats.digest(DigestAlgorithm::Md5).as_deref(),
+            Some("5eb63bbbe01eeed093cb22bb8f5acdc3")
+        );
+        assert_eq!(
+            stats.digest(DigestAlgorithm::Sha256).as_deref(),
+            Some("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_text_normalize_rewrites_output_not_digest() {
+        let content = b"a = 1\r\nb = 2\r\n".to_vec();
+        let client = Arc::new(MockClient::new(content));
+        let args = DownloadArgs::builder()
+            .quiet(true)
+            .single_stream(true)
+            .text_normalize(TextNormalize::Lf)
+            // sha256 of the original CRLF text
+            .sha256("21ee36e3e61ff2e1ef52acf4449e292da9f7ada92309e91c3ad64936ed31604e".to_string())
+            .build();
+
+        let output = download(client, args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, b"a = 1\nb = 2\n");
+    }
+
+    #[tokio::test]
+    async fn test_file_output_is_replaced_only_when_complete() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("out.bin");
+        std::fs::write(&path, b"previous").unwrap();
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let args = || DownloadArgs::builder().chunk_size(100).quiet(true).build();
+
+        let failing = MockClient::new(content.clone()).with_fault(500, Fault::NotFound);
+        let result = download_to_file(Arc::new(failing), args(), &path).await;
+        assert!(matches!(result, Err(S3FcpError::NotFound(_))));
+        assert_eq!(std::fs::read(&path).unwrap(), b"previous");
+
+        let client = MockClient::new(content.clone());
+        download_to_file(Arc::new(client), args(), &path)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_split_output_is_renamed_only_when_complete() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("out.bin");
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let args = || {
+            DownloadArgs::builder()
+                .chunk_size(100)
+                .quiet(true)
+                .split_size(400)
+                .build()
+        };
+
+        let failing = MockClient::new(content.clone()).with_fault(500, Fault::NotFound);
+        let result = download_to_file(Arc::new(failing), args(), &path).await;
+        assert!(matches!(result, Err(S3FcpError::NotFound(_))));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let client = MockClient::new(content.clone());
+        download_to_file(Arc::new(client), args(), &path)
+            .await
+            .unwrap();
+        let mut names: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["out.bin.000", "out.bin.001", "out.bin.002"]);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_output_round_trips() {
+        use async_compression::tokio::write::{GzipDecoder, ZstdDecoder};
+
+        let content: Vec<u8> = (0..1000).map(|i| (i % 7) as u8).collect();
+        let args = |compress| {
+            DownloadArgs::builder()
+                .chunk_size(100)
+                .quiet(true)
+                .compress(compress)
+                .build()
+        };
+
+        let client = Arc::new(MockClient::new(content.clone()));
+        let gzip = download(client, args(Compression::Gzip), Vec::new())
+            .await
+            .unwrap();
+        let mut decoder = GzipDecoder::new(Vec::new());
+        decoder.write_all(&gzip).await.unwrap();
+        decoder.shutdown().await.unwrap();
+        assert_eq!(decoder.into_inner(), content);
+
+        let client = Arc::new(MockClient::new(content.clone()));
+        let zstd = download(client, args(Compression::Zstd), Vec::new())
+            .await
+            .unwrap();
+        let mut decoder = ZstdDecoder::new(Vec::new());
+        decoder.write_all(&zstd).await.unwrap();
+        decoder.shutdown().await.unwrap();
+        assert_eq!(decoder.into_inner(), content);
+    }
+
+    #[tokio::test]
+    async fn test_single_stream_skips_range_requests() {
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let client = Arc::new(MockClient::new(content.clone()));
+        let args = DownloadArgs::builder()
+            .chunk_size(100)
+            .single_stream(true)
+            .quiet(true)
+            .build();
+
+        let output = download(client.clone(), args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, content);
+        assert!(client.range_requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retry_deadline_outlasts_fixed_retries() {
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let client = |failures| {
+            let client = (0..failures).fold(MockClient::new(content.clone()), |client, _| {
+                client.with_fault(0, Fault::Error)
+            });
+            Arc::new(client)
+        };
+        let args = |retry_deadline| {
+            DownloadArgs::builder()
+                .chunk_size(1000)
+                .quiet(true)
+                .maybe_retry_deadline(retry_deadline)
+                .build()
+        };
+
+        // Four failures exhaust the default three retries
+        let result = download(client(4), args(None), Vec::new()).await;
+        assert!(result.is_err());
+
+        let deadline = Some(Duration::from_secs(30));
+        let output = download(client(4), args(deadline), Vec::new())
+            .await
+            .unwrap();
+        assert_eq!(output, content);
+    }
+
+    #[tokio::test]
+    async fn test_retry_deadline_gives_up_when_exceeded() {
+        let client = Arc::new(BrokenClient { panic: false });
+        let args = DownloadArgs::builder()
+            .chunk_size(1000)
+            .quiet(true)
+            .retry_deadline(Duration::from_millis(500))
+            .build();
+
+        let started = Instant::now();
+        let result = download(client, args, Vec::new()).await;
+
+        assert!(matches!(result, Err(S3FcpError::DownloadFailed(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    /// Client whose HEAD requests fail with `error` until `failures` of them did
+    struct FlakyHeadClient {
+        failures: AtomicUsize,
+        error: fn() -> S3FcpError,
+    }
+
+    #[async_trait]
+    impl DownloadClient for FlakyHeadClient {
+        async fn head(&self) -> Result<ObjectMetadata> {
+            let fail = self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+            if fail.is_ok() {
+                return Err((self.error)());
+            }
+            MockClient::new(vec![0u8; 10]).head().await
+        }
+
+        async fn get_range(&self, _start: u64, _end: u64) -> Result<Bytes> {
+            unreachable!("only probed")
+        }
+
+        async fn get_full(&self) -> Result<Bytes> {
+            unreachable!("only probed")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probe_retries_transient_failures() {
+        let client = FlakyHeadClient {
+            failures: AtomicUsize::new(1),
+            error: || S3FcpError::HttpError("connection reset".to_string()),
+        };
+        let metadata = probe(&client).await.unwrap();
+        assert_eq!(metadata.content_length, 10);
+        assert!(metadata.supports_range);
+
+        let client = FlakyHeadClient {
+            failures: AtomicUsize::new(1),
+            error: || S3FcpError::NotFound("no such key".to_string()),
+        };
+        assert!(matches!(probe(&client).await, Err(S3FcpError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_head_timeout_fails_slow_probe() {
+        let client = Arc::new(MockClient::new(vec![7u8; 100]).with_latency(Duration::from_secs(5)));
+        let args = DownloadArgs::builder()
+            .quiet(true)
+            .head_timeout(Duration::from_millis(50))
+            .build();
+
+        let started = Instant::now();
+        let result = download(client, args, Vec::new()).await;
+
+        assert!(matches!(result, Err(S3FcpError::DownloadFailed(_))));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_expected_size_mismatch_fails_before_download() {
+        let client = Arc::new(MockClient::new(vec![7u8; 100]));
+        let args = DownloadArgs::builder()
+            .quiet(true)
+            .expected_size(99)
+            .build();
+
+        let result = download(client.clone(), args, Vec::new()).await;
+
+        let error = result.unwrap_err();
+        assert!(matches!(error, S3FcpError::IntegrityError(_)));
+        assert!(error.to_string().contains("100 bytes, expected 99"));
+        assert!(client.range_requests().is_empty());
+
+        let args = DownloadArgs::builder()
+            .quiet(true)
+            .expected_size(100)
+            .build();
+        assert_eq!(download(client, args, Vec::new()).await.unwrap().len(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_check_terminal_refuses_binary_output_before_download() {
+        let client = Arc::new(MockClient::new(vec![7u8; 100]));
+        let args = DownloadArgs::builder()
+            .quiet(true)
+            .compress(Compression::Gzip)
+            .check_terminal(true)
+            .build();
+
+        let result = download(client.clone(), args, Vec::new()).await;
+
+        assert!(matches!(result, Err(S3FcpError::BinaryOutput(_))));
+        assert!(client.range_requests().is_empty());
+        assert_eq!(client.full_requests(), 0);
+
+        // Without a content type, plain output counts as text
+        let args = DownloadArgs::builder()
+            .quiet(true)
+            .check_terminal(true)
+            .build();
+        assert_eq!(download(client, args, Vec::new()).await.unwrap().len(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_failed_stream_reports_incomplete_output() {
+        let client = Arc::new(MockClient::new(vec![7u8; 1000]).with_fault(300, Fault::NotFound));
+        let args = DownloadArgs::builder()
+            .concurrency(1)
+            .chunk_size(100)
+            .quiet(true)
+            .write_buffer_size(0)
+            .build();
+
+        let (error, incomplete) = download_to_stream(client, args, Vec::new())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, S3FcpError::NotFound(_)));
+        let incomplete = incomplete.unwrap();
+        assert_eq!(
+            incomplete,
+            IncompleteOutput {
+                written: 300,
+                expected: Some(1000)
+            }
+        );
+        assert_eq!(
+            incomplete.to_string(),
+            "output is incomplete, 300 of 1000 bytes written"
+        );
+
+        // Nothing written, nothing to report
+        let client = Arc::new(MockClient::new(vec![7u8; 1000]).with_fault(0, Fault::NotFound));
+        let args = DownloadArgs::builder().quiet(true).build();
+        let (_, incomplete) = download_to_stream(client, args, Vec::new())
+            .await
+            .unwrap_err();
+        assert_eq!(incomplete, None);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_errors_are_not_retried() {
+        let client = Arc::new(MockClient::new(vec![7u8; 100]).with_fault(0, Fault::NotFound));
+        let args = DownloadArgs::builder()
+            .concurrency(1)
+            .chunk_size(100)
+            .quiet(true)
+            .build();
+
+        let result = download(client.clone(), args, Vec::new()).await;
+
+        assert!(matches!(result, Err(S3FcpError::NotFound(_))));
+        assert_eq!(client.range_requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reset_mid_chunk_resumes_from_received_bytes() {
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let client = Arc::new(MockClient::new(content.clone()).with_fault(0, Fault::Reset));
+        let args = DownloadArgs::builder()
+            .concurrency(1)
+            .chunk_size(1000)
+            .quiet(true)
+            .build();
+
+        let output = download(client.clone(), args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, content);
+        assert_eq!(client.range_requests(), vec![(0, 999), (499, 999)]);
+    }
+
+    /// Writer that counts flushes
+    #[derive(Default)]
+    struct FlushCounter {
+        data: Vec<u8>,
+        flushes: usize,
+    }
+
+    impl tokio::io::AsyncWrite for FlushCounter {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.data.extend_from_slice(buf);
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            self.flushes += 1;
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Channel holding `count` in-order chunks of 10 bytes each
+    fn ten_byte_chunks(count: usize) -> flume::Receiver<DownloadedChunk> {
+        let (tx, rx) = flume::unbounded();
+        for index in 0..count {
+            let data = Bytes::from(vec![index as u8; 10]);
+            tx.send(DownloadedChunk { index, data }).unwrap();
+        }
+        rx
+    }
+
+    #[tokio::test]
+    async fn test_output_writer_flushes_periodically() {
+        let writer = ordered_output_writer(
+            ten_byte_chunks(3),
+            3,
+            FlushCounter::default(),
+            Duration::ZERO,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(writer.data.len(), 30);
+        assert_eq!(writer.flushes, 1);
+
+        let interval = Duration::from_nanos(1);
+        let writer = ordered_output_writer(
+            ten_byte_chunks(3),
+            3,
+            FlushCounter::default(),
+            interval,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        // One flush per chunk plus the final flush
+        assert_eq!(writer.flushes, 4);
+    }
+
+    #[tokio::test]
+    async fn test_strict_ordering_rejects_broken_streams() {
+        let in_order = ordered_output_writer(
+            ten_byte_chunks(3),
+            3,
+            Vec::new(),
+            Duration::ZERO,
+            None,
+            true,
+        );
+        assert_eq!(in_order.await.unwrap().len(), 30);
+
+        // Chunk 1 never arrives
+        let (tx, rx) = flume::unbounded();
+        for index in [0, 2] {
+            tx.send(DownloadedChunk {
+                index,
+                data: Bytes::from_static(b"x"),
+            })
+            .unwrap();
+        }
+        drop(tx);
+        let gap = ordered_output_writer(rx, 3, Vec::new(), Duration::ZERO, None, true).await;
+        assert!(gap
+            .unwrap_err()
+            .to_string()
+            .contains("stopped at chunk 1 of 3"));
+
+        for indexes in [[0, 0], [1, 1], [0, 5]] {
+            let (tx, rx) = flume::unbounded();
+            for index in indexes {
+                tx.send(DownloadedChunk {
+                    index,
+                    data: Bytes::from_static(b"x"),
+                })
+                .unwrap();
+            }
+            drop(tx);
+            let strict = ordered_output_writer(rx, 3, Vec::new(), Duration::ZERO, None, true);
+            assert!(strict
+                .await
+                .unwrap_err()
+                .to_string()
+                .contains("ordering invariant violated"));
+        }
+
+        // The default mode tolerates them
+        let (tx, rx) = flume::unbounded();
+        tx.send(DownloadedChunk {
+            index: 0,
+            data: Bytes::from_static(b"x"),
+        })
+        .unwrap();
+        drop(tx);
+        let tolerant = ordered_output_writer(rx, 3, Vec::new(), Duration::ZERO, None, false);
+        assert_eq!(tolerant.await.unwrap(), b"x");
+    }
+
+    #[tokio::test]
+    async fn test_download_with_strict_ordering() {
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let client = Arc::new(MockClient::new(content.clone()).with_fault(300, Fault::Error));
+        let args = DownloadArgs::builder()
+            .concurrency(4)
+            .chunk_size(100)
+            .quiet(true)
+            .strict_ordering(true)
+            .build();
+
+        let output = download(client, args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, content);
+    }
+
+    #[derive(Default)]
+    struct RecordingListener {
+        events: Mutex<Vec<ChunkEvent>>,
+    }
+
+    impl DownloadListener for RecordingListener {
+        fn on_event(&self, event: &ChunkEvent) {
+            self.events.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_listener_receives_chunk_events() {
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let client = Arc::new(MockClient::new(content).with_fault(0, Fault::ShortRead));
+        let listener = Arc::new(RecordingListener::default());
+        let args = DownloadArgs::builder()
+            .concurrency(1)
+            .chunk_size(250)
+            .quiet(true)
+            .listener(listener.clone())
+            .build();
+
+        download(client, args, Vec::new()).await.unwrap();
+
+        let events = listener.events.lock().unwrap();
+        let count = |f: fn(&ChunkEvent) -> bool| events.iter().filter(|e| f(e)).count();
+        assert_eq!(count(|e| matches!(e, ChunkEvent::Queued { .. })), 4);
+        // The short read on the first chunk needs a second request for the tail
+        assert_eq!(count(|e| matches!(e, ChunkEvent::Started { .. })), 5);
+        assert_eq!(
+            count(|e| matches!(e, ChunkEvent::Completed { bytes: 250, .. })),
+            4
+        );
+        assert!(events.contains(&ChunkEvent::Started {
+            index: 0,
+            attempt: 2
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_reorder_window_holds_back_chunks_ahead_of_output() {
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let client = Arc::new(
+            MockClient::new(content.clone()).with_fault(0, Fault::Delay(Duration::from_millis(50))),
+        );
+        let listener = Arc::new(RecordingListener::default());
+        let args = DownloadArgs::builder()
+            .concurrency(4)
+            .chunk_size(100)
+            .reorder_window(2)
+            .quiet(true)
+            .listener(listener.clone())
+            .build();
+
+        let output = download(client, args, Vec::new()).await.unwrap();
+        assert_eq!(output, content);
+
+        // While the first chunk is delayed, only the one after it may be fetched
+        let events = listener.events.lock().unwrap();
+        let first_done = events
+            .iter()
+            .position(|e| matches!(e, ChunkEvent::Completed { index: 0, .. }))
+            .unwrap();
+        assert!(events[..first_done]
+            .iter()
+            .all(|e| !matches!(e, ChunkEvent::Started { index, .. } if *index >= 2)));
+    }
+
+    #[tokio::test]
+    async fn test_reorder_window_of_usize_max() {
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let client = Arc::new(MockClient::new(content.clone()));
+        let args = DownloadArgs::builder()
+            .concurrency(4)
+            .chunk_size(100)
+            .reorder_window(usize::MAX)
+            .quiet(true)
+            .build();
+
+        let output =
+            tokio::time::timeout(Duration::from_secs(10), download(client, args, Vec::new()))
+                .await
+                .expect("download should not hang")
+                .unwrap();
+        assert_eq!(output, content);
+    }
+
+    #[tokio::test]
+    async fn test_short_reads_across_chunks() {
+        let content: Vec<u8> = (0..1050).map(|i| (i % 251) as u8).collect();
+        let client = (0..5).fold(MockClient::new(content.clone()), |client, i| {
+            client.with_fault(i * 100, Fault::ShortRead)
+        });
+        let client = Arc::new(client);
+        let args = DownloadArgs::builder()
+            .concurrency(4)
+            .chunk_size(100)
+            .quiet(true)
+            .build();
+
+        let output = download(client, args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, content);
+    }
+}
@@ -1,20 +1,65 @@
+use crate::cli::ErrorFormat;
+
 #[derive(Debug, thiserror::Error)]
 pub enum S3FcpError {
     #[error("Invalid URI: {0}")]
     InvalidUri(String),
 
+    #[error("Invalid range: {0}")]
+    InvalidRange(String),
+
+    #[error("Invalid config file: {0}")]
+    InvalidConfig(String),
+
+    #[error("Invalid header {0}")]
+    InvalidHeader(String),
+
+    #[error("Range requests not supported: {0}")]
+    RangeNotSupported(String),
+
     #[error("S3 operation failed: {0}")]
     S3Error(String),
 
     #[error("HTTP error: {0}")]
     HttpError(String),
 
+    #[error("Request rejected: {0}")]
+    RequestRejected(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+
+    #[error("Request rejected because the system clock is off: {0}. Sync the clock (e.g. enable NTP) and try again")]
+    ClockSkew(String),
+
+    #[error("No AWS credentials found. Run `aws configure`, set the AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY environment variables, or pass --no-sign-request for public buckets")]
+    NoCredentials,
+
     #[error("Request error: {0}")]
     ReqwestError(#[from] reqwest::Error),
 
     #[error("Download failed: {0}")]
     DownloadFailed(String),
 
+    /// Failure of one entry of a batch, recursive or pattern download
+    #[error("{name}: {error}")]
+    Entry {
+        name: String,
+        error: Box<S3FcpError>,
+    },
+
+    #[error("Integrity check failed: {0}")]
+    IntegrityError(String),
+
+    #[error("No space left on device: {0}")]
+    DiskFull(String),
+
+    #[error("Refusing to write binary output to a terminal: {0}")]
+    BinaryOutput(String),
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -22,4 +67,128 @@ pub enum S3FcpError {
     JoinError(#[from] tokio::task::JoinError),
 }
 
+impl S3FcpError {
+    /// Attribute this error to `name`, an entry of a download of many objects
+    pub fn in_entry(self, name: &str) -> Self {
+        S3FcpError::Entry {
+            name: name.to_string(),
+            error: Box::new(self),
+        }
+    }
+
+    /// Stable, machine-readable name of the error kind
+    /// An entry's failure has the kind of the error behind it
+    pub fn kind(&self) -> &'static str {
+        match self {
+            S3FcpError::Entry { error, .. } => error.kind(),
+            S3FcpError::InvalidUri(_) => "InvalidUri",
+            S3FcpError::InvalidRange(_) => "InvalidRange",
+            S3FcpError::InvalidConfig(_) => "InvalidConfig",
+            S3FcpError::InvalidHeader(_) => "InvalidHeader",
+            S3FcpError::RangeNotSupported(_) => "RangeNotSupported",
+            S3FcpError::S3Error(_) => "S3Error",
+            S3FcpError::HttpError(_) => "HttpError",
+            S3FcpError::RequestRejected(_) => "RequestRejected",
+            S3FcpError::NotFound(_) => "NotFound",
+            S3FcpError::AccessDenied(_) => "AccessDenied",
+            S3FcpError::NoCredentials => "NoCredentials",
+            S3FcpError::ClockSkew(_) => "ClockSkew",
+            S3FcpError::ReqwestError(_) => "RequestError",
+            S3FcpError::DownloadFailed(_) => "DownloadFailed",
+            S3FcpError::IntegrityError(_) => "IntegrityError",
+            S3FcpError::DiskFull(_) => "DiskFull",
+            S3FcpError::BinaryOutput(_) => "BinaryOutput",
+            S3FcpError::IoError(_) => "IoError",
+            S3FcpError::JoinError(_) => "JoinError",
+        }
+    }
+
+    /// Process exit code used when this error terminates the CLI
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            S3FcpError::Entry { error, .. } => error.exit_code(),
+            S3FcpError::InvalidUri(_)
+            | S3FcpError::InvalidRange(_)
+            | S3FcpError::InvalidConfig(_)
+            | S3FcpError::InvalidHeader(_)
+            | S3FcpError::BinaryOutput(_) => 2,
+            S3FcpError::NotFound(_) => 3,
+            S3FcpError::AccessDenied(_) | S3FcpError::NoCredentials => 4,
+            _ => 1,
+        }
+    }
+
+    /// Whether repeating the failed request may succeed
+    /// Errors that come from the request itself, missing access or the local setup
+    /// would fail the same way again
+    pub fn is_retryable(&self) -> bool {
+        if let S3FcpError::Entry { error, .. } = self {
+            return error.is_retryable();
+        }
+        !matches!(
+            self,
+            S3FcpError::InvalidUri(_)
+                | S3FcpError::InvalidRange(_)
+                | S3FcpError::InvalidConfig(_)
+                | S3FcpError::InvalidHeader(_)
+                | S3FcpError::RequestRejected(_)
+                | S3FcpError::NotFound(_)
+                | S3FcpError::AccessDenied(_)
+                | S3FcpError::NoCredentials
+                | S3FcpError::ClockSkew(_)
+                | S3FcpError::BinaryOutput(_)
+        )
+    }
+
+    /// Structured representation used by `--error-format json`
+    /// An entry's failure also names the entry, as `uri`
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut json = serde_json::json!({
+            "error_kind": self.kind(),
+            "message": self.to_string(),
+            "exit_code": self.exit_code(),
+        });
+        if let S3FcpError::Entry { name, .. } = self {
+            json["uri"] = name.clone().into();
+        }
+        json
+    }
+
+    /// Print this error to stderr in `format`
+    pub fn report(&self, format: ErrorFormat) {
+        match format {
+            ErrorFormat::Text => eprintln!("Error: {}", self),
+            ErrorFormat::Json => eprintln!("{}", self.to_json()),
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, S3FcpError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_to_json() {
+        let error = S3FcpError::InvalidUri("URI must start with s3://".to_string());
+        let json = error.to_json();
+
+        assert_eq!(json["error_kind"], "InvalidUri");
+        assert_eq!(json["message"], "Invalid URI: URI must start with s3://");
+        assert_eq!(json["exit_code"], 2);
+    }
+
+    #[test]
+    fn test_entry_error_keeps_kind_and_names_the_entry() {
+        let error = S3FcpError::NotFound("no such key".to_string()).in_entry("s3://b/k");
+        assert_eq!(error.to_string(), "s3://b/k: Not found: no such key");
+        assert_eq!(error.exit_code(), 3);
+        assert!(!error.is_retryable());
+
+        let json = error.to_json();
+        assert_eq!(json["error_kind"], "NotFound");
+        assert_eq!(json["uri"], "s3://b/k");
+        assert_eq!(json["exit_code"], 3);
+    }
+}
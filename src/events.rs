@@ -0,0 +1,41 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Lifecycle events of a single chunk, reported to a `DownloadListener`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkEvent {
+    /// The chunk was handed to the worker pool
+    Queued { index: usize, start: u64, end: u64 },
+    /// A request for the chunk (or the missing tail of a short read) was started
+    Started { index: usize, attempt: usize },
+    /// A request failed and will be retried after `delay`
+    Retried {
+        index: usize,
+        attempt: usize,
+        delay: Duration,
+        error: String,
+    },
+    /// The chunk was downloaded completely
+    Completed { index: usize, bytes: u64 },
+    /// The chunk failed after all retries
+    Failed { index: usize, error: String },
+}
+
+/// Observer of chunk lifecycle events, e.g. for custom metrics or UIs
+/// Called from the download workers, so implementations should return quickly
+pub trait DownloadListener: Send + Sync {
+    fn on_event(&self, event: &ChunkEvent);
+}
+
+impl fmt::Debug for dyn DownloadListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DownloadListener")
+    }
+}
+
+/// Report an event, only constructing it when a listener is present
+pub(crate) fn emit(listener: Option<&dyn DownloadListener>, event: impl FnOnce() -> ChunkEvent) {
+    if let Some(listener) = listener {
+        listener.on_event(&event());
+    }
+}
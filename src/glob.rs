@@ -0,0 +1,131 @@
+/// Characters that make an object key a pattern instead of a literal key
+const METACHARACTERS: [char; 3] = ['*', '?', '['];
+
+/// Whether `key` contains shell glob metacharacters
+pub fn has_glob(key: &str) -> bool {
+    key.contains(METACHARACTERS)
+}
+
+/// The part of `pattern` before its first path segment containing a metacharacter
+/// Every matching key starts with it, so it is the prefix to list, and the matches
+/// are placed below the output directory relative to it
+pub fn literal_dir(pattern: &str) -> &str {
+    let literal = pattern
+        .find(METACHARACTERS)
+        .map_or(pattern, |i| &pattern[..i]);
+    literal.rfind('/').map_or("", |i| &pattern[..=i])
+}
+
+/// Match `key` against a shell-style `pattern`
+///
+/// `*` matches any run of characters and `?` any single character, neither crossing a
+/// `/`. `[abc]`, `[a-z]` and `[!abc]` match one character from, or not from, the set.
+/// An unterminated `[` matches itself.
+pub fn matches(pattern: &str, key: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let key: Vec<char> = key.chars().collect();
+    matches_from(&pattern, &key)
+}
+
+fn matches_from(pattern: &[char], key: &[char]) -> bool {
+    match pattern.first() {
+        None => key.is_empty(),
+        Some('*') => {
+            // Try every split of the key's current segment, shortest first
+            let segment = key.iter().position(|&c| c == '/').unwrap_or(key.len());
+            (0..=segment).any(|skip| matches_from(&pattern[1..], &key[skip..]))
+        }
+        Some('?') => {
+            matches!(key.first(), Some(&c) if c != '/') && matches_from(&pattern[1..], &key[1..])
+        }
+        Some('[') => match (parse_class(&pattern[1..]), key.first()) {
+            (Some((class, rest)), Some(&c)) => {
+                c != '/' && class.contains(c) && matches_from(rest, &key[1..])
+            }
+            (Some(_), None) => false,
+            (None, _) => key.first() == Some(&'[') && matches_from(&pattern[1..], &key[1..]),
+        },
+        Some(&p) => key.first() == Some(&p) && matches_from(&pattern[1..], &key[1..]),
+    }
+}
+
+/// Set of characters in a `[...]` expression
+struct CharClass {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl CharClass {
+    fn contains(&self, c: char) -> bool {
+        let listed = self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+        listed != self.negated
+    }
+}
+
+/// Parse a class following its `[`, returning it and the pattern after its `]`
+/// A `]` right after the `[` or `[!` is part of the set, as in shells
+fn parse_class(pattern: &[char]) -> Option<(CharClass, &[char])> {
+    let negated = pattern.first() == Some(&'!');
+    let body = if negated { &pattern[1..] } else { pattern };
+
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < body.len() {
+        let c = body[i];
+        if c == ']' && i > 0 {
+            return Some((CharClass { negated, ranges }, &body[i + 1..]));
+        }
+        if body.get(i + 1) == Some(&'-') && body.get(i + 2).is_some_and(|&hi| hi != ']') {
+            ranges.push((c, body[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_glob() {
+        assert!(has_glob("logs/*.log"));
+        assert!(has_glob("logs/app-?.log"));
+        assert!(has_glob("logs/[ab].log"));
+        assert!(!has_glob("logs/app.log"));
+    }
+
+    #[test]
+    fn test_literal_dir() {
+        assert_eq!(literal_dir("logs/2024/*.log"), "logs/2024/");
+        assert_eq!(literal_dir("logs/app-*.log"), "logs/");
+        assert_eq!(literal_dir("logs/*/app.log"), "logs/");
+        assert_eq!(literal_dir("*.log"), "");
+    }
+
+    #[test]
+    fn test_star_and_question_mark() {
+        assert!(matches("logs/*.log", "logs/app.log"));
+        assert!(matches("logs/*.log", "logs/.log"));
+        assert!(!matches("logs/*.log", "logs/2024/app.log"));
+        assert!(!matches("logs/*.log", "logs/app.log.gz"));
+        assert!(matches("logs/*/app.log", "logs/2024/app.log"));
+        assert!(matches("logs/app-?.log", "logs/app-1.log"));
+        assert!(!matches("logs/app-?.log", "logs/app-10.log"));
+        assert!(!matches("a?b", "a/b"));
+    }
+
+    #[test]
+    fn test_character_classes() {
+        assert!(matches("app-[0-9].log", "app-7.log"));
+        assert!(!matches("app-[0-9].log", "app-x.log"));
+        assert!(matches("app-[!0-9].log", "app-x.log"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("a[-b]", "a-"));
+        // Unterminated classes are literal
+        assert!(matches("a[b", "a[b"));
+    }
+}
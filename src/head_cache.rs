@@ -0,0 +1,152 @@
+use crate::s3_client::ObjectMetadata;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a cached HEAD response is reused by default
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+/// Number of objects remembered by default
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Identifies an S3 object: bucket, key and optional version ID
+pub type ObjectKey = (String, String, Option<String>);
+
+struct Entry {
+    metadata: ObjectMetadata,
+    fetched: Instant,
+    /// Value of `HeadCache::clock` at the last insert or hit
+    last_used: u64,
+}
+
+/// In-memory LRU of recent HEAD responses, shared by the `S3Client`s of a batch
+/// A single client keeps one to reuse a HEAD made before its download
+///
+/// Entries expire after the TTL; once full, the least recently used entry is evicted.
+pub struct HeadCache {
+    ttl: Duration,
+    capacity: usize,
+    clock: AtomicU64,
+    entries: Mutex<HashMap<ObjectKey, Entry>>,
+}
+
+impl HeadCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            clock: AtomicU64::new(0),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Metadata from a HEAD of `key` made within the TTL, if any
+    pub fn get(&self, key: &ObjectKey) -> Option<ObjectMetadata> {
+        let mut entries = self.entries.lock().unwrap();
+
+        match entries.get_mut(key) {
+            Some(entry) if entry.fetched.elapsed() < self.ttl => {
+                entry.last_used = self.tick();
+                Some(entry.metadata.clone())
+            }
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, key: ObjectKey, metadata: ObjectMetadata) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let lru = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(lru) = lru {
+                entries.remove(&lru);
+            }
+        }
+
+        entries.insert(
+            key,
+            Entry {
+                metadata,
+                fetched: Instant::now(),
+                last_used: self.tick(),
+            },
+        );
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+impl Default for HeadCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL, DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str) -> ObjectKey {
+        ("bucket".to_string(), name.to_string(), None)
+    }
+
+    fn metadata(content_length: u64) -> ObjectMetadata {
+        ObjectMetadata {
+            content_length,
+            supports_range: true,
+            etag: None,
+            content_type: None,
+            content_md5: None,
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn test_head_cache_expires_entries() {
+        let cache = HeadCache::new(Duration::ZERO, 10);
+        cache.insert(key("a"), metadata(1));
+        assert!(cache.get(&key("a")).is_none());
+
+        let cache = HeadCache::new(Duration::from_secs(60), 10);
+        cache.insert(key("a"), metadata(1));
+        assert_eq!(cache.get(&key("a")).unwrap().content_length, 1);
+    }
+
+    #[test]
+    fn test_head_cache_evicts_least_recently_used() {
+        let cache = HeadCache::new(Duration::from_secs(60), 2);
+        cache.insert(key("a"), metadata(1));
+        cache.insert(key("b"), metadata(2));
+        cache.get(&key("a"));
+        cache.insert(key("c"), metadata(3));
+
+        assert!(cache.get(&key("a")).is_some());
+        assert!(cache.get(&key("b")).is_none());
+        assert!(cache.get(&key("c")).is_some());
+    }
+
+    #[test]
+    fn test_head_cache_keys_include_version() {
+        let cache = HeadCache::default();
+        cache.insert(key("a"), metadata(1));
+        let versioned = (
+            "bucket".to_string(),
+            "a".to_string(),
+            Some("v1".to_string()),
+        );
+        assert!(cache.get(&versioned).is_none());
+    }
+}
@@ -1,87 +1,653 @@
 use async_trait::async_trait;
-use bytes::Bytes;
-use reqwest::header::{ACCEPT_RANGES, CONTENT_LENGTH, RANGE};
-use reqwest::Client;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use bytes::{Bytes, BytesMut};
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE,
+    ETAG, HOST, LAST_MODIFIED, RANGE,
+};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{Semaphore, SemaphorePermit};
 
 use crate::error::{Result, S3FcpError};
+use crate::ping::Endpoint;
+use crate::resolve::ResolveOverride;
 use crate::s3_client::{DownloadClient, ObjectMetadata};
 
+/// `Content-MD5` is not among reqwest's predefined header names
+const CONTENT_MD5: &str = "content-md5";
+
+/// How the object size and range support are determined
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeadMethod {
+    /// Use a HEAD request
+    Head,
+    /// Use a one-byte ranged GET request
+    Get,
+    /// Use HEAD, falling back to GET if the server rejects HEAD with 405 or 501
+    #[default]
+    Auto,
+}
+
+/// Request header given as `Name: value`; a value of `@PATH` is read from that file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderArg {
+    pub name: String,
+    pub value: String,
+}
+
+impl HeaderArg {
+    /// Parse `Name: value`, as given with `--header`
+    pub fn parse(s: &str) -> std::result::Result<Self, String> {
+        let (name, value) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid header '{}': expected Name: value", s))?;
+        let name = name.trim();
+        HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("Invalid header name '{}'", name))?;
+
+        Ok(Self {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+        })
+    }
+
+    /// The header to send, reading `@PATH` values from the file without trailing newlines
+    /// Values read from files are marked sensitive, so they are never logged
+    pub fn resolve(&self) -> Result<(HeaderName, HeaderValue)> {
+        let invalid = |e: String| S3FcpError::InvalidHeader(format!("{}: {}", self.name, e));
+
+        let name =
+            HeaderName::from_bytes(self.name.as_bytes()).map_err(|e| invalid(e.to_string()))?;
+        let value = match self.value.strip_prefix('@') {
+            Some(path) => {
+                let text = std::fs::read_to_string(Path::new(path))
+                    .map_err(|e| invalid(format!("cannot read {}: {}", path, e)))?;
+                let mut value = HeaderValue::from_str(text.trim_end_matches(['\r', '\n']))
+                    .map_err(|_| invalid(format!("{} is not a valid header value", path)))?;
+                value.set_sensitive(true);
+                value
+            }
+            None => HeaderValue::from_str(&self.value).map_err(|e| invalid(e.to_string()))?,
+        };
+        Ok((name, value))
+    }
+}
+
+/// Parse a `--header-from-file` file with one `Name: value` header per line
+/// Blank lines and lines starting with `#` are skipped
+pub fn parse_header_file(text: &str) -> std::result::Result<Vec<HeaderArg>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(number, line)| {
+            HeaderArg::parse(line).map_err(|e| format!("line {}: {}", number + 1, e))
+        })
+        .collect()
+}
+
+/// Resolve `headers` into the map sent with every request
+/// A later header replaces an earlier one with the same name
+pub fn header_map(headers: &[HeaderArg]) -> Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for header in headers {
+        let (name, value) = header.resolve()?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
 pub struct HttpClient {
     client: Client,
     url: String,
+    head_method: HeadMethod,
+    /// Sent with every request
+    headers: HeaderMap,
+    /// Sent as the `Host` header instead of the URL's host
+    host: Option<HeaderValue>,
+    /// The client is rebuilt from these settings whenever one of them changes
+    overrides: Vec<ResolveOverride>,
+    http2: bool,
+    max_connections: Option<usize>,
+    /// Held by every request in flight when the connections are limited
+    connections: Option<Semaphore>,
+    /// Used instead of the length the server reports
+    content_length: Option<u64>,
+    /// A request fails once no data has arrived for this long
+    stall_timeout: Option<Duration>,
+    /// Probe with a ranged GET when HEAD doesn't advertise range support
+    range_probe: bool,
 }
 
 impl HttpClient {
     pub fn new(url: String) -> Self {
         Self {
-            client: Client::new(),
+            client: Client::builder()
+                .http1_only()
+                .build()
+                .expect("default HTTP client settings are valid"),
             url,
+            head_method: HeadMethod::default(),
+            headers: HeaderMap::new(),
+            host: None,
+            overrides: Vec::new(),
+            http2: false,
+            max_connections: None,
+            connections: None,
+            content_length: None,
+            stall_timeout: None,
+            range_probe: false,
+        }
+    }
+
+    /// Send `headers` with every request, e.g. for authentication
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Send `host` as the `Host` header of every request, e.g. to reach a virtual host
+    /// through a load balancer; TLS SNI still uses the host from the URL
+    /// It takes precedence over a `Host` header given with `with_headers`
+    pub fn with_host(mut self, host: Option<&str>) -> Result<Self> {
+        self.host = host
+            .map(|host| {
+                HeaderValue::from_str(host).map_err(|_| {
+                    S3FcpError::InvalidHeader(format!("Host: '{}' is not valid", host))
+                })
+            })
+            .transpose()?;
+        Ok(self)
+    }
+
+    pub fn with_head_method(mut self, head_method: HeadMethod) -> Self {
+        self.head_method = head_method;
+        self
+    }
+
+    /// Connect to the given addresses instead of resolving the overridden hosts
+    /// Like curl, an override only applies to the port it names; TLS SNI and the
+    /// Host header still use the host from the URL
+    pub fn with_resolve(mut self, overrides: &[ResolveOverride]) -> Result<Self> {
+        self.overrides = overrides.to_vec();
+        self.client = self.build_client()?;
+        Ok(self)
+    }
+
+    /// Allow HTTP/2, so concurrent range requests share one multiplexed connection
+    /// HTTPS servers negotiate it and may stay on HTTP/1.1; plain `http://` URLs
+    /// assume the server speaks HTTP/2 (prior knowledge)
+    pub fn with_http2(mut self, http2: bool) -> Result<Self> {
+        self.http2 = http2;
+        self.client = self.build_client()?;
+        Ok(self)
+    }
+
+    /// Keep at most `max` requests, and so connections, open to the server at a time
+    /// Requests beyond the limit wait for a free connection; `None` leaves it unlimited
+    pub fn with_max_connections(mut self, max: Option<usize>) -> Result<Self> {
+        self.max_connections = max;
+        self.connections = max.map(Semaphore::new);
+        self.client = self.build_client()?;
+        Ok(self)
+    }
+
+    /// Plan the download with this length instead of the one the server reports, for
+    /// servers that send a wrong or no `Content-Length`; range requests past it fail
+    /// A wrong value truncates or corrupts the output
+    pub fn with_content_length(mut self, content_length: Option<u64>) -> Self {
+        self.content_length = content_length;
+        self
+    }
+
+    /// Fail a request once no data has arrived for `timeout`, so a stalled connection is
+    /// retried instead of trickling on forever; `None` waits indefinitely
+    pub fn with_stall_timeout(mut self, timeout: Option<Duration>) -> Result<Self> {
+        self.stall_timeout = timeout;
+        self.client = self.build_client()?;
+        Ok(self)
+    }
+
+    /// When the HEAD response has no `Accept-Ranges: bytes` or no `Content-Length`, send
+    /// a `Range: bytes=0-0` GET and trust a `206` with a `Content-Range` total instead,
+    /// for servers that honor ranges without advertising them
+    pub fn with_range_probe(mut self, range_probe: bool) -> Self {
+        self.range_probe = range_probe;
+        self
+    }
+
+    fn build_client(&self) -> Result<Client> {
+        let url =
+            reqwest::Url::parse(&self.url).map_err(|e| S3FcpError::InvalidUri(e.to_string()))?;
+        let port = url.port_or_known_default();
+
+        let mut builder = Client::builder();
+        if let Some(max) = self.max_connections {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        for o in self.overrides.iter().filter(|o| Some(o.port) == port) {
+            builder = builder.resolve(&o.host, o.socket_addr());
+        }
+        // Restarts with every read, unlike a timeout for the whole request
+        if let Some(timeout) = self.stall_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        // A fixed flow-control window would let the one connection cap the throughput
+        // of all chunks, so it grows with the measured bandwidth
+        builder = match (self.http2, url.scheme()) {
+            (false, _) => builder.http1_only(),
+            (true, "http") => builder.http2_prior_knowledge().http2_adaptive_window(true),
+            (true, _) => builder.http2_adaptive_window(true),
+        };
+        Ok(builder.build()?)
+    }
+
+    /// Wait for a free connection if their number is limited
+    async fn connection(&self) -> Option<SemaphorePermit<'_>> {
+        match &self.connections {
+            Some(connections) => connections.acquire().await.ok(),
+            None => None,
+        }
+    }
+
+    /// Name a read that hit the stall timeout as such
+    fn read_error(&self, error: reqwest::Error) -> S3FcpError {
+        match self.stall_timeout {
+            Some(timeout) if error.is_timeout() => S3FcpError::HttpError(format!(
+                "connection stalled: no data received for {:?}",
+                timeout
+            )),
+            _ => error.into(),
+        }
+    }
+
+    fn request(&self, method: Method) -> RequestBuilder {
+        let mut headers = self.headers.clone();
+        if let Some(host) = &self.host {
+            headers.insert(HOST, host.clone());
+        }
+        self.client.request(method, &self.url).headers(headers)
+    }
+
+    /// Check that the server answers the URL with the configured headers, using the
+    /// request `head` would, and return the endpoint that answered after redirects
+    pub async fn ping(&self) -> Result<Endpoint> {
+        let _connection = self.connection().await;
+        let mut response = None;
+        if self.head_method != HeadMethod::Get {
+            let head = self.request(Method::HEAD).send().await?;
+            let rejected = matches!(
+                head.status(),
+                StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+            );
+            if !(rejected && self.head_method == HeadMethod::Auto) {
+                response = Some(("HEAD request", head));
+            }
+        }
+        let (context, response) = match response {
+            Some(response) => response,
+            None => (
+                "GET probe",
+                self.request(Method::GET)
+                    .header(RANGE, "bytes=0-0")
+                    .send()
+                    .await?,
+            ),
+        };
+
+        let status = response.status();
+        if !status.is_success() && status != StatusCode::RANGE_NOT_SATISFIABLE {
+            return Err(status_error(context, status));
         }
+        Ok(Endpoint::from_url(
+            response.url().as_str(),
+            response.remote_addr(),
+        ))
     }
+
+    /// Determine size and range support with a `Range: bytes=0-0` GET
+    /// for servers that don't support HEAD
+    async fn probe_with_get(&self) -> Result<ObjectMetadata> {
+        let _connection = self.connection().await;
+        let response = self
+            .request(Method::GET)
+            .header(RANGE, "bytes=0-0")
+            .send()
+            .await?;
+
+        let etag = header_str(&response, ETAG).map(str::to_string);
+        let content_type = header_str(&response, CONTENT_TYPE).map(str::to_string);
+        let last_modified = last_modified(&response);
+
+        match response.status() {
+            // 416 is what servers return for a range request on an empty object
+            StatusCode::PARTIAL_CONTENT | StatusCode::RANGE_NOT_SATISFIABLE => {
+                let content_range = header_str(&response, CONTENT_RANGE);
+                // With an unknown total, e.g. `bytes 0-0/*`, there's nothing to plan
+                // chunks from, so the body is fetched in a single request instead
+                if content_range.is_some_and(has_unknown_total) {
+                    return Ok(ObjectMetadata {
+                        content_length: self.content_length.unwrap_or(0),
+                        supports_range: self.content_length.is_some(),
+                        etag,
+                        content_type,
+                        content_md5: None,
+                        last_modified,
+                    });
+                }
+
+                let content_length = self
+                    .content_length
+                    .or_else(|| content_range.and_then(parse_content_range_total))
+                    .ok_or_else(|| {
+                        S3FcpError::HttpError(
+                            "Missing or invalid Content-Range header in GET probe".to_string(),
+                        )
+                    })?;
+
+                Ok(ObjectMetadata {
+                    content_length,
+                    supports_range: true,
+                    etag,
+                    content_type,
+                    content_md5: None,
+                    last_modified,
+                })
+            }
+            // The server ignored the range; the body is dropped unread
+            status if status.is_success() => {
+                let content_length = self
+                    .content_length
+                    .or_else(|| header_str(&response, CONTENT_LENGTH)?.parse().ok())
+                    .ok_or_else(|| {
+                        S3FcpError::HttpError("Missing Content-Length header".to_string())
+                    })?;
+
+                Ok(ObjectMetadata {
+                    content_length,
+                    supports_range: false,
+                    etag,
+                    content_type,
+                    content_md5: None,
+                    last_modified,
+                })
+            }
+            status => Err(status_error("GET probe", status)),
+        }
+    }
+}
+
+/// Map an unsuccessful status to an error, keeping 404 and 401/403 distinct
+/// Other client errors would fail the same way again, except 408 and 429
+fn status_error(context: &str, status: StatusCode) -> S3FcpError {
+    let message = format!("{} failed with status: {}", context, status);
+    match status {
+        StatusCode::NOT_FOUND => S3FcpError::NotFound(message),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => S3FcpError::AccessDenied(message),
+        StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS => {
+            S3FcpError::HttpError(message)
+        }
+        status if status.is_client_error() => S3FcpError::RequestRejected(message),
+        _ => S3FcpError::HttpError(message),
+    }
+}
+
+fn header_str(response: &Response, name: impl reqwest::header::AsHeaderName) -> Option<&str> {
+    response.headers().get(name).and_then(|v| v.to_str().ok())
+}
+
+/// `Last-Modified` of a response, if it is a valid HTTP date
+fn last_modified(response: &Response) -> Option<SystemTime> {
+    let value = header_str(response, LAST_MODIFIED)?;
+    let time = DateTime::from_str(value, DateTimeFormat::HttpDate).ok()?;
+    SystemTime::try_from(time).ok()
+}
+
+/// Extract the complete length from a `Content-Range` value such as `bytes 0-0/1234`
+fn parse_content_range_total(value: &str) -> Option<u64> {
+    value
+        .strip_prefix("bytes ")?
+        .split_once('/')
+        .and_then(|(_, total)| total.parse().ok())
+}
+
+/// Whether a `Content-Range` value leaves the complete length unknown, as in `bytes 0-0/*`
+fn has_unknown_total(value: &str) -> bool {
+    value
+        .strip_prefix("bytes ")
+        .is_some_and(|range| range.ends_with("/*"))
 }
 
 #[async_trait]
 impl DownloadClient for HttpClient {
     async fn head(&self) -> Result<ObjectMetadata> {
-        let response = self.client.head(&self.url).send().await?;
+        if self.head_method == HeadMethod::Get {
+            return self.probe_with_get().await;
+        }
+
+        let connection = self.connection().await;
+        let response = self.request(Method::HEAD).send().await?;
+
+        if self.head_method == HeadMethod::Auto
+            && matches!(
+                response.status(),
+                StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+            )
+        {
+            drop(connection);
+            return self.probe_with_get().await;
+        }
 
         if !response.status().is_success() {
-            return Err(S3FcpError::HttpError(format!(
-                "HEAD request failed with status: {}",
-                response.status()
-            )));
+            return Err(status_error("HEAD request", response.status()));
         }
 
-        let content_length = response
-            .headers()
-            .get(CONTENT_LENGTH)
-            .and_then(|v| v.to_str().ok())
-            .and_then(|v| v.parse().ok())
-            .ok_or_else(|| S3FcpError::HttpError("Missing Content-Length header".to_string()))?;
+        let content_length = self
+            .content_length
+            .or_else(|| header_str(&response, CONTENT_LENGTH)?.parse().ok());
 
-        let supports_range = response
-            .headers()
-            .get(ACCEPT_RANGES)
-            .and_then(|v| v.to_str().ok())
+        let supports_range = header_str(&response, ACCEPT_RANGES)
             .map(|v| v == "bytes")
             .unwrap_or(false);
 
+        let etag = header_str(&response, ETAG).map(str::to_string);
+        let content_type = header_str(&response, CONTENT_TYPE).map(str::to_string);
+        let content_md5 = header_str(&response, CONTENT_MD5).map(str::to_string);
+        let last_modified = last_modified(&response);
+
+        if self.range_probe && !(supports_range && content_length.is_some()) {
+            drop(connection);
+            // A server that ignores the range, or can't name the total, is still
+            // described by the HEAD response if that has a length
+            match self.probe_with_get().await {
+                Ok(probed) if probed.supports_range || content_length.is_none() => {
+                    return Ok(ObjectMetadata {
+                        etag: probed.etag.or(etag),
+                        content_type: probed.content_type.or(content_type),
+                        content_md5,
+                        last_modified: probed.last_modified.or(last_modified),
+                        ..probed
+                    });
+                }
+                Err(e) if content_length.is_none() => return Err(e),
+                _ => {}
+            }
+        }
+
+        let content_length = content_length
+            .ok_or_else(|| S3FcpError::HttpError("Missing Content-Length header".to_string()))?;
+
         Ok(ObjectMetadata {
             content_length,
             supports_range,
+            etag,
+            content_type,
+            content_md5,
+            last_modified,
         })
     }
 
     async fn get_range(&self, start: u64, end: u64) -> Result<Bytes> {
+        let mut buffer = BytesMut::new();
+        self.get_range_into(start, end, &mut buffer).await?;
+        Ok(buffer.freeze())
+    }
+
+    async fn get_range_into(&self, start: u64, end: u64, buffer: &mut BytesMut) -> Result<()> {
+        if let Some(length) = self.content_length.filter(|&length| end >= length) {
+            return Err(S3FcpError::InvalidRange(format!(
+                "bytes {}-{} are past the declared content length of {}",
+                start, end, length
+            )));
+        }
+
         let range = format!("bytes={}-{}", start, end);
-        let response = self
-            .client
-            .get(&self.url)
+        let _connection = self.connection().await;
+        let mut response = self
+            .request(Method::GET)
             .header(RANGE, range)
             .send()
             .await?;
 
+        if !response.status().is_success() {
+            return Err(status_error("Range request", response.status()));
+        }
+
         // Check for 206 Partial Content
         if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
-            return Err(S3FcpError::HttpError(format!(
+            return Err(S3FcpError::RangeNotSupported(format!(
                 "Expected 206 Partial Content, got {}",
                 response.status()
             )));
         }
 
-        Ok(response.bytes().await?)
+        while let Some(data) = response.chunk().await.map_err(|e| self.read_error(e))? {
+            buffer.extend_from_slice(&data);
+        }
+
+        Ok(())
     }
 
     async fn get_full(&self) -> Result<Bytes> {
-        let response = self.client.get(&self.url).send().await?;
+        let _connection = self.connection().await;
+        let response = self.request(Method::GET).send().await?;
 
         if !response.status().is_success() {
-            return Err(S3FcpError::HttpError(format!(
-                "GET request failed with status: {}",
-                response.status()
-            )));
+            return Err(status_error("GET request", response.status()));
         }
 
-        Ok(response.bytes().await?)
+        response.bytes().await.map_err(|e| self.read_error(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_content_range_total() {
+        assert_eq!(parse_content_range_total("bytes 0-0/1234"), Some(1234));
+        assert_eq!(parse_content_range_total("bytes */0"), Some(0));
+        assert_eq!(parse_content_range_total("bytes 0-0/*"), None);
+        assert_eq!(parse_content_range_total("0-0/1234"), None);
+    }
+
+    #[test]
+    fn test_has_unknown_total() {
+        assert!(has_unknown_total("bytes 0-0/*"));
+        assert!(!has_unknown_total("bytes 0-0/1234"));
+        assert!(!has_unknown_total("bytes */0"));
+        assert!(!has_unknown_total("items 0-0/*"));
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let header = HeaderArg::parse("Authorization:  Bearer abc ").unwrap();
+        assert_eq!(header.name, "Authorization");
+        assert_eq!(header.value, "Bearer abc");
+        assert_eq!(HeaderArg::parse("X-Empty:").unwrap().value, "");
+
+        assert!(HeaderArg::parse("no colon").is_err());
+        assert!(HeaderArg::parse("Bad Name: x").is_err());
+    }
+
+    #[test]
+    fn test_header_value_from_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("token");
+        std::fs::write(&path, "Bearer secret\r\n\n").unwrap();
+
+        let header = HeaderArg::parse(&format!("Authorization: @{}", path.display())).unwrap();
+        let (name, value) = header.resolve().unwrap();
+        assert_eq!(name, "authorization");
+        assert_eq!(value, "Bearer secret");
+        assert!(value.is_sensitive());
+
+        let missing = HeaderArg::parse("Authorization: @/nonexistent/token").unwrap();
+        assert!(matches!(
+            missing.resolve(),
+            Err(S3FcpError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_header_file() {
+        let headers =
+            parse_header_file("# credentials\nAuthorization: Bearer abc\n\nX-Team: data\n")
+                .unwrap();
+        assert_eq!(
+            headers,
+            vec![
+                HeaderArg::parse("Authorization: Bearer abc").unwrap(),
+                HeaderArg::parse("X-Team: data").unwrap(),
+            ]
+        );
+        assert!(parse_header_file("X-Ok: 1\ninvalid\n")
+            .unwrap_err()
+            .starts_with("line 2"));
+
+        // Later headers win
+        let mut headers = headers;
+        headers.push(HeaderArg::parse("X-Team: web").unwrap());
+        let map = header_map(&headers).unwrap();
+        assert_eq!(map["x-team"], "web");
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_status_error() {
+        assert!(matches!(
+            status_error("GET request", StatusCode::NOT_FOUND),
+            S3FcpError::NotFound(_)
+        ));
+        assert!(matches!(
+            status_error("GET request", StatusCode::UNAUTHORIZED),
+            S3FcpError::AccessDenied(_)
+        ));
+        assert!(matches!(
+            status_error("GET request", StatusCode::FORBIDDEN),
+            S3FcpError::AccessDenied(_)
+        ));
+        assert!(matches!(
+            status_error("GET request", StatusCode::INTERNAL_SERVER_ERROR),
+            S3FcpError::HttpError(_)
+        ));
+    }
+
+    #[test]
+    fn test_client_errors_are_not_retried() {
+        for status in [StatusCode::BAD_REQUEST, StatusCode::RANGE_NOT_SATISFIABLE] {
+            let error = status_error("Range request", status);
+            assert!(matches!(error, S3FcpError::RequestRejected(_)));
+            assert!(!error.is_retryable());
+        }
+        for status in [
+            StatusCode::REQUEST_TIMEOUT,
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::SERVICE_UNAVAILABLE,
+        ] {
+            assert!(status_error("Range request", status).is_retryable());
+        }
     }
 }
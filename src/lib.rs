@@ -1,8 +1,35 @@
+pub mod adaptive;
+pub mod batch;
+pub mod cache;
 pub mod chunk;
 pub mod cli;
+pub mod config;
+pub mod diskfull;
 pub mod downloader;
 pub mod error;
+pub mod events;
+pub mod glob;
+pub mod head_cache;
 pub mod http_client;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod mmap;
+pub mod mtime;
+pub mod ping;
 pub mod progress;
+pub mod race;
+pub mod ratelimit;
+pub mod reader;
+pub mod recursive;
+pub mod resolve;
 pub mod s3_client;
+pub mod segment;
+pub mod template;
+pub mod terminal;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod text;
+pub mod timings;
+pub mod uploader;
 pub mod uri;
+pub mod verify;
@@ -1,55 +1,757 @@
-use clap::Parser;
+use aws_config::meta::region::RegionProviderChain;
+use aws_config::provider_config::ProviderConfig;
+use aws_config::stalled_stream_protection::StalledStreamProtectionConfig;
+use aws_config::web_identity_token::{StaticConfiguration, WebIdentityTokenCredentialsProvider};
+use aws_config::Region;
+use aws_credential_types::provider::SharedCredentialsProvider;
+use clap::{CommandFactory, FromArgMatches};
+use reqwest::header::HeaderMap;
+#[cfg(feature = "metrics")]
+use s3fcp::metrics::{MetricsListener, MetricsPusher, PUSH_INTERVAL};
 use s3fcp::{
-    cli::{Cli, Command, DownloadArgs},
-    downloader::download_to_stdout,
-    http_client::HttpClient,
-    s3_client::S3Client,
-    uri::{HttpUri, S3Uri},
+    batch::{list_entry, BatchSource, BatchSummary},
+    cache::CachingClient,
+    chunk::parse_range_index,
+    cli::{
+        BatchArgs, Cli, Command, DownloadArgs, ErrorFormat, OnError, PingArgs, S3Args,
+        TimingsFormat, UploadArgs,
+    },
+    config::Config,
+    downloader::{download_to_file, download_to_stdout},
+    error::{Result, S3FcpError},
+    glob::has_glob,
+    head_cache::HeadCache,
+    http_client::{header_map, parse_header_file, HeaderArg, HttpClient},
+    mtime::{is_newer, set_modified},
+    ping::ping,
+    race::RacingClient,
+    recursive::{download_glob, download_prefix, PrefixOptions},
+    resolve::{sdk_http_client, ResolveOverride},
+    s3_client::{version_ago, DownloadClient, ObjectVersion, ResponseOverrides, S3Client},
+    timings::Timings,
+    uploader::{upload_file, S3Uploader},
+    uri::{cdn_url, validate_accelerate_bucket, validate_bucket_name, HttpUri, S3Uri},
+    verify::ChecksumManifest,
 };
+use std::collections::{HashMap, HashSet};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
 
 #[tokio::main]
 async fn main() {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let error_format = cli.error_format;
 
-    let result = match cli.command {
+    let config = match &cli.config {
+        Some(path) => Config::load(path),
+        None => Ok(Config::default()),
+    };
+    let result = match config {
+        Ok(config) => {
+            config.apply(&mut cli.command, &matches);
+            run(cli.command, &config, error_format).await
+        }
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        e.report(error_format);
+        std::process::exit(e.exit_code());
+    }
+}
+
+async fn run(command: Command, config: &Config, error_format: ErrorFormat) -> Result<()> {
+    match command {
         Command::S3(args) => {
-            let uri = match S3Uri::parse(&args.uri) {
-                Ok(uri) => uri,
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
-            };
+            let mut download_args = DownloadArgs::from(&args);
+            download_args.error_format = error_format;
+            let download_args =
+                with_range_index(download_args, args.ranges_file.as_deref()).await?;
+            #[cfg(feature = "metrics")]
+            let (download_args, pusher) =
+                start_metrics(args.metrics_endpoint.as_deref(), download_args);
+
+            let timings = download_args.timings.clone();
+            let result = download_s3(&args, download_args, config).await;
+            let result = with_accelerate_hint(result, args.accelerate);
+            #[cfg(feature = "metrics")]
+            finish_metrics(pusher).await;
+            report_timings(timings.as_deref(), args.timings);
+            result
+        }
+        Command::Http(args) => {
+            let uri = HttpUri::parse(&args.url)?;
+
+            let download_args =
+                with_range_index(DownloadArgs::from(&args), args.ranges_file.as_deref()).await?;
+            #[cfg(feature = "metrics")]
+            let (download_args, pusher) =
+                start_metrics(args.metrics_endpoint.as_deref(), download_args);
 
-            let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
-            let download_args = DownloadArgs::from(&args);
-            let client = Arc::new(S3Client::new(
-                aws_sdk_s3::Client::new(&config),
+            let headers = load_headers(&args.headers, args.header_from_file.as_deref()).await?;
+            let source = uri.url.clone();
+            let client = HttpClient::new(uri.url)
+                .with_head_method(args.head_method)
+                .with_headers(headers)
+                .with_host(args.host.as_deref())?
+                .with_http2(args.http2)?
+                .with_max_connections(args.max_connections_per_host)?
+                .with_resolve(&args.resolve)?
+                .with_stall_timeout(args.stall_timeout)?
+                .with_range_probe(args.probe_ranges)
+                .with_content_length(args.content_length);
+            let client = Arc::new(client);
+            let client = with_cache(client, source, args.cache_dir.clone(), args.cache_max_size);
+
+            let timings = download_args.timings.clone();
+            let result =
+                download_to_output(client, download_args, args.output.as_deref(), args.force).await;
+            #[cfg(feature = "metrics")]
+            finish_metrics(pusher).await;
+            report_timings(timings.as_deref(), args.timings);
+            result
+        }
+        Command::Cp(args) => {
+            let uri = parse_s3_uri(&args.dest, args.skip_bucket_validation)?;
+            if args.accelerate {
+                validate_accelerate_bucket(&uri.bucket)?;
+            }
+
+            let web_identity = web_identity(&args.web_identity_token_file, &args.role_arn);
+            let config = load_aws_config(&[], false, web_identity, config).await;
+            let config = with_region(config, uri.region.as_deref());
+            let uploader = Arc::new(S3Uploader::new(
+                s3_sdk_client(&config, args.dualstack, args.fips, args.accelerate),
                 uri.bucket,
                 uri.key,
-                args.version_id,
             ));
 
-            download_to_stdout(client, download_args).await
+            let result = upload_file(uploader, &args.src, UploadArgs::from(&args)).await;
+            with_accelerate_hint(result, args.accelerate)
         }
-        Command::Http(args) => {
-            let uri = match HttpUri::parse(&args.url) {
-                Ok(uri) => uri,
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
+        Command::Presign(args) => {
+            let uri = parse_s3_uri(&args.uri, args.skip_bucket_validation)?;
+            if args.accelerate {
+                validate_accelerate_bucket(&uri.bucket)?;
+            }
+
+            let web_identity = web_identity(&args.web_identity_token_file, &args.role_arn);
+            let config = load_aws_config(&[], false, web_identity, config).await;
+            let config = with_region(config, uri.region.as_deref());
+            let client = S3Client::new(
+                s3_sdk_client(&config, args.dualstack, args.fips, args.accelerate),
+                uri.bucket,
+                uri.key,
+                args.version_id,
+            )
+            .with_response_overrides(ResponseOverrides {
+                content_type: args.response_content_type,
+                content_disposition: args.response_content_disposition,
+            });
+
+            println!("{}", client.presign(args.expires_in).await?);
+            Ok(())
+        }
+        Command::Batch(args) => download_batch(&args, config, error_format).await,
+        Command::Ping(args) => ping_target(&args, config).await,
+    }
+}
+
+/// Send one lightweight request to the target with the same credentials, endpoint and
+/// connection settings a download would use, and print how long it took
+async fn ping_target(args: &PingArgs, file: &Config) -> Result<()> {
+    let target = args.target.as_str();
+    let request = async {
+        if target.starts_with("s3://") {
+            // The bucket alone is enough, and then checked with HeadBucket
+            let uri = S3Uri::parse_prefix(target)?;
+            if !args.skip_bucket_validation {
+                validate_bucket_name(&uri.bucket)?;
+            }
+            if args.accelerate {
+                validate_accelerate_bucket(&uri.bucket)?;
+            }
+            let web_identity = web_identity(&args.web_identity_token_file, &args.role_arn);
+            let config =
+                load_aws_config(&args.resolve, args.no_sign_request, web_identity, file).await;
+            let config = with_region(config, uri.region.as_deref());
+            let client = S3Client::new(
+                s3_sdk_client(&config, args.dualstack, args.fips, args.accelerate),
+                uri.bucket,
+                uri.key,
+                None,
+            );
+            with_accelerate_hint(client.ping().await, args.accelerate)
+        } else {
+            let uri = HttpUri::parse(target)?;
+            let headers = load_headers(&args.headers, args.header_from_file.as_deref()).await?;
+            HttpClient::new(uri.url)
+                .with_head_method(args.head_method)
+                .with_headers(headers)
+                .with_resolve(&args.resolve)?
+                .ping()
+                .await
+        }
+    };
+    let request = async {
+        tokio::time::timeout(args.timeout, request)
+            .await
+            .unwrap_or_else(|_| {
+                // Not an HTTP error: S3 targets time out the same way
+                Err(S3FcpError::DownloadFailed(format!(
+                    "no answer from {} within {:?}",
+                    target, args.timeout
+                )))
+            })
+    };
+
+    let report = ping(target, request).await?;
+    if args.json {
+        println!("{}", report.to_json());
+    } else {
+        println!("{}", report);
+    }
+    Ok(())
+}
+
+/// Download each URI of the list as soon as its line is read, one at a time so the
+/// batch stays within `--concurrency` workers, and summarize the outcome at the end
+/// A failed entry stops the batch, or with `--on-error continue` is reported and
+/// skipped; the batch then fails if any entry did
+async fn download_batch(args: &BatchArgs, file: &Config, error_format: ErrorFormat) -> Result<()> {
+    let input: Box<dyn AsyncBufRead + Unpin + Send> = match &args.list {
+        _ if args.reads_stdin() => Box::new(BufReader::new(tokio::io::stdin())),
+        Some(path) => Box::new(BufReader::new(tokio::fs::File::open(path).await?)),
+        None => unreachable!("clap requires a list or --from-stdin"),
+    };
+    tokio::fs::create_dir_all(&args.output).await?;
+
+    // One S3 client per region named in the list, `None` for the default one
+    let mut sdk_clients = HashMap::new();
+    // Lets a download reuse the HEAD response the output template needed
+    let head_cache = (!args.no_head_cache).then(|| Arc::new(HeadCache::default()));
+    let mut names = HashSet::new();
+    let mut summary = BatchSummary::default();
+    let mut lines = input.lines();
+    while let Some(line) = lines.next_line().await? {
+        let Some(uri) = list_entry(&line) else {
+            continue;
+        };
+
+        let download = async {
+            let source = BatchSource::parse(uri, args.skip_bucket_validation)?;
+            let client: Arc<dyn DownloadClient> = match &source {
+                BatchSource::S3(uri) => {
+                    if !sdk_clients.contains_key(&uri.region) {
+                        let config = load_aws_config(&[], args.no_sign_request, None, file).await;
+                        let config = with_region(config, uri.region.as_deref());
+                        let client = s3_sdk_client(&config, false, false, false);
+                        sdk_clients.insert(uri.region.clone(), client);
+                    }
+                    let sdk_client = sdk_clients[&uri.region].clone();
+                    let client =
+                        S3Client::new(sdk_client, uri.bucket.clone(), uri.key.clone(), None);
+                    match &head_cache {
+                        Some(cache) => Arc::new(client.with_head_cache(cache.clone())),
+                        None => Arc::new(client),
+                    }
                 }
+                BatchSource::Http(uri) => Arc::new(HttpClient::new(uri.url.clone())),
             };
+            let name = match &args.output_template {
+                Some(template) => {
+                    // Only a HEAD request tells the date; for S3 the download reuses it
+                    let last_modified = if template.uses_date() {
+                        client.head().await?.last_modified
+                    } else {
+                        None
+                    };
+                    source.render(template, last_modified)?
+                }
+                None => PathBuf::from(source.file_name()?),
+            };
+            if !names.insert(name.clone()) {
+                return Err(S3FcpError::InvalidUri(format!(
+                    "an earlier entry was already saved as {}",
+                    name.display()
+                )));
+            }
+            let path = args.output.join(name);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            // Atomic output leaves no empty or partial file that looks like a finished download
+            download_to_file(client, DownloadArgs::from(args), &path).await?;
+            Ok(tokio::fs::metadata(&path).await?.len())
+        };
+        match download.await {
+            Ok(bytes) => {
+                summary.downloaded += 1;
+                summary.bytes += bytes;
+            }
+            Err(e) if args.on_error == OnError::Abort => return Err(e.in_entry(uri)),
+            Err(e) => {
+                summary.failed.push((uri.to_string(), e.to_string()));
+                e.in_entry(uri).report(error_format);
+            }
+        }
+    }
 
-            let client = Arc::new(HttpClient::new(uri.url));
+    if !args.quiet {
+        eprintln!("{}", summary);
+    }
+    summary.into_result()
+}
 
-            download_to_stdout(client, DownloadArgs::from(&args)).await
+async fn download_s3(args: &S3Args, download_args: DownloadArgs, file: &Config) -> Result<()> {
+    if args.recursive {
+        let uri = S3Uri::parse_prefix(&args.uri)?;
+        if !args.skip_bucket_validation {
+            validate_bucket_name(&uri.bucket)?;
+        }
+        if args.accelerate {
+            validate_accelerate_bucket(&uri.bucket)?;
         }
+
+        let web_identity = web_identity(&args.web_identity_token_file, &args.role_arn);
+        let config = load_aws_config(&args.resolve, args.no_sign_request, web_identity, file).await;
+        let config = with_region(config, uri.region.as_deref());
+        let config = with_stall_timeout(config, args.stall_timeout);
+        let output_dir = args.output.as_deref().expect("clap requires --output");
+
+        let checksums = match &args.checksum_file {
+            Some(path) => Some(read_checksum_manifest(path).await?),
+            None => None,
+        };
+        let options = PrefixOptions::builder()
+            .resume_listing(args.resume_listing)
+            .head_each_object(args.head_each_object)
+            .maybe_checksums(checksums)
+            .overwrite_if_newer(args.overwrite_if_newer)
+            .preserve_mtime(args.preserve_mtime)
+            .dry_run(args.dry_run)
+            .on_error(args.on_error)
+            .maybe_output_template(args.output_template.clone())
+            .build();
+
+        return download_prefix(
+            s3_sdk_client(&config, args.dualstack, args.fips, args.accelerate),
+            &uri.bucket,
+            &uri.key,
+            output_dir,
+            download_args,
+            options,
+        )
+        .await;
+    }
+
+    let uri = parse_s3_uri(&args.uri, args.skip_bucket_validation)?;
+    if args.accelerate {
+        validate_accelerate_bucket(&uri.bucket)?;
+    }
+    let cdn = match &args.cdn_url {
+        Some(template) => Some(cdn_url(template, &uri.key)?),
+        None => None,
     };
 
-    if let Err(e) = result {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+    let web_identity = web_identity(&args.web_identity_token_file, &args.role_arn);
+    let config = load_aws_config(&args.resolve, args.no_sign_request, web_identity, file).await;
+    let config = with_region(config, uri.region.as_deref());
+    let config = with_stall_timeout(config, args.stall_timeout);
+    let sdk_client = s3_sdk_client(&config, args.dualstack, args.fips, args.accelerate);
+
+    if !args.no_glob && has_glob(&uri.key) {
+        let Some(output_dir) = args.output.as_deref() else {
+            return Err(S3FcpError::InvalidUri(format!(
+                "'{}' is a key pattern; pass --output with a directory for the matches, \
+                 or --no-glob if the key contains these characters",
+                args.uri
+            )));
+        };
+        if args.cdn_url.is_some() {
+            return Err(S3FcpError::InvalidUri(format!(
+                "'{}' is a key pattern; --cdn-url needs a single key (pass --no-glob)",
+                args.uri
+            )));
+        }
+        if args.version_id.is_some() || args.versions_ago.is_some() || args.list_versions {
+            return Err(S3FcpError::InvalidUri(format!(
+                "'{}' is a key pattern; version options need a single key (pass --no-glob)",
+                args.uri
+            )));
+        }
+        return download_glob(
+            sdk_client,
+            &uri.bucket,
+            &uri.key,
+            output_dir,
+            download_args,
+            args.on_error,
+            args.output_template.as_ref(),
+        )
+        .await;
+    }
+    if args.output_template.is_some() {
+        return Err(S3FcpError::InvalidUri(format!(
+            "'{}' is a single key; --output-template needs --recursive or a key pattern",
+            args.uri
+        )));
+    }
+
+    let mut version_id = args.version_id.clone();
+    if args.list_versions || args.versions_ago.is_some() {
+        let listing = S3Client::new(
+            sdk_client.clone(),
+            uri.bucket.clone(),
+            uri.key.clone(),
+            None,
+        );
+        let versions = listing.versions().await?;
+        if args.list_versions {
+            print_versions(&versions, args.json);
+            return Ok(());
+        }
+        if let Some(ago) = args.versions_ago {
+            version_id = Some(version_ago(&versions, ago)?);
+        }
+    }
+
+    let source = match &version_id {
+        Some(version) => format!("s3://{}/{}?versionId={}", uri.bucket, uri.key, version),
+        None => format!("s3://{}/{}", uri.bucket, uri.key),
+    };
+    let mut client = S3Client::new(sdk_client, uri.bucket, uri.key, version_id)
+        .with_response_overrides(ResponseOverrides {
+            content_type: args.response_content_type.clone(),
+            content_disposition: args.response_content_disposition.clone(),
+        });
+    let mut last_modified = None;
+    if args.overwrite_if_newer || args.preserve_mtime {
+        // The download reuses this HEAD response instead of sending another
+        client = client.with_head_cache(Arc::new(HeadCache::default()));
+        last_modified = client.head().await?.last_modified;
+    }
+    if args.overwrite_if_newer {
+        let path = args.output.as_deref().expect("clap requires --output");
+        if !is_newer(path, last_modified).await {
+            if !args.quiet {
+                eprintln!("{} is up to date, skipping download", path.display());
+            }
+            return Ok(());
+        }
+    }
+
+    let client = Arc::new(client);
+    if let Some(path) = &args.save_tags {
+        save_tags(&client, path).await?;
+    }
+    if let Some(path) = &args.save_metadata {
+        save_metadata(&client, path).await?;
+    }
+    let client: Arc<dyn DownloadClient> = match cdn {
+        Some(cdn) => {
+            let cdn = HttpClient::new(cdn.url).with_resolve(&args.resolve)?;
+            Arc::new(RacingClient::new(client, Arc::new(cdn)))
+        }
+        None => client,
+    };
+    let client = with_cache(client, source, args.cache_dir.clone(), args.cache_max_size);
+
+    download_to_output(client, download_args, args.output.as_deref(), args.force).await?;
+    if let (true, Some(path), Some(time)) = (args.preserve_mtime, &args.output, last_modified) {
+        set_modified(path, time).await?;
+    }
+    Ok(())
+}
+
+/// Print one line per version, or a JSON array with `--json`
+fn print_versions(versions: &[ObjectVersion], json: bool) {
+    if json {
+        let versions: Vec<_> = versions.iter().map(ObjectVersion::to_json).collect();
+        println!("{}", serde_json::Value::from(versions));
+    } else {
+        for version in versions {
+            println!("{}", version);
+        }
+    }
+}
+
+/// Write the object's tags to `path` as a JSON object
+async fn save_tags(client: &S3Client, path: &Path) -> Result<()> {
+    let tags: serde_json::Map<String, serde_json::Value> = client
+        .tags()
+        .await?
+        .into_iter()
+        .map(|(key, value)| (key, value.into()))
+        .collect();
+
+    let json = serde_json::to_string_pretty(&tags).expect("tags serialize to JSON");
+    tokio::fs::write(path, json + "\n").await?;
+    Ok(())
+}
+
+/// Write the object's stored headers and user metadata to `path` as a JSON object
+async fn save_metadata(client: &S3Client, path: &Path) -> Result<()> {
+    let metadata = client.stored_metadata().await?.to_json();
+    let json = serde_json::to_string_pretty(&metadata).expect("metadata serializes to JSON");
+    tokio::fs::write(path, json + "\n").await?;
+    Ok(())
+}
+
+/// Token file and role ARN given with `--web-identity-token-file` and `--role-arn`
+/// clap requires both or neither
+fn web_identity<'a>(
+    token_file: &'a Option<PathBuf>,
+    role_arn: &'a Option<String>,
+) -> Option<(&'a Path, &'a str)> {
+    Some((token_file.as_deref()?, role_arn.as_deref()?))
+}
+
+/// Load the AWS config, pinning the hosts given with `--resolve`
+/// With `--no-sign-request` requests are sent unsigned, as needed for public buckets
+/// With a `web_identity` token file and role ARN, credentials come from
+/// `AssumeRoleWithWebIdentity` in the resolved region instead of the default chain
+/// The region and endpoint from `--config` are used only when the AWS environment
+/// and profile leave them unset
+async fn load_aws_config(
+    resolve: &[ResolveOverride],
+    no_sign_request: bool,
+    web_identity: Option<(&Path, &str)>,
+    file: &Config,
+) -> aws_config::SdkConfig {
+    let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+    if let Some(region) = &file.region {
+        loader = loader
+            .region(RegionProviderChain::default_provider().or_else(Region::new(region.clone())));
+    }
+    if no_sign_request {
+        loader = loader.no_credentials();
+    }
+    if !resolve.is_empty() {
+        loader = loader.http_client(sdk_http_client(resolve));
+    }
+
+    let mut config = loader.load().await;
+    if let Some((token_file, role_arn)) = web_identity {
+        let mut provider_config = ProviderConfig::default().with_region(config.region().cloned());
+        if !resolve.is_empty() {
+            provider_config = provider_config.with_http_client(sdk_http_client(resolve));
+        }
+        let provider = WebIdentityTokenCredentialsProvider::builder()
+            .static_configuration(StaticConfiguration {
+                web_identity_token_file: token_file.to_path_buf(),
+                role_arn: role_arn.to_string(),
+                session_name: "s3fcp".to_string(),
+            })
+            .configure(&provider_config)
+            .build();
+        config = config
+            .into_builder()
+            .credentials_provider(SharedCredentialsProvider::new(provider))
+            .build();
+    }
+
+    match &file.endpoint_url {
+        Some(endpoint) if config.endpoint_url().is_none() => {
+            config.into_builder().endpoint_url(endpoint).build()
+        }
+        _ => config,
+    }
+}
+
+/// Point S3 errors of an accelerated transfer at the likely cause, since S3 rejects
+/// every request to a bucket that doesn't have Transfer Acceleration enabled
+fn with_accelerate_hint<T>(result: Result<T>, accelerate: bool) -> Result<T> {
+    match result {
+        Err(S3FcpError::S3Error(message)) if accelerate => Err(S3FcpError::S3Error(format!(
+            "{} (check that Transfer Acceleration is enabled on the bucket)",
+            message
+        ))),
+        result => result,
+    }
+}
+
+/// Use the region given in the S3 URI, over those of the environment, profile and config file
+fn with_region(config: aws_config::SdkConfig, region: Option<&str>) -> aws_config::SdkConfig {
+    match region {
+        Some(region) => config
+            .into_builder()
+            .region(Region::new(region.to_string()))
+            .build(),
+        None => config,
+    }
+}
+
+/// Fail S3 responses that send no data for `timeout`, in place of the SDK's default
+/// grace period of 20s
+fn with_stall_timeout(
+    config: aws_config::SdkConfig,
+    timeout: Option<Duration>,
+) -> aws_config::SdkConfig {
+    match timeout {
+        Some(timeout) => config
+            .into_builder()
+            .stalled_stream_protection(
+                StalledStreamProtectionConfig::enabled()
+                    .grace_period(timeout)
+                    .build(),
+            )
+            .build(),
+        None => config,
+    }
+}
+
+/// Build the S3 client, switching to the dual-stack, FIPS or accelerated endpoint when
+/// asked to
+/// An endpoint URL from the environment or profile takes precedence over all of them
+fn s3_sdk_client(
+    config: &aws_config::SdkConfig,
+    dualstack: bool,
+    fips: bool,
+    accelerate: bool,
+) -> aws_sdk_s3::Client {
+    let mut builder = aws_sdk_s3::config::Builder::from(config);
+    if let Some(endpoint) = config.endpoint_url() {
+        for (set, flag) in [
+            (dualstack, "--dualstack"),
+            (fips, "--fips"),
+            (accelerate, "--accelerate"),
+        ] {
+            if set {
+                eprintln!(
+                    "Warning: {} is ignored because the endpoint URL {} is configured",
+                    flag, endpoint
+                );
+            }
+        }
+    } else {
+        builder = builder
+            .use_dual_stack(dualstack)
+            .use_fips(fips)
+            .accelerate(accelerate);
+    }
+    aws_sdk_s3::Client::from_conf(builder.build())
+}
+
+fn parse_s3_uri(uri: &str, skip_bucket_validation: bool) -> Result<S3Uri> {
+    if skip_bucket_validation {
+        S3Uri::parse_unvalidated(uri)
+    } else {
+        S3Uri::parse(uri)
+    }
+}
+
+/// Download to the file at `output`, or to stdout
+/// Binary output is refused when stdout is a terminal, unless `force` is set
+async fn download_to_output(
+    client: Arc<dyn DownloadClient>,
+    mut args: DownloadArgs,
+    output: Option<&Path>,
+    force: bool,
+) -> Result<()> {
+    match output {
+        Some(path) => download_to_file(client, args, path).await,
+        None => {
+            args.check_terminal = !force && std::io::stdout().is_terminal();
+            download_to_stdout(client, args).await
+        }
+    }
+}
+
+/// Download only the ranges listed in the `--ranges-file` index, if one is given
+async fn with_range_index(mut args: DownloadArgs, path: Option<&Path>) -> Result<DownloadArgs> {
+    if let Some(path) = path {
+        let json = tokio::fs::read_to_string(path).await?;
+        args.ranges = parse_range_index(&json)
+            .map_err(|e| S3FcpError::InvalidRange(format!("{}: {}", path.display(), e)))?;
+    }
+    Ok(args)
+}
+
+/// Headers from `--header-from-file` followed by those from `--header`, so the command
+/// line wins for names given in both
+async fn load_headers(headers: &[HeaderArg], file: Option<&Path>) -> Result<HeaderMap> {
+    let mut all = Vec::new();
+    if let Some(path) = file {
+        let text = tokio::fs::read_to_string(path).await?;
+        all = parse_header_file(&text)
+            .map_err(|e| S3FcpError::InvalidHeader(format!("file {}: {}", path.display(), e)))?;
+    }
+    all.extend_from_slice(headers);
+    header_map(&all)
+}
+
+/// Read the `sha256sum` manifest given with `--checksum-file`
+async fn read_checksum_manifest(path: &Path) -> Result<ChecksumManifest> {
+    let text = tokio::fs::read_to_string(path).await?;
+    ChecksumManifest::parse(&text).map_err(|e| {
+        S3FcpError::IntegrityError(format!(
+            "invalid checksum manifest {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// Wrap the client in a range cache when `--cache-dir` is given
+/// `source` names the object, so sources sharing the directory keep separate entries
+fn with_cache(
+    client: Arc<dyn DownloadClient>,
+    source: String,
+    cache_dir: Option<PathBuf>,
+    cache_max_size: usize,
+) -> Arc<dyn DownloadClient> {
+    match cache_dir {
+        Some(dir) => Arc::new(CachingClient::new(
+            client,
+            source,
+            dir,
+            cache_max_size as u64,
+        )),
+        None => client,
+    }
+}
+
+/// Report chunk events to a pushgateway when `--metrics-endpoint` is given
+#[cfg(feature = "metrics")]
+fn start_metrics(
+    endpoint: Option<&str>,
+    mut args: DownloadArgs,
+) -> (DownloadArgs, Option<MetricsPusher>) {
+    let Some(endpoint) = endpoint else {
+        return (args, None);
+    };
+
+    let metrics = Arc::new(MetricsListener::new());
+    args.listener = Some(metrics.clone());
+    (
+        args,
+        Some(MetricsPusher::start(endpoint, metrics, PUSH_INTERVAL)),
+    )
+}
+
+/// Push the final metrics; a failed push doesn't fail the download
+#[cfg(feature = "metrics")]
+async fn finish_metrics(pusher: Option<MetricsPusher>) {
+    if let Some(pusher) = pusher {
+        if let Err(e) = pusher.finish().await {
+            eprintln!("Warning: {}", e);
+        }
+    }
+}
+
+/// Print the `--timings` breakdown to stderr, also after a failed download
+fn report_timings(timings: Option<&Timings>, format: Option<TimingsFormat>) {
+    let (Some(timings), Some(format)) = (timings, format) else {
+        return;
+    };
+    let report = timings.report();
+    match format {
+        TimingsFormat::Text => eprintln!("{}", report),
+        TimingsFormat::Json => eprintln!("{}", report.to_json()),
     }
 }
@@ -0,0 +1,200 @@
+use crate::error::{Result, S3FcpError};
+use crate::events::{ChunkEvent, DownloadListener};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// How often metrics are pushed while a download is running
+pub const PUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Counts chunk lifecycle events as Prometheus metrics
+///
+/// Exported metrics:
+/// - `s3fcp_bytes_downloaded_total`: bytes of completed chunks
+/// - `s3fcp_chunks_completed_total`: chunks downloaded completely
+/// - `s3fcp_chunks_failed_total`: chunks that failed after all retries
+/// - `s3fcp_chunk_retries_total`: retried chunk requests
+/// - `s3fcp_active_chunks`: chunks currently being downloaded by a worker
+#[derive(Debug, Default)]
+pub struct MetricsListener {
+    bytes_downloaded: AtomicU64,
+    chunks_completed: AtomicU64,
+    chunks_failed: AtomicU64,
+    chunk_retries: AtomicU64,
+    active_chunks: AtomicI64,
+}
+
+impl MetricsListener {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Render the current values in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+
+        metric(
+            "s3fcp_bytes_downloaded_total",
+            "counter",
+            "Bytes of completed chunks",
+            self.bytes_downloaded.load(Ordering::Relaxed).to_string(),
+        );
+        metric(
+            "s3fcp_chunks_completed_total",
+            "counter",
+            "Chunks downloaded completely",
+            self.chunks_completed.load(Ordering::Relaxed).to_string(),
+        );
+        metric(
+            "s3fcp_chunks_failed_total",
+            "counter",
+            "Chunks that failed after all retries",
+            self.chunks_failed.load(Ordering::Relaxed).to_string(),
+        );
+        metric(
+            "s3fcp_chunk_retries_total",
+            "counter",
+            "Retried chunk requests",
+            self.chunk_retries.load(Ordering::Relaxed).to_string(),
+        );
+        metric(
+            "s3fcp_active_chunks",
+            "gauge",
+            "Chunks currently being downloaded",
+            self.active_chunks.load(Ordering::Relaxed).to_string(),
+        );
+
+        out
+    }
+}
+
+impl DownloadListener for MetricsListener {
+    fn on_event(&self, event: &ChunkEvent) {
+        match event {
+            ChunkEvent::Queued { .. } => {}
+            ChunkEvent::Started { attempt, .. } => {
+                if *attempt == 1 {
+                    self.active_chunks.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            ChunkEvent::Retried { .. } => {
+                self.chunk_retries.fetch_add(1, Ordering::Relaxed);
+            }
+            ChunkEvent::Completed { bytes, .. } => {
+                self.bytes_downloaded.fetch_add(*bytes, Ordering::Relaxed);
+                self.chunks_completed.fetch_add(1, Ordering::Relaxed);
+                self.active_chunks.fetch_sub(1, Ordering::Relaxed);
+            }
+            ChunkEvent::Failed { .. } => {
+                self.chunks_failed.fetch_add(1, Ordering::Relaxed);
+                self.active_chunks.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Periodically pushes a `MetricsListener` to a Prometheus pushgateway
+pub struct MetricsPusher {
+    client: reqwest::Client,
+    url: String,
+    metrics: Arc<MetricsListener>,
+    handle: JoinHandle<()>,
+}
+
+impl MetricsPusher {
+    /// Start pushing to `endpoint` (the pushgateway base URL) every `interval`
+    /// Periodic pushes are best effort; failures are ignored until `finish`
+    pub fn start(endpoint: &str, metrics: Arc<MetricsListener>, interval: Duration) -> Self {
+        let client = reqwest::Client::new();
+        let url = format!("{}/metrics/job/s3fcp", endpoint.trim_end_matches('/'));
+
+        let handle = tokio::spawn({
+            let (client, url, metrics) = (client.clone(), url.clone(), metrics.clone());
+            async move {
+                let mut ticker = tokio::time::interval(interval);
+                loop {
+                    ticker.tick().await;
+                    let _ = push(&client, &url, &metrics).await;
+                }
+            }
+        });
+
+        Self {
+            client,
+            url,
+            metrics,
+            handle,
+        }
+    }
+
+    /// Stop the periodic pushes and push the final values
+    pub async fn finish(self) -> Result<()> {
+        self.handle.abort();
+        push(&self.client, &self.url, &self.metrics).await
+    }
+}
+
+async fn push(client: &reqwest::Client, url: &str, metrics: &MetricsListener) -> Result<()> {
+    let response = client.put(url).body(metrics.render()).send().await?;
+
+    if !response.status().is_success() {
+        return Err(S3FcpError::HttpError(format!(
+            "Metrics push to {} failed with status {}",
+            url,
+            response.status()
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_follow_chunk_events() {
+        let metrics = MetricsListener::new();
+        let events = [
+            ChunkEvent::Started {
+                index: 0,
+                attempt: 1,
+            },
+            ChunkEvent::Started {
+                index: 1,
+                attempt: 1,
+            },
+            ChunkEvent::Retried {
+                index: 1,
+                attempt: 1,
+                delay: Duration::from_millis(100),
+                error: "timeout".to_string(),
+            },
+            ChunkEvent::Started {
+                index: 1,
+                attempt: 2,
+            },
+            ChunkEvent::Completed {
+                index: 0,
+                bytes: 100,
+            },
+        ];
+        for event in &events {
+            metrics.on_event(event);
+        }
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("s3fcp_bytes_downloaded_total 100\n"));
+        assert!(rendered.contains("s3fcp_chunks_completed_total 1\n"));
+        assert!(rendered.contains("s3fcp_chunk_retries_total 1\n"));
+        assert!(rendered.contains("s3fcp_active_chunks 1\n"));
+        assert!(rendered.contains("# TYPE s3fcp_active_chunks gauge\n"));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::chunk::{auto_chunk_size, create_chunks};
+use crate::cli::DownloadArgs;
+use crate::downloader::{check_expected_size, fetch_range, probe_within, progress_tracker};
+use crate::error::{Result, S3FcpError};
+use crate::s3_client::DownloadClient;
+use futures::TryStreamExt;
+use memmap2::{Mmap, MmapMut};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Download the whole object into a new file at `path` and return it mapped into memory
+///
+/// The file is sized to the object up front and each chunk is copied straight to its
+/// offset in the mapping as it arrives, so chunks are never reordered and the file is
+/// never read back. Sources without range support are fetched in a single request.
+///
+/// The mapping is only valid while no other process truncates or rewrites the file.
+/// Byte ranges, output compression, text normalization and split output can't be
+/// combined with a mapping and are rejected; checksums aren't verified.
+pub async fn download_to_mmap(
+    client: Arc<dyn DownloadClient>,
+    args: DownloadArgs,
+    path: &Path,
+) -> Result<Mmap> {
+    if args.is_partial()
+        || args.compress.is_some()
+        || args.text_normalize.is_some()
+        || args.split_size.is_some()
+    {
+        return Err(S3FcpError::DownloadFailed(
+            "ranges, compression, text normalization and split output can't be downloaded into a mapping".to_string(),
+        ));
+    }
+
+    let client = match &args.rate_limit {
+        Some(limiter) => limiter.client(client),
+        None => client,
+    };
+    let metadata = probe_within(client.as_ref(), args.head_timeout).await?;
+    check_expected_size(&metadata, args.expected_size)?;
+    let content_length = metadata.content_length;
+
+    let file = tokio::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .await?;
+    file.set_len(content_length).await?;
+    let file = file.into_std().await;
+    // SAFETY: the file was just created with the object's size, and the returned mapping
+    // documents that it must not be modified by others while it is in use
+    let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+
+    let progress = progress_tracker(content_length, &args);
+
+    // Handle edge case: empty file, which no chunk size applies to
+    if content_length == 0 {
+        progress.finish();
+        return Ok(mmap.make_read_only()?);
+    }
+
+    if metadata.supports_range && !args.single_stream {
+        let chunk_size = args
+            .chunk_size_for(content_length)
+            .map_err(S3FcpError::InvalidRange)?;
+        let chunk_size = if args.auto_chunk {
+            auto_chunk_size(content_length, chunk_size, args.concurrency)
+        } else {
+            chunk_size
+        };
+        let chunks = create_chunks(content_length, chunk_size);
+        if args.progress_detail {
+            progress.show_chunk_detail(chunks.len());
+        }
+
+        // Chunks are consecutive and `chunk_size` long, so each gets its own slice
+        let targets = chunks.into_iter().zip(mmap.chunks_mut(chunk_size));
+        futures::stream::iter(targets.map(Ok::<_, S3FcpError>))
+            .try_for_each_concurrent(args.concurrency.max(1), |(chunk, target)| {
+                let (client, progress) = (&client, &progress);
+                let (retry_deadline, listener) = (args.retry_deadline, args.listener.as_deref());
+                async move {
+                    progress.chunk_started();
+                    let data =
+                        fetch_range(client.as_ref(), &chunk, retry_deadline, listener).await?;
+                    progress.chunk_finished();
+                    target.copy_from_slice(&data);
+                    progress.increment(data.len() as u64);
+                    Ok(())
+                }
+            })
+            .await?;
+    } else {
+        let data = client.get_full().await?;
+        if data.len() as u64 != content_length {
+            return Err(S3FcpError::DownloadFailed(format!(
+                "Expected {} bytes, got {}",
+                content_length,
+                data.len()
+            )));
+        }
+        mmap.copy_from_slice(&data);
+        progress.increment(data.len() as u64);
+    }
+    progress.finish();
+
+    if args.fsync {
+        mmap.flush()?;
+    }
+    Ok(mmap.make_read_only()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockClient;
+
+    fn content() -> Vec<u8> {
+        (0..1000).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn test_download_to_mmap_places_chunks() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("object");
+        let client = Arc::new(MockClient::new(content()));
+        let args = DownloadArgs::builder()
+            .concurrency(4)
+            .chunk_size(64)
+            .quiet(true)
+            .build();
+
+        let mmap = download_to_mmap(client.clone(), args, &path).await.unwrap();
+
+        assert_eq!(&mmap[..], &content()[..]);
+        assert_eq!(std::fs::read(&path).unwrap(), content());
+        assert_eq!(client.range_requests().len(), 16);
+    }
+
+    #[tokio::test]
+    async fn test_download_to_mmap_without_range_support() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("object");
+        let client = Arc::new(MockClient::new(content()).without_range_support());
+        let args = DownloadArgs::builder().quiet(true).build();
+
+        let mmap = download_to_mmap(client.clone(), args, &path).await.unwrap();
+
+        assert_eq!(&mmap[..], &content()[..]);
+        assert_eq!(client.full_requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_download_to_mmap_empty_object() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("empty");
+        let client = Arc::new(MockClient::new(Vec::new()));
+        let args = DownloadArgs::builder().quiet(true).build();
+
+        let mmap = download_to_mmap(client.clone(), args, &path).await.unwrap();
+
+        assert!(mmap.is_empty());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+
+        let args = DownloadArgs::builder()
+            .quiet(true)
+            .chunk_percent(5.0)
+            .build();
+        let mmap = download_to_mmap(client.clone(), args, &path).await.unwrap();
+
+        assert!(mmap.is_empty());
+        assert!(client.range_requests().is_empty());
+        assert_eq!(client.full_requests(), 0);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::error::Result;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Whether an object last modified at `last_modified` is newer than the file at `path`
+/// A missing file or an unknown time on either side counts as newer, so it is downloaded
+pub async fn is_newer(path: &Path, last_modified: Option<SystemTime>) -> bool {
+    let Some(last_modified) = last_modified else {
+        return true;
+    };
+    match tokio::fs::metadata(path).await.and_then(|m| m.modified()) {
+        Ok(local) => last_modified > local,
+        Err(_) => true,
+    }
+}
+
+/// Set the modification time of the file at `path`
+pub async fn set_modified(path: &Path, time: SystemTime) -> Result<()> {
+    let file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .await?
+        .into_std()
+        .await;
+    file.set_modified(time)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_is_newer_compares_with_local_mtime() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("file");
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        assert!(is_newer(&path, Some(time)).await);
+
+        std::fs::write(&path, b"data").unwrap();
+        set_modified(&path, time).await.unwrap();
+        assert!(!is_newer(&path, Some(time)).await);
+        assert!(!is_newer(&path, Some(time - Duration::from_secs(1))).await);
+        assert!(is_newer(&path, Some(time + Duration::from_secs(1))).await);
+        assert!(is_newer(&path, None).await);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::error::Result;
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Where the request of `s3fcp ping` was answered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    /// Scheme, host and port the request went to, after redirects
+    pub url: String,
+    /// Address of the server, when the HTTP client reports it
+    pub address: Option<SocketAddr>,
+}
+
+impl Endpoint {
+    /// Endpoint of a request sent to `url`, dropping its path and query
+    pub fn from_url(url: &str, address: Option<SocketAddr>) -> Self {
+        let url = match reqwest::Url::parse(url) {
+            Ok(url) => url.origin().ascii_serialization(),
+            Err(_) => url.to_string(),
+        };
+        Self { url, address }
+    }
+}
+
+/// Outcome of a successful `s3fcp ping`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PingReport {
+    pub target: String,
+    pub endpoint: Endpoint,
+    /// Time until the response arrived, connection setup and signing included
+    pub latency: Duration,
+}
+
+impl PingReport {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "target": self.target,
+            "endpoint": self.endpoint.url,
+            "address": self.endpoint.address.map(|address| address.to_string()),
+            "latency": self.latency.as_secs_f64(),
+        })
+    }
+}
+
+impl fmt::Display for PingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: reachable in {}ms via {}",
+            self.target,
+            self.latency.as_millis(),
+            self.endpoint.url
+        )?;
+        if let Some(address) = self.endpoint.address {
+            write!(f, " ({})", address)?;
+        }
+        Ok(())
+    }
+}
+
+/// Time `request`, which answers with the endpoint it reached
+pub async fn ping<F>(target: &str, request: F) -> Result<PingReport>
+where
+    F: Future<Output = Result<Endpoint>>,
+{
+    let started = Instant::now();
+    let endpoint = request.await?;
+    Ok(PingReport {
+        target: target.to_string(),
+        endpoint,
+        latency: started.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::S3FcpError;
+
+    #[tokio::test]
+    async fn test_ping_report() {
+        let endpoint = Endpoint::from_url(
+            "https://bucket.s3.eu-west-1.amazonaws.com/key?x-id=HeadObject",
+            Some("52.218.1.2:443".parse().unwrap()),
+        );
+        assert_eq!(endpoint.url, "https://bucket.s3.eu-west-1.amazonaws.com");
+
+        let report = ping("s3://bucket/key", async { Ok(endpoint) })
+            .await
+            .unwrap();
+        let report = PingReport {
+            latency: Duration::from_millis(42),
+            ..report
+        };
+        assert_eq!(
+            report.to_string(),
+            "s3://bucket/key: reachable in 42ms via https://bucket.s3.eu-west-1.amazonaws.com (52.218.1.2:443)"
+        );
+        assert_eq!(report.to_json()["address"], "52.218.1.2:443");
+        assert_eq!(report.to_json()["latency"], 0.042);
+
+        let failed = ping("s3://bucket/key", async {
+            Err(S3FcpError::AccessDenied("HEAD request failed".to_string()))
+        })
+        .await;
+        assert!(matches!(failed, Err(S3FcpError::AccessDenied(_))));
+    }
+}
@@ -1,37 +1,362 @@
-use indicatif::{ProgressBar, ProgressStyle};
-use std::sync::Arc;
+use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// Default time before the bar appears, so near-instant transfers don't flicker
+pub const DEFAULT_PROGRESS_DELAY: Duration = Duration::from_millis(200);
+
+const TEMPLATE: &str = "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})";
+
+/// `TEMPLATE` without the estimated time remaining
+const NO_ETA_TEMPLATE: &str =
+    "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec})";
+
+/// Layout of the progress bar
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BarTemplate {
+    /// Elapsed time, bar, bytes, rate and ETA
+    #[default]
+    Default,
+    /// `Default` without the ETA
+    NoEta,
+    /// An indicatif template, checked with `validate_template`
+    /// `{msg}` holds the chunk counters of `--progress-detail`
+    Custom(String),
+}
+
+impl BarTemplate {
+    /// The indicatif template; the built-in ones get the chunk counters appended with `detail`
+    fn render(&self, detail: bool) -> String {
+        let base = match self {
+            Self::Default => TEMPLATE,
+            Self::NoEta => NO_ETA_TEMPLATE,
+            Self::Custom(template) => return template.clone(),
+        };
+        if detail {
+            format!("{} {{msg}}", base)
+        } else {
+            base.to_string()
+        }
+    }
+}
+
+/// Check that `template` is a valid indicatif progress bar template
+pub fn validate_template(template: &str) -> Result<(), String> {
+    ProgressStyle::default_bar()
+        .template(template)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid progress template: {}", e))
+}
 
 pub struct ProgressTracker {
     bar: Option<ProgressBar>,
+    /// Reveals the hidden bar once the delay has passed
+    reveal: Option<JoinHandle<()>>,
+    /// Prints a summary on SIGUSR1, also in quiet mode
+    report: Option<JoinHandle<()>>,
+    total_bytes: u64,
+    done_bytes: AtomicU64,
+    total_chunks: AtomicUsize,
+    done_chunks: AtomicUsize,
+    active_chunks: AtomicUsize,
+    /// Whether the chunk counters are shown on the bar
+    detail: AtomicBool,
+    template: Mutex<BarTemplate>,
+    /// Whether the finished bar stays on screen instead of being cleared
+    keep: AtomicBool,
+    /// Whether `finish` prints a completion line
+    summarize: AtomicBool,
+    started: Instant,
 }
 
 impl ProgressTracker {
-    pub fn new(total_bytes: u64, quiet: bool) -> Arc<Self> {
-        let bar = if quiet {
-            None
-        } else {
-            let pb = ProgressBar::new(total_bytes);
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-                    .expect("Invalid progress bar template")
-                    .progress_chars("#>-"),
-            );
-            Some(pb)
+    /// Create a tracker whose bar is drawn only after `delay`
+    /// A zero delay draws the bar immediately
+    pub fn new(total_bytes: u64, quiet: bool, delay: Duration) -> Arc<Self> {
+        let bar = (!quiet).then(|| {
+            let pb = ProgressBar::with_draw_target(Some(total_bytes), ProgressDrawTarget::hidden());
+            pb.set_style(style(&BarTemplate::Default.render(false)));
+            pb
+        });
+
+        let reveal = match &bar {
+            Some(pb) if delay.is_zero() => {
+                show(pb);
+                None
+            }
+            Some(pb) => {
+                let pb = pb.clone();
+                Some(tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    show(&pb);
+                }))
+            }
+            None => None,
         };
 
-        Arc::new(Self { bar })
+        Arc::new_cyclic(|tracker| Self {
+            bar,
+            reveal,
+            report: report_on_signal(tracker.clone()),
+            total_bytes,
+            done_bytes: AtomicU64::new(0),
+            total_chunks: AtomicUsize::new(0),
+            done_chunks: AtomicUsize::new(0),
+            active_chunks: AtomicUsize::new(0),
+            detail: AtomicBool::new(false),
+            template: Mutex::new(BarTemplate::Default),
+            keep: AtomicBool::new(false),
+            summarize: AtomicBool::new(false),
+            started: Instant::now(),
+        })
     }
 
     pub fn increment(&self, bytes: u64) {
+        self.done_bytes.fetch_add(bytes, Ordering::Relaxed);
         if let Some(ref bar) = self.bar {
             bar.inc(bytes);
         }
     }
 
+    /// Bytes transferred so far
+    pub fn bytes_done(&self) -> u64 {
+        self.done_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Chunks completed so far
+    pub fn chunks_done(&self) -> usize {
+        self.done_chunks.load(Ordering::Relaxed)
+    }
+
+    /// Show completed and in-flight chunk counts on the bar, e.g. `3/10 chunks, 4 active`
+    pub fn show_chunk_detail(&self, total_chunks: usize) {
+        self.total_chunks.store(total_chunks, Ordering::Relaxed);
+        self.detail.store(true, Ordering::Relaxed);
+        self.restyle();
+        self.update_detail();
+    }
+
+    /// Draw the bar with `template` instead of the default layout
+    pub fn use_template(&self, template: BarTemplate) {
+        *self.template.lock().unwrap() = template;
+        self.restyle();
+    }
+
+    fn restyle(&self) {
+        if let Some(ref bar) = self.bar {
+            let template = self.template.lock().unwrap();
+            bar.set_style(style(&template.render(self.detail.load(Ordering::Relaxed))));
+        }
+    }
+
+    /// Leave the completed bar on screen when the transfer finishes
+    pub fn keep_on_finish(&self) {
+        self.keep.store(true, Ordering::Relaxed);
+    }
+
+    /// Print `completion` to stderr when the transfer finishes, for `--summary-only`
+    pub fn summarize_on_finish(&self) {
+        self.summarize.store(true, Ordering::Relaxed);
+    }
+
+    /// A worker started downloading a chunk
+    pub fn chunk_started(&self) {
+        self.active_chunks.fetch_add(1, Ordering::Relaxed);
+        self.update_detail();
+    }
+
+    /// A worker finished downloading a chunk
+    pub fn chunk_finished(&self) {
+        self.active_chunks.fetch_sub(1, Ordering::Relaxed);
+        self.done_chunks.fetch_add(1, Ordering::Relaxed);
+        self.update_detail();
+    }
+
+    /// Completed/total chunks and in-flight chunks
+    pub fn chunk_detail(&self) -> String {
+        format!(
+            "{}/{} chunks, {} active",
+            self.done_chunks.load(Ordering::Relaxed),
+            self.total_chunks.load(Ordering::Relaxed),
+            self.active_chunks.load(Ordering::Relaxed)
+        )
+    }
+
+    fn update_detail(&self) {
+        if let Some(ref bar) = self.bar {
+            if self.detail.load(Ordering::Relaxed) {
+                bar.set_message(self.chunk_detail());
+            }
+        }
+    }
+
+    /// Stop the bar and clear its line, so later output starts on a clean line
+    /// With `keep_on_finish` the completed bar stays on screen, followed by a newline
     pub fn finish(&self) {
+        if let Some(reveal) = &self.reveal {
+            reveal.abort();
+        }
         if let Some(ref bar) = self.bar {
-            bar.finish_with_message("Download complete");
+            if self.keep.load(Ordering::Relaxed) {
+                bar.finish();
+            } else {
+                bar.finish_and_clear();
+            }
         }
+        if self.summarize.load(Ordering::Relaxed) {
+            eprintln!("{}", self.completion());
+        }
+    }
+
+    /// One-line record of a finished transfer such as `Downloaded 100.00 MiB in 25.0s (4.00 MiB/s)`
+    pub fn completion(&self) -> String {
+        let done = self.done_bytes.load(Ordering::Relaxed);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            done as f64 / elapsed
+        } else {
+            0.0
+        };
+        format!(
+            "Downloaded {} in {:.1}s ({}/s)",
+            HumanBytes(done),
+            elapsed,
+            HumanBytes(rate as u64)
+        )
+    }
+
+    /// One-line snapshot such as `12.00 MiB / 100.00 MiB (12%), 4.00 MiB/s, ETA 22 seconds`
+    pub fn summary(&self) -> String {
+        let done = self.done_bytes.load(Ordering::Relaxed);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            done as f64 / elapsed
+        } else {
+            0.0
+        };
+        let percent = (done * 100).checked_div(self.total_bytes).unwrap_or(100);
+        let eta = if rate > 0.0 {
+            let remaining = self.total_bytes.saturating_sub(done) as f64 / rate;
+            HumanDuration(Duration::from_secs_f64(remaining)).to_string()
+        } else {
+            "unknown".to_string()
+        };
+
+        format!(
+            "{} / {} ({}%), {}/s, ETA {}",
+            HumanBytes(done),
+            HumanBytes(self.total_bytes),
+            percent,
+            HumanBytes(rate as u64),
+            eta
+        )
+    }
+}
+
+impl Drop for ProgressTracker {
+    fn drop(&mut self) {
+        for task in [&self.reveal, &self.report].into_iter().flatten() {
+            task.abort();
+        }
+    }
+}
+
+/// Style drawing `template`, or the default layout if the template is invalid
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::default_bar()
+        .template(template)
+        .or_else(|_| ProgressStyle::default_bar().template(TEMPLATE))
+        .expect("Invalid progress bar template")
+        .progress_chars("#>-")
+}
+
+/// Start drawing to stderr, ticking at once so a slow first chunk still shows the bar
+fn show(bar: &ProgressBar) {
+    if !bar.is_finished() {
+        bar.set_draw_target(ProgressDrawTarget::stderr());
+        bar.tick();
+    }
+}
+
+/// Print the tracker's summary to stderr whenever the process receives SIGUSR1,
+/// like `dd` does on SIGINFO
+#[cfg(unix)]
+fn report_on_signal(tracker: Weak<ProgressTracker>) -> Option<JoinHandle<()>> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signals = signal(SignalKind::user_defined1()).ok()?;
+    Some(tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            let Some(tracker) = tracker.upgrade() else {
+                break;
+            };
+            let summary = format!("s3fcp: {}", tracker.summary());
+            match &tracker.bar {
+                Some(bar) if !bar.is_hidden() => bar.println(summary),
+                _ => eprintln!("{}", summary),
+            }
+        }
+    }))
+}
+
+#[cfg(not(unix))]
+fn report_on_signal(_tracker: Weak<ProgressTracker>) -> Option<JoinHandle<()>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_summary_counts_bytes_in_quiet_mode() {
+        let tracker = ProgressTracker::new(4096, true, Duration::ZERO);
+        tracker.increment(1024);
+
+        let summary = tracker.summary();
+        assert!(
+            summary.starts_with("1.00 KiB / 4.00 KiB (25%)"),
+            "{}",
+            summary
+        );
+    }
+
+    #[tokio::test]
+    async fn test_completion_line() {
+        let tracker = ProgressTracker::new(4096, true, Duration::ZERO);
+        tracker.increment(4096);
+
+        let completion = tracker.completion();
+        assert!(
+            completion.starts_with("Downloaded 4.00 KiB in ") && completion.ends_with("/s)"),
+            "{}",
+            completion
+        );
+    }
+
+    #[test]
+    fn test_bar_templates() {
+        assert!(!BarTemplate::NoEta.render(true).contains("{eta}"));
+        assert!(BarTemplate::Default.render(true).ends_with(" {msg}"));
+        assert_eq!(
+            BarTemplate::Custom("{bytes}".to_string()).render(true),
+            "{bytes}"
+        );
+
+        assert!(validate_template("{bytes}/{total_bytes} {bytes_per_sec}").is_ok());
+        assert!(validate_template("{bytes:x}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chunk_detail_counts_active_and_completed() {
+        let tracker = ProgressTracker::new(300, true, Duration::ZERO);
+        tracker.show_chunk_detail(3);
+        tracker.chunk_started();
+        tracker.chunk_started();
+        tracker.chunk_finished();
+
+        assert_eq!(tracker.chunk_detail(), "1/3 chunks, 1 active");
     }
 }
@@ -0,0 +1,196 @@
+use crate::error::{Result, S3FcpError};
+use crate::s3_client::{DownloadClient, ObjectMetadata};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures::future::{select, Either};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+
+/// One of the two sources of a `RacingClient`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Primary,
+    Alternate,
+}
+
+/// `DownloadClient` over two sources of the same object, e.g. S3 and its CloudFront
+/// distribution
+///
+/// The first data request is sent to both sources; whichever answers first serves it
+/// and every later request, and the slower request is cancelled. A source that fails
+/// the race loses it. Metadata and multipart parts always come from the primary source,
+/// so the alternate must serve the same bytes.
+pub struct RacingClient {
+    primary: Arc<dyn DownloadClient>,
+    alternate: Arc<dyn DownloadClient>,
+    winner: OnceCell<Source>,
+}
+
+impl RacingClient {
+    pub fn new(primary: Arc<dyn DownloadClient>, alternate: Arc<dyn DownloadClient>) -> Self {
+        Self {
+            primary,
+            alternate,
+            winner: OnceCell::new(),
+        }
+    }
+
+    /// Source chosen by the race, once it is decided
+    pub fn winner(&self) -> Option<Source> {
+        self.winner.get().copied()
+    }
+
+    fn client(&self, source: Source) -> &Arc<dyn DownloadClient> {
+        match source {
+            Source::Primary => &self.primary,
+            Source::Alternate => &self.alternate,
+        }
+    }
+
+    /// Send `request` to the winner, racing both sources if there is none yet
+    /// Requests arriving during the race wait for it and go to the winner
+    async fn request<T, F, Fut>(&self, request: F) -> Result<T>
+    where
+        F: Fn(Arc<dyn DownloadClient>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if let Some(source) = self.winner() {
+            return request(self.client(source).clone()).await;
+        }
+
+        let mut raced = None;
+        let source = *self
+            .winner
+            .get_or_try_init(|| async {
+                let (source, value) = self.race(&request).await?;
+                raced = Some(value);
+                Ok::<_, S3FcpError>(source)
+            })
+            .await?;
+        match raced {
+            Some(value) => Ok(value),
+            None => request(self.client(source).clone()).await,
+        }
+    }
+
+    /// First successful response of the two sources; the error of the primary if both fail
+    async fn race<T, F, Fut>(&self, request: &F) -> Result<(Source, T)>
+    where
+        F: Fn(Arc<dyn DownloadClient>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let primary = pin!(request(self.primary.clone()));
+        let alternate = pin!(request(self.alternate.clone()));
+        match select(primary, alternate).await {
+            Either::Left((Ok(value), _)) => Ok((Source::Primary, value)),
+            Either::Right((Ok(value), _)) => Ok((Source::Alternate, value)),
+            Either::Left((Err(e), alternate)) => match alternate.await {
+                Ok(value) => Ok((Source::Alternate, value)),
+                Err(_) => Err(e),
+            },
+            Either::Right((Err(_), primary)) => Ok((Source::Primary, primary.await?)),
+        }
+    }
+}
+
+#[async_trait]
+impl DownloadClient for RacingClient {
+    async fn head(&self) -> Result<ObjectMetadata> {
+        self.primary.head().await
+    }
+
+    async fn get_range(&self, start: u64, end: u64) -> Result<Bytes> {
+        self.request(|client| async move { client.get_range(start, end).await })
+            .await
+    }
+
+    async fn get_full(&self) -> Result<Bytes> {
+        self.request(|client| async move { client.get_full().await })
+            .await
+    }
+
+    async fn get_range_into(&self, start: u64, end: u64, buffer: &mut BytesMut) -> Result<()> {
+        match self.winner() {
+            Some(source) => self.client(source).get_range_into(start, end, buffer).await,
+            None => {
+                buffer.extend_from_slice(&self.get_range(start, end).await?);
+                Ok(())
+            }
+        }
+    }
+
+    async fn part_size(&self, part_number: u32) -> Result<Option<u64>> {
+        self.primary.part_size(part_number).await
+    }
+
+    async fn get_part(&self, part_number: u32) -> Result<Bytes> {
+        self.primary.get_part(part_number).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::DownloadArgs;
+    use crate::downloader::download;
+    use crate::testing::{Fault, MockClient};
+    use std::time::Duration;
+
+    fn content() -> Vec<u8> {
+        (0..1000).map(|i| (i % 256) as u8).collect()
+    }
+
+    fn args() -> DownloadArgs {
+        DownloadArgs::builder()
+            .concurrency(4)
+            .chunk_size(100)
+            .quiet(true)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_faster_source_serves_remaining_chunks() {
+        let slow = Arc::new(MockClient::new(content()).with_latency(Duration::from_millis(200)));
+        let fast = Arc::new(MockClient::new(content()));
+        let client = Arc::new(RacingClient::new(slow.clone(), fast.clone()));
+
+        let output = download(client.clone(), args(), Vec::new()).await.unwrap();
+
+        assert_eq!(output, content());
+        assert_eq!(client.winner(), Some(Source::Alternate));
+        assert_eq!(fast.range_requests().len(), 10);
+        // Only the raced first chunk reached the slow source
+        assert!(slow.range_requests().len() <= 1);
+    }
+
+    #[tokio::test]
+    async fn test_failing_source_loses_the_race() {
+        let primary = Arc::new(MockClient::new(content()));
+        let alternate = Arc::new(MockClient::new(content()).with_fault(0, Fault::NotFound));
+        let client = Arc::new(RacingClient::new(primary, alternate));
+        // One worker, so the first chunk is the one that is raced
+        let args = DownloadArgs {
+            concurrency: 1,
+            ..args()
+        };
+
+        let output = download(client.clone(), args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, content());
+        assert_eq!(client.winner(), Some(Source::Primary));
+    }
+
+    #[tokio::test]
+    async fn test_race_fails_when_both_sources_fail() {
+        let primary = Arc::new(MockClient::new(content()).with_fault(0, Fault::NotFound));
+        let alternate = Arc::new(MockClient::new(content()).with_fault(0, Fault::NotFound));
+        let client = RacingClient::new(primary, alternate);
+
+        let result = client.get_range(0, 99).await;
+
+        assert!(matches!(result, Err(S3FcpError::NotFound(_))));
+        assert_eq!(client.winner(), None);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::error::Result;
+use crate::s3_client::{DownloadClient, ObjectMetadata};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Limits how often requests start, for `--max-rps`
+///
+/// A token bucket holding a single request: requests are spaced evenly, so S3 sees a
+/// steady rate instead of bursts. One limiter is shared by every worker and, when the
+/// download args are cloned, by every object of a recursive download.
+#[derive(Debug)]
+pub struct RequestRateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RequestRateLimiter {
+    /// `requests_per_second` must be positive, finite and give a representable interval,
+    /// as `--max-rps` values are checked to
+    pub fn new(requests_per_second: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / requests_per_second),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until the next request may start
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot.into()).await;
+    }
+
+    /// Client whose requests wait for this limiter
+    pub fn client(self: &Arc<Self>, client: Arc<dyn DownloadClient>) -> Arc<dyn DownloadClient> {
+        Arc::new(RateLimitedClient {
+            inner: client,
+            limiter: self.clone(),
+        })
+    }
+}
+
+struct RateLimitedClient {
+    inner: Arc<dyn DownloadClient>,
+    limiter: Arc<RequestRateLimiter>,
+}
+
+#[async_trait]
+impl DownloadClient for RateLimitedClient {
+    async fn head(&self) -> Result<ObjectMetadata> {
+        self.limiter.acquire().await;
+        self.inner.head().await
+    }
+
+    async fn get_range(&self, start: u64, end: u64) -> Result<Bytes> {
+        self.limiter.acquire().await;
+        self.inner.get_range(start, end).await
+    }
+
+    async fn get_full(&self) -> Result<Bytes> {
+        self.limiter.acquire().await;
+        self.inner.get_full().await
+    }
+
+    async fn get_range_into(&self, start: u64, end: u64, buffer: &mut BytesMut) -> Result<()> {
+        self.limiter.acquire().await;
+        self.inner.get_range_into(start, end, buffer).await
+    }
+
+    async fn part_size(&self, part_number: u32) -> Result<Option<u64>> {
+        self.limiter.acquire().await;
+        self.inner.part_size(part_number).await
+    }
+
+    async fn get_part(&self, part_number: u32) -> Result<Bytes> {
+        self.limiter.acquire().await;
+        self.inner.get_part(part_number).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::DownloadArgs;
+    use crate::downloader::download;
+    use crate::testing::MockClient;
+
+    #[tokio::test]
+    async fn test_acquire_spaces_requests() {
+        let limiter = RequestRateLimiter::new(50.0);
+        let started = Instant::now();
+        let mut slots = Vec::new();
+        for _ in 0..4 {
+            limiter.acquire().await;
+            slots.push(started.elapsed());
+        }
+
+        // The first request starts at once, each later one 20ms after the previous
+        assert!(slots[0] < Duration::from_millis(20));
+        for pair in slots.windows(2) {
+            assert!(pair[1] - pair[0] >= Duration::from_millis(19));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_respects_max_rps() {
+        let content: Vec<u8> = (0..500).map(|i| (i % 256) as u8).collect();
+        let client = Arc::new(MockClient::new(content.clone()));
+        let args = DownloadArgs::builder()
+            .concurrency(5)
+            .chunk_size(100)
+            .quiet(true)
+            .rate_limit(Arc::new(RequestRateLimiter::new(50.0)))
+            .build();
+
+        let started = Instant::now();
+        let output = download(client, args, Vec::new()).await.unwrap();
+
+        assert_eq!(output, content);
+        // HEAD and five chunks: five 20ms gaps despite five workers
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+}
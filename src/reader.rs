@@ -0,0 +1,73 @@
+use crate::cli::DownloadArgs;
+use crate::downloader::download;
+use crate::error::Result;
+use crate::s3_client::DownloadClient;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, DuplexStream, ReadBuf};
+use tokio::task::JoinHandle;
+
+/// Size of the in-memory pipe between the download pipeline and the reader
+/// Once it is full the ordered writer blocks, which in turn stalls the workers
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// Ordered object bytes exposed as an `AsyncRead`
+/// Reaching EOF reports any error from the underlying download, and dropping the
+/// reader cancels the download
+pub struct DownloadReader {
+    pipe: DuplexStream,
+    handle: Option<JoinHandle<Result<()>>>,
+}
+
+impl AsyncRead for DownloadReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut self.pipe).poll_read(cx, buf))?;
+
+        // The pipe only reports EOF once the pipeline has dropped its writer,
+        // so check how the download finished before reporting EOF
+        if buf.filled().len() == filled && buf.remaining() > 0 {
+            if let Some(handle) = self.handle.as_mut() {
+                let result = ready!(Pin::new(handle).poll(cx));
+                self.handle = None;
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => return Poll::Ready(Err(io::Error::other(e))),
+                    Err(e) => return Poll::Ready(Err(io::Error::other(e))),
+                }
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for DownloadReader {
+    fn drop(&mut self) {
+        if let Some(handle) = &self.handle {
+            handle.abort();
+        }
+    }
+}
+
+/// Run the download pipeline in the background and return a reader over the ordered bytes
+pub fn download_reader(client: Arc<dyn DownloadClient>, args: DownloadArgs) -> DownloadReader {
+    let (writer, pipe) = tokio::io::duplex(PIPE_CAPACITY);
+
+    let handle = tokio::spawn(async move {
+        download(client, args, writer).await?;
+        Ok(())
+    });
+
+    DownloadReader {
+        pipe,
+        handle: Some(handle),
+    }
+}
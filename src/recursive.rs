@@ -0,0 +1,670 @@
+use crate::cli::{DownloadArgs, ErrorFormat, OnError};
+use crate::downloader::download_to_file;
+use crate::error::{Result, S3FcpError};
+use crate::glob::{literal_dir, matches};
+use crate::mtime::{is_newer, set_modified};
+use crate::s3_client::{s3_error, ObjectMetadata, S3Client};
+use crate::segment::remove_segments;
+use crate::template::{OutputTemplate, TemplateFields};
+use crate::verify::ChecksumManifest;
+use aws_sdk_s3::types::Object;
+use aws_sdk_s3::Client;
+use indicatif::HumanBytes;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// File in the output directory holding the continuation token of the listing
+/// page currently being downloaded, removed once the whole prefix is done
+const LISTING_STATE_FILE: &str = ".s3fcp-listing";
+
+/// Map an object key to its path below `output_dir`
+/// Returns `None` for directory markers and keys that would escape `output_dir`
+fn local_path(output_dir: &Path, prefix: &str, key: &str) -> Option<PathBuf> {
+    let relative = key.strip_prefix(prefix)?.trim_start_matches('/');
+    if relative.is_empty() || relative.ends_with('/') {
+        return None;
+    }
+
+    let relative = Path::new(relative);
+    if !relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    Some(output_dir.join(relative))
+}
+
+/// Local paths of the objects of a recursive or glob download
+struct Destinations<'a> {
+    output_dir: &'a Path,
+    bucket: &'a str,
+    prefix: &'a str,
+    template: Option<&'a OutputTemplate>,
+    /// Paths given out so far, as a template can map two keys to the same path
+    taken: HashSet<PathBuf>,
+}
+
+impl<'a> Destinations<'a> {
+    fn new(
+        output_dir: &'a Path,
+        bucket: &'a str,
+        prefix: &'a str,
+        template: Option<&'a OutputTemplate>,
+    ) -> Self {
+        Self {
+            output_dir,
+            bucket,
+            prefix,
+            template,
+            taken: HashSet::new(),
+        }
+    }
+
+    /// Path of `key`: its `local_path`, or what the template gives for it
+    /// Returns `Ok(None)` for objects to skip, and fails if the template gives a path
+    /// outside `output_dir` or one an earlier object already took
+    fn path(&mut self, key: &str, last_modified: Option<SystemTime>) -> Result<Option<PathBuf>> {
+        let Some(template) = self.template else {
+            return Ok(local_path(self.output_dir, self.prefix, key));
+        };
+        if key.ends_with('/') {
+            return Ok(None);
+        }
+
+        let fields = TemplateFields {
+            bucket: self.bucket,
+            key,
+            path: relative_name(self.prefix, key),
+            last_modified,
+        };
+        let path = self.output_dir.join(template.render(&fields)?);
+        if !self.taken.insert(path.clone()) {
+            return Err(S3FcpError::InvalidUri(format!(
+                "an earlier object was already saved as {}",
+                path.display()
+            )));
+        }
+        Ok(Some(path))
+    }
+}
+
+async fn read_listing_state(path: &Path) -> Result<Option<String>> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(token) if !token.trim().is_empty() => Ok(Some(token.trim().to_string())),
+        Ok(_) => Ok(None),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Whether a previous run already downloaded this object completely
+async fn is_complete(path: &Path, size: u64) -> bool {
+    tokio::fs::metadata(path)
+        .await
+        .is_ok_and(|metadata| metadata.len() == size)
+}
+
+/// Options that only apply to recursive downloads
+#[derive(Default, Clone, bon::Builder)]
+pub struct PrefixOptions {
+    /// Continue from the saved listing position, skipping complete files
+    #[builder(default)]
+    pub resume_listing: bool,
+    /// Send a HEAD request per object instead of planning it from the listed size and ETag
+    #[builder(default)]
+    pub head_each_object: bool,
+    /// Verify downloaded files against these SHA-256 digests
+    pub checksums: Option<ChecksumManifest>,
+    /// Skip objects whose local file is at least as new as their Last-Modified
+    #[builder(default)]
+    pub overwrite_if_newer: bool,
+    /// Set each downloaded file's mtime to its object's Last-Modified
+    #[builder(default)]
+    pub preserve_mtime: bool,
+    /// Print the objects that would be downloaded instead of downloading them
+    #[builder(default)]
+    pub dry_run: bool,
+    /// Whether a failed object stops the download
+    #[builder(default)]
+    pub on_error: OnError,
+    /// Lay out the downloaded files by this template instead of mirroring the keys
+    pub output_template: Option<OutputTemplate>,
+}
+
+/// Download every object below `prefix` into `output_dir`, mirroring the key layout
+///
+/// Objects are downloaded page by page while listing, so memory stays bounded
+/// regardless of the number of objects. The continuation token of the page in
+/// progress is saved in the output directory; with `resume_listing` an
+/// interrupted run continues from that page, skipping objects whose local file
+/// already has the listed size. Each object's chunks are planned from the size and
+/// ETag in the listing, so no HEAD request is sent unless `head_each_object` is set.
+///
+/// With `overwrite_if_newer`, objects are skipped unless their listed Last-Modified is
+/// newer than the local file's mtime. Combined with `preserve_mtime`, repeated runs only
+/// fetch objects that changed in between.
+///
+/// With `checksums`, each file listed in the manifest is hashed while it is written.
+/// A mismatch doesn't stop the download; all mismatches and listed files missing from
+/// the prefix are reported together in a final `IntegrityError`.
+///
+/// With `dry_run`, each object that would be downloaded is printed to stdout with its
+/// size and destination path, followed by the totals. Nothing is written to `output_dir`.
+///
+/// With `output_template`, each object is written where the template puts it instead. An
+/// object the template would place outside `output_dir`, or on the path of an earlier
+/// object, fails.
+///
+/// With `OnError::Continue`, a failed object is reported, its partial file removed, and
+/// the download moves on; the failures are listed at the end in a `DownloadFailed` error.
+pub async fn download_prefix(
+    client: Client,
+    bucket: &str,
+    prefix: &str,
+    output_dir: &Path,
+    args: DownloadArgs,
+    options: PrefixOptions,
+) -> Result<()> {
+    let PrefixOptions {
+        resume_listing,
+        head_each_object,
+        checksums,
+        overwrite_if_newer,
+        preserve_mtime,
+        dry_run,
+        on_error,
+        output_template,
+    } = options;
+    let mut destinations = Destinations::new(output_dir, bucket, prefix, output_template.as_ref());
+    let mut seen = HashSet::new();
+    let mut mismatched = Vec::new();
+    let mut failures = Failures::for_args(&args);
+    let (mut planned_objects, mut planned_bytes) = (0, 0);
+
+    if !dry_run {
+        tokio::fs::create_dir_all(output_dir).await?;
+    }
+    let state_path = output_dir.join(LISTING_STATE_FILE);
+
+    let mut continuation_token = if resume_listing {
+        read_listing_state(&state_path).await?
+    } else {
+        None
+    };
+
+    loop {
+        let response = client
+            .list_objects_v2()
+            .bucket(bucket)
+            .prefix(prefix)
+            .set_continuation_token(continuation_token.clone())
+            .send()
+            .await
+            .map_err(|e| s3_error("ListObjectsV2 failed", e))?;
+
+        for object in response.contents() {
+            let Some(key) = object.key() else { continue };
+            let size = object.size().unwrap_or_default() as u64;
+            let last_modified = object
+                .last_modified()
+                .and_then(|time| SystemTime::try_from(*time).ok());
+
+            let name = relative_name(prefix, key);
+            let path = match destinations.path(key, last_modified) {
+                Ok(Some(path)) => path,
+                Ok(None) => continue,
+                Err(e) => {
+                    failures.record(name, None, Err(e), on_error).await?;
+                    continue;
+                }
+            };
+            if let Some(checksums) = &checksums {
+                if checksums.digest(name).is_none() {
+                    eprintln!("Warning: {} is not in the checksum manifest", name);
+                }
+                seen.insert(name.to_string());
+            }
+
+            if resume_listing && is_complete(&path, size).await {
+                continue;
+            }
+            if overwrite_if_newer && !is_newer(&path, last_modified).await {
+                continue;
+            }
+            if dry_run {
+                println!("{:>12}  {}", size, path.display());
+                planned_objects += 1;
+                planned_bytes += size;
+                continue;
+            }
+
+            let download = async {
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+
+                let mut s3_client =
+                    S3Client::new(client.clone(), bucket.to_string(), key.to_string(), None);
+                if let (false, Some(metadata)) = (head_each_object, listed_metadata(object)) {
+                    s3_client = s3_client.with_metadata(metadata);
+                }
+
+                let mut object_args = args.clone();
+                object_args.sha256 = checksums
+                    .as_ref()
+                    .and_then(|checksums| checksums.digest(name))
+                    .map(str::to_string);
+                download_to_file(Arc::new(s3_client), object_args, &path).await?;
+                if let (true, Some(time)) = (preserve_mtime, last_modified) {
+                    set_modified(&path, time).await?;
+                }
+                Ok(())
+            };
+            match download.await {
+                Err(S3FcpError::IntegrityError(message)) if checksums.is_some() => {
+                    eprintln!("{}: {}", name, message);
+                    mismatched.push(name.to_string());
+                }
+                result => {
+                    let partial = (!args.atomic).then_some(path.as_path());
+                    failures.record(name, partial, result, on_error).await?
+                }
+            }
+        }
+
+        match response.next_continuation_token() {
+            Some(token) => {
+                if !dry_run {
+                    tokio::fs::write(&state_path, token).await?;
+                }
+                continuation_token = Some(token.to_string());
+            }
+            None => break,
+        }
+    }
+
+    if dry_run {
+        println!("{}", plan_summary(planned_objects, planned_bytes));
+        return Ok(());
+    }
+
+    match tokio::fs::remove_file(&state_path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+
+    failures.into_result()?;
+    match checksums {
+        Some(checksums) => checksum_report(&checksums, &seen, mismatched),
+        None => Ok(()),
+    }
+}
+
+/// Objects of a download that failed under `OnError::Continue`
+#[derive(Debug, Default)]
+struct Failures {
+    /// Partial output is split into segments, which are removed along with it
+    split_output: bool,
+    /// How each failure is reported as it happens
+    error_format: ErrorFormat,
+    attempted: usize,
+    /// Each failed object's name with the reason
+    failed: Vec<(String, String)>,
+}
+
+impl Failures {
+    fn for_args(args: &DownloadArgs) -> Self {
+        Self {
+            split_output: args.split_size.is_some(),
+            error_format: args.error_format,
+            ..Self::default()
+        }
+    }
+
+    /// Record the outcome of downloading `name`, whose failure may leave a `partial` file
+    /// With `OnError::Abort` an error is passed on, attributed to `name`; otherwise it is
+    /// reported, the partial file removed, and the download continues
+    async fn record(
+        &mut self,
+        name: &str,
+        partial: Option<&Path>,
+        result: Result<()>,
+        on_error: OnError,
+    ) -> Result<()> {
+        self.attempted += 1;
+        match result {
+            Err(e) if on_error == OnError::Continue => {
+                let reason = e.to_string();
+                e.in_entry(name).report(self.error_format);
+                if let Some(path) = partial {
+                    let _ = tokio::fs::remove_file(path).await;
+                    if self.split_output {
+                        remove_segments(path).await;
+                    }
+                }
+                self.failed.push((name.to_string(), reason));
+                Ok(())
+            }
+            Err(e) => Err(e.in_entry(name)),
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Fail with the list of failed objects, if any
+    fn into_result(self) -> Result<()> {
+        if self.failed.is_empty() {
+            return Ok(());
+        }
+        let mut message = format!("{} of {} objects failed", self.failed.len(), self.attempted);
+        for (name, reason) in &self.failed {
+            message.push_str(&format!("\n  {}: {}", name, reason));
+        }
+        Err(S3FcpError::DownloadFailed(message))
+    }
+}
+
+/// Last line of a dry run's output
+fn plan_summary(objects: u64, bytes: u64) -> String {
+    let noun = if objects == 1 { "object" } else { "objects" };
+    format!(
+        "{} {} would be downloaded, {} ({} bytes) in total",
+        objects,
+        noun,
+        HumanBytes(bytes),
+        bytes
+    )
+}
+
+/// Download every object whose key matches the glob `pattern` into `output_dir`
+///
+/// Unlike `download_prefix`, only keys matching the whole pattern are fetched. Each match
+/// is placed below `output_dir` by its key relative to the pattern's literal directory, so
+/// `logs/*.log` writes `app.log` and `logs/*/app.log` writes `2024/app.log`. Fails with
+/// `NotFound` if no key matches. Failed objects are handled as `on_error` says, and
+/// `output_template` replaces the layout, as in `download_prefix`.
+pub async fn download_glob(
+    client: Client,
+    bucket: &str,
+    pattern: &str,
+    output_dir: &Path,
+    args: DownloadArgs,
+    on_error: OnError,
+    output_template: Option<&OutputTemplate>,
+) -> Result<()> {
+    let dir = literal_dir(pattern);
+    let mut destinations = Destinations::new(output_dir, bucket, dir, output_template);
+    let mut matched = 0;
+    let mut failures = Failures::for_args(&args);
+    let mut continuation_token = None;
+
+    loop {
+        let response = client
+            .list_objects_v2()
+            .bucket(bucket)
+            .prefix(dir)
+            .set_continuation_token(continuation_token.take())
+            .send()
+            .await
+            .map_err(|e| s3_error("ListObjectsV2 failed", e))?;
+
+        for object in response.contents() {
+            let Some(key) = object.key().filter(|key| matches(pattern, key)) else {
+                continue;
+            };
+            let last_modified = object
+                .last_modified()
+                .and_then(|time| SystemTime::try_from(*time).ok());
+            let path = match destinations.path(key, last_modified) {
+                Ok(Some(path)) => path,
+                Ok(None) => continue,
+                Err(e) => {
+                    matched += 1;
+                    failures
+                        .record(relative_name(dir, key), None, Err(e), on_error)
+                        .await?;
+                    continue;
+                }
+            };
+            matched += 1;
+
+            let mut s3_client =
+                S3Client::new(client.clone(), bucket.to_string(), key.to_string(), None);
+            if let Some(metadata) = listed_metadata(object) {
+                s3_client = s3_client.with_metadata(metadata);
+            }
+            let download = async {
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                download_to_file(Arc::new(s3_client), args.clone(), &path).await
+            };
+            let result = download.await;
+            // Atomic downloads never leave a partial file at the output path
+            let partial = (!args.atomic).then_some(path.as_path());
+            failures
+                .record(relative_name(dir, key), partial, result, on_error)
+                .await?;
+        }
+
+        match response.next_continuation_token() {
+            Some(token) => continuation_token = Some(token.to_string()),
+            None => break,
+        }
+    }
+
+    if matched == 0 {
+        return Err(S3FcpError::NotFound(format!(
+            "no keys in bucket {} match {}",
+            bucket, pattern
+        )));
+    }
+    failures.into_result()
+}
+
+/// What a HEAD request would report for a listed object, or `None` if the listing
+/// left out its size
+/// S3 serves every object in ranges, so the size is all chunk planning needs
+fn listed_metadata(object: &Object) -> Option<ObjectMetadata> {
+    Some(ObjectMetadata {
+        content_length: object.size()? as u64,
+        supports_range: true,
+        etag: object.e_tag().map(str::to_string),
+        content_type: None,
+        content_md5: None,
+        last_modified: object
+            .last_modified()
+            .and_then(|time| SystemTime::try_from(*time).ok()),
+    })
+}
+
+/// Key relative to the downloaded prefix, as listed in checksum manifests
+fn relative_name<'a>(prefix: &str, key: &'a str) -> &'a str {
+    key.strip_prefix(prefix)
+        .unwrap_or(key)
+        .trim_start_matches('/')
+}
+
+/// Fail if any file mismatched or a file in the manifest wasn't below the prefix
+fn checksum_report(
+    checksums: &ChecksumManifest,
+    seen: &HashSet<String>,
+    mut mismatched: Vec<String>,
+) -> Result<()> {
+    let mut missing: Vec<_> = checksums
+        .names()
+        .filter(|name| !seen.contains(*name))
+        .collect();
+    if mismatched.is_empty() && missing.is_empty() {
+        return Ok(());
+    }
+
+    mismatched.sort();
+    missing.sort();
+    let mut problems = Vec::new();
+    if !mismatched.is_empty() {
+        problems.push(format!("checksum mismatch for {}", mismatched.join(", ")));
+    }
+    if !missing.is_empty() {
+        problems.push(format!(
+            "not found below the prefix: {}",
+            missing.join(", ")
+        ));
+    }
+    Err(S3FcpError::IntegrityError(problems.join("; ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_path() {
+        let dir = Path::new("/out");
+        assert_eq!(
+            local_path(dir, "logs/", "logs/2024/a.log"),
+            Some(PathBuf::from("/out/2024/a.log"))
+        );
+        assert_eq!(
+            local_path(dir, "logs", "logs/a.log"),
+            Some(PathBuf::from("/out/a.log"))
+        );
+        assert_eq!(
+            local_path(dir, "", "a/b.txt"),
+            Some(PathBuf::from("/out/a/b.txt"))
+        );
+    }
+
+    #[test]
+    fn test_local_path_skips_directory_markers() {
+        assert_eq!(local_path(Path::new("/out"), "logs/", "logs/"), None);
+        assert_eq!(local_path(Path::new("/out"), "logs/", "logs/2024/"), None);
+    }
+
+    #[test]
+    fn test_checksum_report_lists_mismatched_and_missing_files() {
+        let digest = "0".repeat(64);
+        let manifest =
+            ChecksumManifest::parse(&format!("{0}  a.txt\n{0}  b.txt\n{0}  c.txt\n", digest))
+                .unwrap();
+        let seen: HashSet<String> = ["a.txt", "b.txt"].map(String::from).into();
+
+        assert!(checksum_report(&manifest, &seen, Vec::new()).is_err());
+        let all: HashSet<String> = ["a.txt", "b.txt", "c.txt"].map(String::from).into();
+        assert!(checksum_report(&manifest, &all, Vec::new()).is_ok());
+
+        let error = checksum_report(&manifest, &seen, vec!["b.txt".to_string()]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Integrity check failed: checksum mismatch for b.txt; not found below the prefix: c.txt"
+        );
+    }
+
+    #[test]
+    fn test_listed_metadata() {
+        let object = Object::builder()
+            .key("logs/app.log")
+            .size(2048)
+            .e_tag("\"abc\"")
+            .build();
+        let metadata = listed_metadata(&object).unwrap();
+        assert_eq!(metadata.content_length, 2048);
+        assert!(metadata.supports_range);
+        assert_eq!(metadata.etag.as_deref(), Some("\"abc\""));
+
+        assert!(listed_metadata(&Object::builder().key("logs/app.log").build()).is_none());
+    }
+
+    #[test]
+    fn test_plan_summary() {
+        assert_eq!(
+            plan_summary(1, 512),
+            "1 object would be downloaded, 512 B (512 bytes) in total"
+        );
+        assert_eq!(
+            plan_summary(3, 3 * 1024 * 1024),
+            "3 objects would be downloaded, 3.00 MiB (3145728 bytes) in total"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failures_continue_or_abort() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("b.txt");
+        std::fs::write(&path, b"partial").unwrap();
+        let failed = || Err(S3FcpError::NotFound("b.txt".to_string()));
+
+        let mut failures = Failures::default();
+        assert!(failures
+            .record("b.txt", Some(&path), failed(), OnError::Abort)
+            .await
+            .is_err());
+
+        let mut failures = Failures::default();
+        let other = dir.path().join("a.txt");
+        let continue_with = OnError::Continue;
+        failures
+            .record("a.txt", Some(&other), Ok(()), continue_with)
+            .await
+            .unwrap();
+        failures
+            .record("b.txt", Some(&path), failed(), continue_with)
+            .await
+            .unwrap();
+        assert!(!path.exists());
+        assert_eq!(
+            failures.into_result().unwrap_err().to_string(),
+            "Download failed: 1 of 2 objects failed\n  b.txt: Not found: b.txt"
+        );
+        assert!(Failures::default().into_result().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_failures_remove_partial_segments() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("b.bin");
+        for index in 0..2 {
+            std::fs::write(crate::segment::segment_path(&path, index), b"partial").unwrap();
+        }
+        let args = DownloadArgs::builder().split_size(7).build();
+
+        let mut failures = Failures::for_args(&args);
+        let failed = Err(S3FcpError::NotFound("b.bin".to_string()));
+        failures
+            .record("b.bin", Some(&path), failed, OnError::Continue)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_local_path_rejects_traversal() {
+        assert_eq!(local_path(Path::new("/out"), "", "../etc/passwd"), None);
+        assert_eq!(local_path(Path::new("/out"), "a/", "a/../../x"), None);
+        assert_eq!(local_path(Path::new("/out"), "a/", "a/./x"), None);
+    }
+
+    #[test]
+    fn test_destinations_follow_the_template() {
+        let template = OutputTemplate::parse("{basename}").unwrap();
+        let mut destinations =
+            Destinations::new(Path::new("/out"), "bucket", "logs/", Some(&template));
+
+        assert_eq!(
+            destinations.path("logs/a/app.log", None).unwrap(),
+            Some(PathBuf::from("/out/app.log"))
+        );
+        assert_eq!(destinations.path("logs/b/", None).unwrap(), None);
+        // Flattening two keys with the same name onto one path
+        assert!(destinations.path("logs/b/app.log", None).is_err());
+
+        let mut destinations = Destinations::new(Path::new("/out"), "bucket", "logs/", None);
+        assert_eq!(
+            destinations.path("logs/a/app.log", None).unwrap(),
+            Some(PathBuf::from("/out/a/app.log"))
+        );
+    }
+}
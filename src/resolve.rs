@@ -0,0 +1,119 @@
+use aws_smithy_http_client::tls::{self, rustls_provider::CryptoMode};
+use aws_smithy_runtime_api::client::dns::{DnsFuture, ResolveDns, ResolveDnsError};
+use aws_smithy_runtime_api::client::http::SharedHttpClient;
+use std::net::{IpAddr, SocketAddr};
+
+/// curl-style `host:port:addr` override that pins a host name to an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveOverride {
+    pub host: String,
+    pub port: u16,
+    pub addr: IpAddr,
+}
+
+impl ResolveOverride {
+    /// Parse `host:port:addr`; IPv6 addresses may be wrapped in brackets
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut parts = s.trim().splitn(3, ':');
+        let (Some(host), Some(port), Some(addr)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!(
+                "Invalid resolve entry '{}': expected host:port:addr",
+                s
+            ));
+        };
+
+        if host.is_empty() {
+            return Err(format!("Invalid resolve entry '{}': host is empty", s));
+        }
+        let port = port
+            .parse()
+            .map_err(|_| format!("Invalid port in resolve entry '{}'", s))?;
+        let addr = addr
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse()
+            .map_err(|_| format!("Invalid address in resolve entry '{}'", s))?;
+
+        Ok(Self {
+            host: host.to_lowercase(),
+            port,
+            addr,
+        })
+    }
+
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.addr, self.port)
+    }
+}
+
+/// DNS resolver for the AWS SDK that answers overridden hosts itself
+/// and falls back to the system resolver for everything else
+#[derive(Debug, Clone)]
+struct OverrideResolver {
+    overrides: Vec<ResolveOverride>,
+}
+
+impl ResolveDns for OverrideResolver {
+    fn resolve_dns<'a>(&'a self, name: &'a str) -> DnsFuture<'a> {
+        let pinned: Vec<IpAddr> = self
+            .overrides
+            .iter()
+            .filter(|o| o.host.eq_ignore_ascii_case(name))
+            .map(|o| o.addr)
+            .collect();
+
+        DnsFuture::new(async move {
+            if !pinned.is_empty() {
+                return Ok(pinned);
+            }
+            let addrs = tokio::net::lookup_host((name, 0))
+                .await
+                .map_err(ResolveDnsError::new)?;
+            Ok(addrs.map(|addr| addr.ip()).collect())
+        })
+    }
+}
+
+/// HTTPS client for the AWS SDK that honors the given overrides
+/// The SDK connects to the endpoint's own port, so only the address is used
+pub fn sdk_http_client(overrides: &[ResolveOverride]) -> SharedHttpClient {
+    aws_smithy_http_client::Builder::new()
+        .tls_provider(tls::Provider::Rustls(CryptoMode::AwsLc))
+        .build_with_resolver(OverrideResolver {
+            overrides: overrides.to_vec(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resolve_override() {
+        let o = ResolveOverride::parse("Example.com:443:10.0.0.1").unwrap();
+        assert_eq!(o.host, "example.com");
+        assert_eq!(o.socket_addr(), "10.0.0.1:443".parse().unwrap());
+
+        let o = ResolveOverride::parse("example.com:8080:[::1]").unwrap();
+        assert_eq!(o.socket_addr(), "[::1]:8080".parse().unwrap());
+
+        assert!(ResolveOverride::parse("example.com:443").is_err());
+        assert!(ResolveOverride::parse(":443:10.0.0.1").is_err());
+        assert!(ResolveOverride::parse("example.com:https:10.0.0.1").is_err());
+        assert!(ResolveOverride::parse("example.com:443:not-an-ip").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_override_resolver_pins_host() {
+        let resolver = OverrideResolver {
+            overrides: vec![ResolveOverride::parse("bucket.example.com:443:10.1.2.3").unwrap()],
+        };
+
+        let addrs = resolver.resolve_dns("bucket.example.com").await.unwrap();
+        assert_eq!(addrs, vec!["10.1.2.3".parse::<IpAddr>().unwrap()]);
+
+        let addrs = resolver.resolve_dns("localhost").await.unwrap();
+        assert!(!addrs.is_empty());
+    }
+}
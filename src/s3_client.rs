@@ -1,12 +1,157 @@
 use async_trait::async_trait;
+use aws_credential_types::provider::error::CredentialsError;
+use aws_sdk_s3::config::Region;
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::get_object::builders::GetObjectFluentBuilder;
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
 use aws_sdk_s3::Client;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use indicatif::HumanBytes;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
 
 use crate::error::{Result, S3FcpError};
+use crate::head_cache::{HeadCache, ObjectKey};
+use crate::ping::Endpoint;
 
+/// Header S3 sets on redirect and region-mismatch errors to name the bucket's region
+const BUCKET_REGION_HEADER: &str = "x-amz-bucket-region";
+
+/// Largest difference from S3's clock that signed requests tolerate
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(15 * 60);
+
+/// What a `DownloadClient` learned about an object without downloading it
+#[derive(Debug, Clone)]
 pub struct ObjectMetadata {
+    /// Size in bytes; 0 if a source without range support doesn't report it
     pub content_length: u64,
+    /// Whether the source serves byte ranges, so the object can be downloaded in chunks
     pub supports_range: bool,
+    /// Entity tag identifying this version of the object, quotes included
+    pub etag: Option<String>,
+    /// MIME type the source reports for the object
+    pub content_type: Option<String>,
+    /// Base64 MD5 of the whole object from a `Content-MD5` header
+    pub content_md5: Option<String>,
+    /// When the object was last written, if the source reports it
+    pub last_modified: Option<SystemTime>,
+}
+
+/// Multi-line, human-readable summary; unknown values are shown as `-`
+impl fmt::Display for ObjectMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Size:          {} ({} bytes)",
+            HumanBytes(self.content_length),
+            self.content_length
+        )?;
+        writeln!(
+            f,
+            "Range support: {}",
+            if self.supports_range { "yes" } else { "no" }
+        )?;
+        writeln!(
+            f,
+            "Content type:  {}",
+            self.content_type.as_deref().unwrap_or("-")
+        )?;
+        write!(f, "ETag:          {}", self.etag.as_deref().unwrap_or("-"))
+    }
+}
+
+/// Headers and user-defined metadata stored with an S3 object, as returned by HEAD
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoredMetadata {
+    pub content_type: Option<String>,
+    pub content_encoding: Option<String>,
+    pub cache_control: Option<String>,
+    /// `x-amz-meta-*` headers, keyed by the name after the prefix
+    pub user_metadata: BTreeMap<String, String>,
+}
+
+impl From<&HeadObjectOutput> for StoredMetadata {
+    fn from(response: &HeadObjectOutput) -> Self {
+        Self {
+            content_type: response.content_type().map(str::to_string),
+            content_encoding: response.content_encoding().map(str::to_string),
+            cache_control: response.cache_control().map(str::to_string),
+            user_metadata: response
+                .metadata()
+                .map(|metadata| metadata.clone().into_iter().collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl StoredMetadata {
+    /// Structured representation written by `--save-metadata`; missing headers are `null`
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "content_type": self.content_type,
+            "content_encoding": self.content_encoding,
+            "cache_control": self.cache_control,
+            "metadata": self.user_metadata,
+        })
+    }
+}
+
+/// One stored version of an S3 object, as listed by `ListObjectVersions`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectVersion {
+    pub version_id: String,
+    pub size: u64,
+    /// RFC 3339 timestamp of when the version was written
+    pub last_modified: Option<String>,
+    pub is_latest: bool,
+}
+
+impl ObjectVersion {
+    /// Structured representation used by `--list-versions --json`
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "version_id": self.version_id,
+            "size": self.size,
+            "last_modified": self.last_modified,
+            "is_latest": self.is_latest,
+        })
+    }
+}
+
+/// `version_id  size  last_modified`, with ` (latest)` appended for the current version
+impl fmt::Display for ObjectVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}  {}  {}",
+            self.version_id,
+            self.size,
+            self.last_modified.as_deref().unwrap_or("-")
+        )?;
+        if self.is_latest {
+            write!(f, " (latest)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Version ID of the version `ago` steps before the newest one in `versions` (newest first)
+pub fn version_ago(versions: &[ObjectVersion], ago: usize) -> Result<String> {
+    versions
+        .get(ago)
+        .map(|v| v.version_id.clone())
+        .ok_or_else(|| {
+            S3FcpError::NotFound(format!(
+                "Cannot go back {} versions: the object has {} stored versions",
+                ago,
+                versions.len()
+            ))
+        })
 }
 
 #[async_trait]
@@ -14,72 +159,522 @@ pub trait DownloadClient: Send + Sync {
     async fn head(&self) -> Result<ObjectMetadata>;
     async fn get_range(&self, start: u64, end: u64) -> Result<Bytes>;
     async fn get_full(&self) -> Result<Bytes>;
+
+    /// Append an inclusive range to `buffer` as it arrives, so bytes received
+    /// before a mid-transfer failure are kept and only the rest is re-requested
+    async fn get_range_into(&self, start: u64, end: u64, buffer: &mut BytesMut) -> Result<()> {
+        buffer.extend_from_slice(&self.get_range(start, end).await?);
+        Ok(())
+    }
+
+    /// Size of a part of the object's original multipart upload, numbered from 1,
+    /// or `None` if the source can't address parts
+    async fn part_size(&self, _part_number: u32) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Fetch a part of the object's original multipart upload, numbered from 1
+    async fn get_part(&self, part_number: u32) -> Result<Bytes> {
+        Err(S3FcpError::DownloadFailed(format!(
+            "Cannot request part {}: the source doesn't support part requests",
+            part_number
+        )))
+    }
 }
 
 pub struct S3Client {
-    client: Client,
+    /// Replaced with a client for the bucket's actual region after a redirect
+    client: RwLock<Client>,
     bucket: String,
     key: String,
     version_id: Option<String>,
+    head_cache: Option<Arc<HeadCache>>,
+    /// Returned by `head` without a request, e.g. from a listing
+    metadata: Option<ObjectMetadata>,
+    response_overrides: ResponseOverrides,
+}
+
+/// Response headers S3 should return in place of the object's stored metadata,
+/// sent as the `response-content-type` and `response-content-disposition` parameters
+#[derive(Debug, Default, Clone)]
+pub struct ResponseOverrides {
+    pub content_type: Option<String>,
+    pub content_disposition: Option<String>,
+}
+
+/// Decide whether a failed response should be retried in another region
+/// S3 answers requests sent to the wrong region with 301 (PermanentRedirect),
+/// 307 (TemporaryRedirect) or 400 (AuthorizationHeaderMalformed) and names the
+/// bucket's region in the `x-amz-bucket-region` header
+fn redirect_region(
+    status: u16,
+    bucket_region: Option<&str>,
+    current_region: Option<&str>,
+) -> Option<String> {
+    if !matches!(status, 301 | 307 | 400) {
+        return None;
+    }
+    bucket_region
+        .filter(|region| Some(*region) != current_region)
+        .map(str::to_string)
+}
+
+/// Bucket region reported in a failed response, if any
+fn bucket_region<E>(error: &SdkError<E>) -> Option<&str> {
+    error.raw_response()?.headers().get(BUCKET_REGION_HEADER)
+}
+
+/// Whether the request failed because no credentials provider had credentials
+fn credentials_missing(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if let Some(CredentialsError::CredentialsNotLoaded(_)) = error.downcast_ref() {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+/// S3 error code in the XML body of a failed response, e.g. `RequestTimeout`
+fn error_code<E>(error: &SdkError<E>) -> Option<&str> {
+    let body = std::str::from_utf8(error.raw_response()?.body().bytes()?).ok()?;
+    let start = body.find("<Code>")? + "<Code>".len();
+    body[start..].split("</Code>").next()
+}
+
+/// How far the local clock is from the `Date` of a response S3 rejected with 403
+/// HEAD responses have no body to carry the `RequestTimeTooSkewed` code, so the skew
+/// is measured directly
+fn clock_skew<E>(error: &SdkError<E>) -> Option<Duration> {
+    let response = error.raw_response()?;
+    if response.status().as_u16() != 403 {
+        return None;
+    }
+    let date = response.headers().get("date")?;
+    let server_time = DateTime::from_str(date, DateTimeFormat::HttpDate).ok()?;
+    let server_time = SystemTime::try_from(server_time).ok()?;
+    let now = SystemTime::now();
+    let skew = now
+        .duration_since(server_time)
+        .or_else(|_| server_time.duration_since(now))
+        .ok()?;
+    (skew > MAX_CLOCK_SKEW).then_some(skew)
+}
+
+/// `NotFound` naming the version when S3 reports that `version_id` of `key` doesn't exist
+/// HEAD responses have no body to carry the `NoSuchVersion` code, so a 404 for a
+/// versioned request is taken to mean the same
+fn missing_version<E>(
+    error: &SdkError<E>,
+    key: &str,
+    version_id: Option<&str>,
+) -> Option<S3FcpError> {
+    let version_id = version_id?;
+    let missing = match error_code(error) {
+        Some(code) => code == "NoSuchVersion",
+        None => error.raw_response()?.status().as_u16() == 404,
+    };
+    missing
+        .then(|| S3FcpError::NotFound(format!("version {} of {} does not exist", version_id, key)))
+}
+
+/// Build the error for a failed S3 operation, naming the bucket's region if S3 reported it
+pub(crate) fn s3_error<E>(context: &str, error: SdkError<E>) -> S3FcpError
+where
+    E: std::error::Error + 'static,
+{
+    if credentials_missing(&error) {
+        return S3FcpError::NoCredentials;
+    }
+    if let Some(skew) = clock_skew(&error) {
+        return S3FcpError::ClockSkew(format!(
+            "{}: the local clock differs from S3's by {}s",
+            context,
+            skew.as_secs()
+        ));
+    }
+    match error_code(&error) {
+        Some("RequestTimeTooSkewed") => {
+            return S3FcpError::ClockSkew(format!("{}: RequestTimeTooSkewed", context));
+        }
+        // S3 gave up waiting for the request body; a new attempt usually goes through
+        Some("RequestTimeout") => {
+            return S3FcpError::S3Error(format!(
+                "{}: S3 timed out waiting for the request (RequestTimeout)",
+                context
+            ));
+        }
+        _ => {}
+    }
+    // Checked after the clock, whose skew S3 also reports with a 403
+    if let Some(status) = error.raw_response().map(|r| r.status().as_u16()) {
+        // HEAD responses have no body to carry the code, so the status names the error
+        let reason = error_code(&error).unwrap_or(match status {
+            403 => "403 Forbidden",
+            _ => "404 Not Found",
+        });
+        match status {
+            403 => return S3FcpError::AccessDenied(format!("{}: {}", context, reason)),
+            404 => return S3FcpError::NotFound(format!("{}: {}", context, reason)),
+            _ => {}
+        }
+    }
+
+    match bucket_region(&error) {
+        Some(region) => S3FcpError::S3Error(format!(
+            "{}: {} (the bucket is in region {}, try setting AWS_REGION={})",
+            context, error, region, region
+        )),
+        None => S3FcpError::S3Error(format!("{}: {}", context, error)),
+    }
 }
 
 impl S3Client {
     pub fn new(client: Client, bucket: String, key: String, version_id: Option<String>) -> Self {
         Self {
-            client,
+            client: RwLock::new(client),
             bucket,
             key,
             version_id,
+            head_cache: None,
+            metadata: None,
+            response_overrides: ResponseOverrides::default(),
         }
     }
+
+    /// Ask S3 to answer GET requests, including presigned ones, with these headers
+    pub fn with_response_overrides(mut self, overrides: ResponseOverrides) -> Self {
+        self.response_overrides = overrides;
+        self
+    }
+
+    /// Reuse recent HEAD responses for the same object from `cache`
+    pub fn with_head_cache(mut self, cache: Arc<HeadCache>) -> Self {
+        self.head_cache = Some(cache);
+        self
+    }
+
+    /// Plan the download from `metadata` instead of sending a HEAD request, for objects
+    /// whose size and ETag are already known from `ListObjectsV2`
+    pub fn with_metadata(mut self, metadata: ObjectMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    fn object_key(&self) -> ObjectKey {
+        (
+            self.bucket.clone(),
+            self.key.clone(),
+            self.version_id.clone(),
+        )
+    }
+
+    /// Fetch the object's tags as key/value pairs
+    pub async fn tags(&self) -> Result<Vec<(String, String)>> {
+        let response = self
+            .send(|client| {
+                client
+                    .get_object_tagging()
+                    .bucket(&self.bucket)
+                    .key(&self.key)
+                    .set_version_id(self.version_id.clone())
+                    .send()
+            })
+            .await
+            .map_err(|e| self.object_error("GetObjectTagging failed", e))?;
+
+        Ok(response
+            .tag_set()
+            .iter()
+            .map(|tag| (tag.key().to_string(), tag.value().to_string()))
+            .collect())
+    }
+
+    /// Fetch the object's stored headers and user-defined metadata
+    /// Unlike `head`, this always sends a request, since the HEAD cache keeps only what
+    /// downloads need
+    pub async fn stored_metadata(&self) -> Result<StoredMetadata> {
+        let response = self
+            .send(|client| {
+                client
+                    .head_object()
+                    .bucket(&self.bucket)
+                    .key(&self.key)
+                    .set_version_id(self.version_id.clone())
+                    .send()
+            })
+            .await
+            .map_err(|e| self.object_error("HEAD request failed", e))?;
+
+        Ok(StoredMetadata::from(&response))
+    }
+
+    /// List the stored versions of the object, newest first
+    /// Delete markers are skipped since they can't be downloaded
+    pub async fn versions(&self) -> Result<Vec<ObjectVersion>> {
+        let mut versions = Vec::new();
+        let mut key_marker = None;
+        let mut version_id_marker = None;
+
+        loop {
+            let response = self
+                .send(|client| {
+                    client
+                        .list_object_versions()
+                        .bucket(&self.bucket)
+                        .prefix(&self.key)
+                        .set_key_marker(key_marker.clone())
+                        .set_version_id_marker(version_id_marker.clone())
+                        .send()
+                })
+                .await
+                .map_err(|e| s3_error("ListObjectVersions failed", e))?;
+
+            // The prefix also matches longer keys, which are listed after this one
+            versions.extend(
+                response
+                    .versions()
+                    .iter()
+                    .filter(|v| v.key() == Some(self.key.as_str()))
+                    .map(|v| ObjectVersion {
+                        version_id: v.version_id().unwrap_or("null").to_string(),
+                        size: v.size().unwrap_or(0) as u64,
+                        last_modified: v
+                            .last_modified()
+                            .and_then(|t| t.fmt(DateTimeFormat::DateTime).ok()),
+                        is_latest: v.is_latest().unwrap_or(false),
+                    }),
+            );
+
+            if !response.is_truncated().unwrap_or(false)
+                || response.next_key_marker() != Some(self.key.as_str())
+            {
+                break;
+            }
+            key_marker = response.next_key_marker().map(str::to_string);
+            version_id_marker = response.next_version_id_marker().map(str::to_string);
+        }
+
+        Ok(versions)
+    }
+
+    /// Generate a presigned GET URL for the object, valid for `expires_in`
+    pub async fn presign(&self, expires_in: Duration) -> Result<String> {
+        let config = PresigningConfig::expires_in(expires_in)
+            .map_err(|e| S3FcpError::S3Error(format!("Invalid presigning configuration: {}", e)))?;
+
+        let request = self
+            .get_object(&self.client())
+            .presigned(config)
+            .await
+            .map_err(|e| s3_error("Presigning failed", e))?;
+
+        Ok(request.uri().to_string())
+    }
+
+    /// Check that the bucket, or the object if there is a key, can be reached with the
+    /// configured credentials and endpoint, returning the endpoint that answered
+    pub async fn ping(&self) -> Result<Endpoint> {
+        // The request URI is only known once the SDK has resolved the endpoint
+        let uri = Arc::new(Mutex::new(String::new()));
+        if self.key.is_empty() {
+            self.send(|client| {
+                let uri = uri.clone();
+                client
+                    .head_bucket()
+                    .bucket(&self.bucket)
+                    .customize()
+                    .mutate_request(move |request| {
+                        *uri.lock().unwrap() = request.uri().to_string();
+                    })
+                    .send()
+            })
+            .await
+            .map_err(|e| s3_error("HeadBucket request failed", e))?;
+        } else {
+            self.send(|client| {
+                let uri = uri.clone();
+                client
+                    .head_object()
+                    .bucket(&self.bucket)
+                    .key(&self.key)
+                    .set_version_id(self.version_id.clone())
+                    .customize()
+                    .mutate_request(move |request| {
+                        *uri.lock().unwrap() = request.uri().to_string();
+                    })
+                    .send()
+            })
+            .await
+            .map_err(|e| self.object_error("HEAD request failed", e))?;
+        }
+        let uri = uri.lock().unwrap().clone();
+        Ok(Endpoint::from_url(&uri, None))
+    }
+
+    /// GetObject request for this object and version, with the response overrides
+    fn get_object(&self, client: &Client) -> GetObjectFluentBuilder {
+        client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .set_version_id(self.version_id.clone())
+            .set_response_content_type(self.response_overrides.content_type.clone())
+            .set_response_content_disposition(self.response_overrides.content_disposition.clone())
+    }
+
+    fn client(&self) -> Client {
+        self.client.read().unwrap().clone()
+    }
+
+    /// Build the error for a failed request for this object, see `s3_error`
+    fn object_error<E>(&self, context: &str, error: SdkError<E>) -> S3FcpError
+    where
+        E: std::error::Error + 'static,
+    {
+        missing_version(&error, &self.key, self.version_id.as_deref())
+            .unwrap_or_else(|| s3_error(context, error))
+    }
+
+    /// Send a request, retrying once against the bucket's region if S3 reports
+    /// that the bucket lives in a different region than the configured one
+    async fn send<T, E, F, Fut>(&self, request: F) -> std::result::Result<T, SdkError<E>>
+    where
+        F: Fn(Client) -> Fut,
+        Fut: Future<Output = std::result::Result<T, SdkError<E>>>,
+    {
+        let client = self.client();
+        let result = request(client.clone()).await;
+
+        let Err(error) = &result else {
+            return result;
+        };
+        let Some(status) = error.raw_response().map(|r| r.status().as_u16()) else {
+            return result;
+        };
+        let current_region = client.config().region().map(|r| r.as_ref());
+        let Some(region) = redirect_region(status, bucket_region(error), current_region) else {
+            return result;
+        };
+
+        let client = Client::from_conf(
+            client
+                .config()
+                .to_builder()
+                .region(Region::new(region))
+                .build(),
+        );
+        *self.client.write().unwrap() = client.clone();
+
+        request(client).await
+    }
 }
 
 #[async_trait]
 impl DownloadClient for S3Client {
     async fn head(&self) -> Result<ObjectMetadata> {
-        let mut request = self
-            .client
-            .head_object()
-            .bucket(&self.bucket)
-            .key(&self.key);
-
-        if let Some(version) = &self.version_id {
-            request = request.version_id(version);
+        if let Some(metadata) = &self.metadata {
+            return Ok(metadata.clone());
+        }
+        if let Some(metadata) = self
+            .head_cache
+            .as_ref()
+            .and_then(|cache| cache.get(&self.object_key()))
+        {
+            return Ok(metadata);
         }
 
-        let response = request
-            .send()
+        let response = self
+            .send(|client| {
+                client
+                    .head_object()
+                    .bucket(&self.bucket)
+                    .key(&self.key)
+                    .set_version_id(self.version_id.clone())
+                    .send()
+            })
             .await
-            .map_err(|e| S3FcpError::S3Error(format!("HEAD request failed: {}", e)))?;
+            .map_err(|e| self.object_error("HEAD request failed", e))?;
 
         let content_length = response
             .content_length()
             .ok_or_else(|| S3FcpError::S3Error("Content-Length header missing".to_string()))?
             as u64;
 
-        Ok(ObjectMetadata {
+        let metadata = ObjectMetadata {
             content_length,
             supports_range: true, // S3 always supports range requests
-        })
+            etag: response.e_tag().map(str::to_string),
+            content_type: response.content_type().map(str::to_string),
+            content_md5: None,
+            last_modified: response
+                .last_modified()
+                .and_then(|time| SystemTime::try_from(*time).ok()),
+        };
+
+        if let Some(cache) = &self.head_cache {
+            cache.insert(self.object_key(), metadata.clone());
+        }
+
+        Ok(metadata)
     }
 
     async fn get_range(&self, start: u64, end: u64) -> Result<Bytes> {
+        let mut buffer = BytesMut::new();
+        self.get_range_into(start, end, &mut buffer).await?;
+        Ok(buffer.freeze())
+    }
+
+    async fn get_range_into(&self, start: u64, end: u64, buffer: &mut BytesMut) -> Result<()> {
         let range = format!("bytes={}-{}", start, end);
-        let mut request = self
-            .client
-            .get_object()
-            .bucket(&self.bucket)
-            .key(&self.key)
-            .range(range);
+        let mut response = self
+            .send(|client| self.get_object(&client).range(&range).send())
+            .await
+            .map_err(|e| self.object_error("GET request failed", e))?;
 
-        if let Some(version) = &self.version_id {
-            request = request.version_id(version);
+        while let Some(data) = response
+            .body
+            .try_next()
+            .await
+            .map_err(|e| S3FcpError::S3Error(format!("Failed to read response body: {}", e)))?
+        {
+            buffer.extend_from_slice(&data);
         }
 
-        let response = request
-            .send()
+        Ok(())
+    }
+
+    async fn part_size(&self, part_number: u32) -> Result<Option<u64>> {
+        let response = self
+            .send(|client| {
+                client
+                    .head_object()
+                    .bucket(&self.bucket)
+                    .key(&self.key)
+                    .part_number(part_number as i32)
+                    .set_version_id(self.version_id.clone())
+                    .send()
+            })
             .await
-            .map_err(|e| S3FcpError::S3Error(format!("GET request failed: {}", e)))?;
+            .map_err(|e| self.object_error("HEAD request failed", e))?;
+
+        // Objects uploaded in a single request have no parts count
+        if response.parts_count().is_none() {
+            return Ok(None);
+        }
+        Ok(response.content_length().map(|len| len as u64))
+    }
+
+    async fn get_part(&self, part_number: u32) -> Result<Bytes> {
+        let response = self
+            .send(|client| {
+                self.get_object(&client)
+                    .part_number(part_number as i32)
+                    .send()
+            })
+            .await
+            .map_err(|e| self.object_error("GET request failed", e))?;
 
         let data = response
             .body
@@ -92,16 +687,10 @@ impl DownloadClient for S3Client {
     }
 
     async fn get_full(&self) -> Result<Bytes> {
-        let mut request = self.client.get_object().bucket(&self.bucket).key(&self.key);
-
-        if let Some(version) = &self.version_id {
-            request = request.version_id(version);
-        }
-
-        let response = request
-            .send()
+        let response = self
+            .send(|client| self.get_object(&client).send())
             .await
-            .map_err(|e| S3FcpError::S3Error(format!("GET request failed: {}", e)))?;
+            .map_err(|e| self.object_error("GET request failed", e))?;
 
         let data = response
             .body
@@ -113,3 +702,309 @@ impl DownloadClient for S3Client {
         Ok(data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_metadata_display() {
+        let metadata = ObjectMetadata {
+            content_length: 3 * 1024 * 1024,
+            supports_range: true,
+            etag: Some("\"abc\"".to_string()),
+            content_type: None,
+            content_md5: None,
+            last_modified: None,
+        };
+
+        assert_eq!(
+            metadata.to_string(),
+            "Size:          3.00 MiB (3145728 bytes)\n\
+             Range support: yes\n\
+             Content type:  -\n\
+             ETag:          \"abc\""
+        );
+    }
+
+    #[test]
+    fn test_object_version_output() {
+        let version = ObjectVersion {
+            version_id: "3HL4kqtJlcpXroDTDmJ".to_string(),
+            size: 1024,
+            last_modified: Some("2024-05-01T12:00:00Z".to_string()),
+            is_latest: true,
+        };
+
+        assert_eq!(
+            version.to_string(),
+            "3HL4kqtJlcpXroDTDmJ  1024  2024-05-01T12:00:00Z (latest)"
+        );
+        assert_eq!(version.to_json()["size"], 1024);
+        assert_eq!(version.to_json()["is_latest"], true);
+    }
+
+    #[test]
+    fn test_stored_metadata_json() {
+        let response = HeadObjectOutput::builder()
+            .content_type("text/html")
+            .cache_control("max-age=60")
+            .metadata("owner", "web")
+            .build();
+        let json = StoredMetadata::from(&response).to_json();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "content_type": "text/html",
+                "content_encoding": null,
+                "cache_control": "max-age=60",
+                "metadata": {"owner": "web"},
+            })
+        );
+    }
+
+    #[test]
+    fn test_version_ago() {
+        let versions: Vec<_> = ["v3", "v2", "v1"]
+            .into_iter()
+            .map(|id| ObjectVersion {
+                version_id: id.to_string(),
+                size: 1,
+                last_modified: None,
+                is_latest: id == "v3",
+            })
+            .collect();
+
+        assert_eq!(version_ago(&versions, 0).unwrap(), "v3");
+        assert_eq!(version_ago(&versions, 2).unwrap(), "v1");
+        assert!(matches!(
+            version_ago(&versions, 3),
+            Err(S3FcpError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_missing_credentials_are_detected() {
+        let error: SdkError<std::io::Error> = SdkError::construction_failure(
+            CredentialsError::not_loaded("no providers in chain provided credentials"),
+        );
+        assert!(matches!(
+            s3_error("GET request failed", error),
+            S3FcpError::NoCredentials
+        ));
+
+        let error: SdkError<std::io::Error> =
+            SdkError::construction_failure(CredentialsError::provider_error("expired token"));
+        assert!(matches!(
+            s3_error("GET request failed", error),
+            S3FcpError::S3Error(_)
+        ));
+    }
+
+    /// Failed response as the SDK reports it, with the given status, headers and body
+    fn failed_response(
+        status: u16,
+        headers: &[(&'static str, String)],
+        body: &'static str,
+    ) -> SdkError<std::io::Error> {
+        let mut response = aws_smithy_runtime_api::http::Response::new(
+            status.try_into().unwrap(),
+            aws_sdk_s3::primitives::SdkBody::from(body),
+        );
+        for (name, value) in headers {
+            response.headers_mut().insert(*name, value.clone());
+        }
+        SdkError::service_error(std::io::Error::other("service error"), response)
+    }
+
+    #[test]
+    fn test_clock_errors_are_detected() {
+        let body = "<Error><Code>RequestTimeTooSkewed</Code></Error>";
+        let error = s3_error("GET request failed", failed_response(403, &[], body));
+        assert!(matches!(error, S3FcpError::ClockSkew(_)));
+        assert!(!error.is_retryable());
+
+        // HEAD responses have no body, but their Date shows the skew
+        let date = DateTime::from(SystemTime::now() - Duration::from_secs(3600))
+            .fmt(DateTimeFormat::HttpDate)
+            .unwrap();
+        let error = s3_error(
+            "HEAD request failed",
+            failed_response(403, &[("date", date)], ""),
+        );
+        assert!(error.to_string().contains("differs from S3's by 360"));
+
+        let date = DateTime::from(SystemTime::now())
+            .fmt(DateTimeFormat::HttpDate)
+            .unwrap();
+        let error = s3_error(
+            "HEAD request failed",
+            failed_response(403, &[("date", date)], ""),
+        );
+        assert!(matches!(error, S3FcpError::AccessDenied(_)));
+
+        let body = "<Error><Code>RequestTimeout</Code></Error>";
+        let error = s3_error("GET request failed", failed_response(400, &[], body));
+        assert!(error.to_string().contains("RequestTimeout"));
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_missing_objects_and_denied_access_are_detected() {
+        let body = "<Error><Code>NoSuchKey</Code></Error>";
+        let error = s3_error("GET request failed", failed_response(404, &[], body));
+        assert_eq!(
+            error.to_string(),
+            "Not found: GET request failed: NoSuchKey"
+        );
+        assert_eq!(error.exit_code(), 3);
+        assert!(!error.is_retryable());
+
+        let body = "<Error><Code>NoSuchBucket</Code></Error>";
+        let error = s3_error("GET request failed", failed_response(404, &[], body));
+        assert!(matches!(error, S3FcpError::NotFound(_)));
+
+        // HEAD responses carry no code
+        let error = s3_error("HEAD request failed", failed_response(404, &[], ""));
+        assert_eq!(
+            error.to_string(),
+            "Not found: HEAD request failed: 404 Not Found"
+        );
+
+        let body = "<Error><Code>AccessDenied</Code></Error>";
+        let error = s3_error("GET request failed", failed_response(403, &[], body));
+        assert!(matches!(error, S3FcpError::AccessDenied(_)));
+        assert_eq!(error.exit_code(), 4);
+        assert!(!error.is_retryable());
+
+        let error = s3_error("GET request failed", failed_response(500, &[], ""));
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_missing_version_is_detected() {
+        let body = "<Error><Code>NoSuchVersion</Code></Error>";
+        let error = missing_version(&failed_response(404, &[], body), "data.bin", Some("v1"));
+        assert_eq!(
+            error.unwrap().to_string(),
+            "Not found: version v1 of data.bin does not exist"
+        );
+
+        // HEAD responses carry no code
+        let error = failed_response(404, &[], "");
+        assert!(missing_version(&error, "data.bin", Some("v1")).is_some());
+        assert!(missing_version(&error, "data.bin", None).is_none());
+
+        let body = "<Error><Code>AccessDenied</Code></Error>";
+        let error = failed_response(403, &[], body);
+        assert!(missing_version(&error, "data.bin", Some("v1")).is_none());
+    }
+
+    /// Client with static credentials, enough to presign without network access
+    fn offline_client() -> Client {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version_latest()
+            .region(Region::new("us-east-1"))
+            .credentials_provider(aws_sdk_s3::config::Credentials::new(
+                "AKIDEXAMPLE",
+                "secret",
+                None,
+                None,
+                "test",
+            ))
+            .build();
+        Client::from_conf(config)
+    }
+
+    #[tokio::test]
+    async fn test_known_metadata_skips_head_request() {
+        let client = S3Client::new(
+            offline_client(),
+            "bucket".to_string(),
+            "key".to_string(),
+            None,
+        )
+        .with_metadata(ObjectMetadata {
+            content_length: 42,
+            supports_range: true,
+            etag: Some("\"abc\"".to_string()),
+            content_type: None,
+            content_md5: None,
+            last_modified: None,
+        });
+
+        // A real request would be rejected, since the credentials are made up
+        let metadata = client.head().await.unwrap();
+        assert_eq!(metadata.content_length, 42);
+        assert_eq!(metadata.etag.as_deref(), Some("\"abc\""));
+    }
+
+    #[tokio::test]
+    async fn test_presigned_url_encodes_key_exactly() {
+        let client = S3Client::new(
+            offline_client(),
+            "bucket".to_string(),
+            " a b+c#d/ü.txt ".to_string(),
+            None,
+        );
+
+        let url = client.presign(Duration::from_secs(60)).await.unwrap();
+        assert!(url.contains("/%20a%20b%2Bc%23d/%C3%BC.txt%20?"), "{}", url);
+    }
+
+    #[tokio::test]
+    async fn test_presigned_url_carries_response_overrides() {
+        let client = S3Client::new(
+            offline_client(),
+            "bucket".to_string(),
+            "report".to_string(),
+            None,
+        )
+        .with_response_overrides(ResponseOverrides {
+            content_type: Some("application/pdf".to_string()),
+            content_disposition: Some("attachment; filename=\"report.pdf\"".to_string()),
+        });
+
+        let url = client.presign(Duration::from_secs(60)).await.unwrap();
+        assert!(
+            url.contains("response-content-type=application%2Fpdf"),
+            "{}",
+            url
+        );
+        assert!(
+            url.contains(
+                "response-content-disposition=attachment%3B%20filename%3D%22report.pdf%22"
+            ),
+            "{}",
+            url
+        );
+    }
+
+    #[test]
+    fn test_redirect_region_on_region_mismatch() {
+        assert_eq!(
+            redirect_region(301, Some("eu-west-1"), Some("us-east-1")),
+            Some("eu-west-1".to_string())
+        );
+        assert_eq!(
+            redirect_region(400, Some("eu-west-1"), None),
+            Some("eu-west-1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_redirect_region_ignores_other_errors() {
+        // Already talking to the right region
+        assert_eq!(
+            redirect_region(301, Some("us-east-1"), Some("us-east-1")),
+            None
+        );
+        // Not a redirect, or S3 didn't say where the bucket is
+        assert_eq!(
+            redirect_region(403, Some("eu-west-1"), Some("us-east-1")),
+            None
+        );
+        assert_eq!(redirect_region(301, None, Some("us-east-1")), None);
+    }
+}
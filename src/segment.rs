@@ -0,0 +1,196 @@
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::fs::File;
+use tokio::io::AsyncWrite;
+
+type OpenFuture = Pin<Box<dyn Future<Output = io::Result<File>> + Send>>;
+
+/// Path of segment `index` of `base`, e.g. `file.bin.000`
+pub fn segment_path(base: &Path, index: usize) -> PathBuf {
+    let mut path = base.as_os_str().to_owned();
+    path.push(format!(".{:03}", index));
+    PathBuf::from(path)
+}
+
+/// Rename the first `count` segments of `from` to the segments of `to`
+pub async fn rename_segments(from: &Path, to: &Path, count: usize) -> io::Result<()> {
+    for index in 0..count {
+        tokio::fs::rename(segment_path(from, index), segment_path(to, index)).await?;
+    }
+    Ok(())
+}
+
+/// Remove `base.000`, `base.001`, … up to the first segment that can't be removed
+pub async fn remove_segments(base: &Path) {
+    for index in 0.. {
+        if tokio::fs::remove_file(segment_path(base, index))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+/// Writer that splits its output into `base.000`, `base.001`, … of at most
+/// `segment_size` bytes each, so the segments concatenate to the written bytes
+pub struct SegmentingSink {
+    base: PathBuf,
+    segment_size: u64,
+    /// Segment being written and the bytes written to it so far
+    current: Option<(File, u64)>,
+    /// Next segment while it is being created
+    opening: Option<OpenFuture>,
+    segments: Vec<PathBuf>,
+}
+
+impl SegmentingSink {
+    /// Create the first segment; it exists even if nothing is written
+    pub async fn create(base: &Path, segment_size: u64) -> io::Result<Self> {
+        assert!(segment_size > 0, "segment size must be positive");
+
+        let first = segment_path(base, 0);
+        let file = File::create(&first).await?;
+        Ok(Self {
+            base: base.to_path_buf(),
+            segment_size,
+            current: Some((file, 0)),
+            opening: None,
+            segments: vec![first],
+        })
+    }
+
+    /// Paths of the segments created so far, in order
+    pub fn segments(&self) -> &[PathBuf] {
+        &self.segments
+    }
+
+    /// Flush and close the full segment, then start creating the next one
+    fn poll_roll_over(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some((file, _)) = &mut self.current {
+            ready!(Pin::new(file).poll_flush(cx))?;
+            self.current = None;
+        }
+
+        let opening = self.opening.get_or_insert_with(|| {
+            let path = segment_path(&self.base, self.segments.len());
+            Box::pin(File::create(path))
+        });
+        let file = ready!(opening.as_mut().poll(cx))?;
+        self.opening = None;
+        self.segments
+            .push(segment_path(&self.base, self.segments.len()));
+        self.current = Some((file, 0));
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for SegmentingSink {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let full = match &this.current {
+            Some((_, written)) => *written >= this.segment_size,
+            None => true,
+        };
+        if full {
+            ready!(this.poll_roll_over(cx))?;
+        }
+
+        let (file, written) = this.current.as_mut().expect("segment is open");
+        let room = (this.segment_size - *written).min(buf.len() as u64) as usize;
+        let n = ready!(Pin::new(file).poll_write(cx, &buf[..room]))?;
+        *written += n as u64;
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.current {
+            Some((file, _)) => Pin::new(file).poll_flush(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.current {
+            Some((file, _)) => Pin::new(file).poll_shutdown(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_segments_concatenate_to_written_bytes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let base = dir.path().join("out.bin");
+        let content: Vec<u8> = (0..250).map(|i| i as u8).collect();
+
+        let mut sink = SegmentingSink::create(&base, 100).await.unwrap();
+        for part in content.chunks(30) {
+            sink.write_all(part).await.unwrap();
+        }
+        sink.flush().await.unwrap();
+
+        let names: Vec<_> = sink
+            .segments()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["out.bin.000", "out.bin.001", "out.bin.002"]);
+
+        let mut joined = Vec::new();
+        for (path, size) in sink.segments().iter().zip([100, 100, 50]) {
+            let data = std::fs::read(path).unwrap();
+            assert_eq!(data.len(), size);
+            joined.extend(data);
+        }
+        assert_eq!(joined, content);
+    }
+
+    #[tokio::test]
+    async fn test_rename_and_remove_segments() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let temp = dir.path().join(".out.bin.tmp");
+        let base = dir.path().join("out.bin");
+
+        let mut sink = SegmentingSink::create(&temp, 10).await.unwrap();
+        sink.write_all(&[7; 25]).await.unwrap();
+        sink.flush().await.unwrap();
+        rename_segments(&temp, &base, sink.segments().len())
+            .await
+            .unwrap();
+
+        assert!(!segment_path(&temp, 0).exists());
+        assert_eq!(std::fs::read(segment_path(&base, 2)).unwrap(), [7; 5]);
+
+        remove_segments(&base).await;
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_exact_multiple_creates_no_empty_segment() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let base = dir.path().join("out.bin");
+
+        let mut sink = SegmentingSink::create(&base, 10).await.unwrap();
+        sink.write_all(&[7; 20]).await.unwrap();
+        sink.flush().await.unwrap();
+
+        assert_eq!(sink.segments().len(), 2);
+    }
+}
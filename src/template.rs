@@ -0,0 +1,249 @@
+use crate::error::{Result, S3FcpError};
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+/// Field of an `--output-template`, written as `{name}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// The whole key
+    Key,
+    /// The key relative to the downloaded prefix, the default layout
+    Path,
+    /// Last segment of the key
+    Basename,
+    /// `Basename` without its extension
+    Stem,
+    /// Extension of `Basename`, without the dot
+    Ext,
+    Bucket,
+    /// Parts of the Last-Modified date, in UTC
+    Year,
+    Month,
+    Day,
+}
+
+impl Field {
+    const ALL: [(&'static str, Field); 9] = [
+        ("key", Field::Key),
+        ("path", Field::Path),
+        ("basename", Field::Basename),
+        ("stem", Field::Stem),
+        ("ext", Field::Ext),
+        ("bucket", Field::Bucket),
+        ("year", Field::Year),
+        ("month", Field::Month),
+        ("day", Field::Day),
+    ];
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|&(_, field)| field)
+    }
+
+    fn is_date(self) -> bool {
+        matches!(self, Field::Year | Field::Month | Field::Day)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(Field),
+}
+
+/// What an `--output-template` is filled in from for one object
+#[derive(Debug, Clone, Default)]
+pub struct TemplateFields<'a> {
+    pub bucket: &'a str,
+    pub key: &'a str,
+    /// Key relative to the downloaded prefix
+    pub path: &'a str,
+    pub last_modified: Option<SystemTime>,
+}
+
+/// Local path of each downloaded object, relative to the output directory, built from
+/// `{key}`, `{path}`, `{basename}`, `{stem}`, `{ext}`, `{bucket}`, `{year}`, `{month}`
+/// and `{day}`
+///
+/// e.g. `{year}/{month}/{basename}` sorts objects by their Last-Modified month, and
+/// `{basename}` flattens a prefix into one directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputTemplate {
+    parts: Vec<Part>,
+}
+
+impl OutputTemplate {
+    /// Parse a template, rejecting unknown fields, unbalanced braces and templates
+    /// without any field
+    pub fn parse(template: &str) -> std::result::Result<Self, String> {
+        let invalid = |reason: &str| format!("Invalid output template '{}': {}", template, reason);
+
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while !rest.is_empty() {
+            let Some(open) = rest.find(['{', '}']) else {
+                parts.push(Part::Literal(rest.to_string()));
+                break;
+            };
+            if rest[open..].starts_with('}') {
+                return Err(invalid("unmatched '}'"));
+            }
+            if open > 0 {
+                parts.push(Part::Literal(rest[..open].to_string()));
+            }
+            let Some(close) = rest[open..].find('}') else {
+                return Err(invalid("unmatched '{'"));
+            };
+            let name = &rest[open + 1..open + close];
+            let field = Field::parse(name).ok_or_else(|| {
+                let known: Vec<_> = Field::ALL.iter().map(|(name, _)| *name).collect();
+                invalid(&format!(
+                    "unknown field {{{}}}, expected one of {}",
+                    name,
+                    known.join(", ")
+                ))
+            })?;
+            parts.push(Part::Field(field));
+            rest = &rest[open + close + 1..];
+        }
+
+        if !parts.iter().any(|part| matches!(part, Part::Field(_))) {
+            return Err(invalid("every object would get the same path"));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Whether the template needs the object's Last-Modified
+    pub fn uses_date(&self) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, Part::Field(field) if field.is_date()))
+    }
+
+    /// Path of the object relative to the output directory
+    /// Fails if the result is empty or would leave the output directory, e.g. through a
+    /// `..` segment of the key
+    pub fn render(&self, fields: &TemplateFields) -> Result<PathBuf> {
+        let basename = fields.key.rsplit('/').next().unwrap_or_default();
+        let (stem, ext) = match basename.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => (stem, ext),
+            _ => (basename, ""),
+        };
+        let date = match (self.uses_date(), fields.last_modified) {
+            (false, _) => String::new(),
+            (true, Some(time)) => DateTime::from(time)
+                .fmt(DateTimeFormat::DateTime)
+                .map_err(|e| S3FcpError::InvalidUri(format!("{}: {}", fields.key, e)))?,
+            (true, None) => {
+                return Err(S3FcpError::InvalidUri(format!(
+                    "the output template uses the date, but {} has no Last-Modified",
+                    fields.key
+                )))
+            }
+        };
+
+        let mut rendered = String::new();
+        for part in &self.parts {
+            rendered.push_str(match part {
+                Part::Literal(text) => text,
+                Part::Field(Field::Key) => fields.key,
+                Part::Field(Field::Path) => fields.path,
+                Part::Field(Field::Basename) => basename,
+                Part::Field(Field::Stem) => stem,
+                Part::Field(Field::Ext) => ext,
+                Part::Field(Field::Bucket) => fields.bucket,
+                // `DateTime` formats as `YYYY-MM-DDThh:mm:ssZ`
+                Part::Field(Field::Year) => &date[0..4],
+                Part::Field(Field::Month) => &date[5..7],
+                Part::Field(Field::Day) => &date[8..10],
+            });
+        }
+
+        let path = Path::new(&rendered);
+        let below_output = !rendered.ends_with('/')
+            && path.components().next().is_some()
+            && path.components().all(|c| matches!(c, Component::Normal(_)));
+        if !below_output {
+            return Err(S3FcpError::InvalidUri(format!(
+                "the output template gives '{}' for {}, which is not a file below the output directory",
+                rendered, fields.key
+            )));
+        }
+        Ok(path.to_path_buf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn fields(key: &str) -> TemplateFields<'_> {
+        TemplateFields {
+            bucket: "bucket",
+            key,
+            path: key.strip_prefix("logs/").unwrap_or(key),
+            // 2024-03-05T12:00:00Z
+            last_modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_640_000)),
+        }
+    }
+
+    fn render(template: &str, key: &str) -> Result<PathBuf> {
+        OutputTemplate::parse(template)
+            .unwrap()
+            .render(&fields(key))
+    }
+
+    #[test]
+    fn test_render_substitutes_fields() {
+        assert_eq!(
+            render("{bucket}/{path}", "logs/2024/app.log").unwrap(),
+            Path::new("bucket/2024/app.log")
+        );
+        assert_eq!(
+            render("{year}/{month}/{day}/{basename}", "logs/2024/app.log").unwrap(),
+            Path::new("2024/03/05/app.log")
+        );
+        assert_eq!(
+            render("{stem}-{year}{month}{day}.{ext}", "logs/app.tar.gz").unwrap(),
+            Path::new("app.tar-20240305.gz")
+        );
+        assert_eq!(
+            render("{stem}{ext}", "logs/.env").unwrap(),
+            Path::new(".env")
+        );
+        assert_eq!(
+            render("all/{key}", "logs/app.log").unwrap(),
+            Path::new("all/logs/app.log")
+        );
+    }
+
+    #[test]
+    fn test_render_rejects_paths_outside_the_output_dir() {
+        assert!(render("{key}", "../etc/passwd").is_err());
+        assert!(render("{key}", "/etc/passwd").is_err());
+        assert!(render("../{basename}", "logs/app.log").is_err());
+        assert!(render("{path}", "logs/a/../../x").is_err());
+        assert!(render("{key}", "logs/dir/").is_err());
+
+        let undated = TemplateFields {
+            last_modified: None,
+            ..fields("logs/app.log")
+        };
+        let template = OutputTemplate::parse("{year}/{basename}").unwrap();
+        assert!(template.uses_date());
+        assert!(template.render(&undated).is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_templates() {
+        assert!(OutputTemplate::parse("{basename").is_err());
+        assert!(OutputTemplate::parse("basename}").is_err());
+        assert!(OutputTemplate::parse("{name}").is_err());
+        assert!(OutputTemplate::parse("out.bin").is_err());
+        assert!(!OutputTemplate::parse("{basename}").unwrap().uses_date());
+    }
+}
@@ -0,0 +1,100 @@
+use crate::error::{Result, S3FcpError};
+use crate::s3_client::ObjectMetadata;
+
+/// `application/` subtypes that are text, besides those ending in `+json`, `+xml`
+/// or `+yaml`
+const TEXT_APPLICATION_TYPES: [&str; 10] = [
+    "json",
+    "xml",
+    "javascript",
+    "ecmascript",
+    "yaml",
+    "x-yaml",
+    "toml",
+    "x-ndjson",
+    "x-sh",
+    "sql",
+];
+
+/// Whether a `Content-Type` names text, which a terminal can show
+pub fn is_text(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let Some((kind, subtype)) = mime.split_once('/') else {
+        return false;
+    };
+    match kind {
+        "text" => true,
+        "application" => {
+            TEXT_APPLICATION_TYPES.contains(&subtype)
+                || ["+json", "+xml", "+yaml"]
+                    .iter()
+                    .any(|suffix| subtype.ends_with(suffix))
+        }
+        _ => false,
+    }
+}
+
+/// Refuse to dump a binary object on a terminal, as curl does
+///
+/// Output is binary if it is compressed or the object's `Content-Type` isn't text. An
+/// object without a `Content-Type` is let through, since nothing says it is binary.
+pub fn check_terminal_output(metadata: &ObjectMetadata, compressed: bool) -> Result<()> {
+    let binary = match &metadata.content_type {
+        _ if compressed => Some("compressed output"),
+        Some(content_type) if !is_text(content_type) => Some(content_type.as_str()),
+        _ => None,
+    };
+    match binary {
+        Some(what) => Err(S3FcpError::BinaryOutput(format!(
+            "{} would garble the terminal; pass -o <FILE>, pipe the output, or use --force",
+            what
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(content_type: Option<&str>) -> ObjectMetadata {
+        ObjectMetadata {
+            content_length: 100,
+            supports_range: true,
+            etag: None,
+            content_type: content_type.map(str::to_string),
+            content_md5: None,
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn test_is_text() {
+        assert!(is_text("text/plain; charset=utf-8"));
+        assert!(is_text("Text/CSV"));
+        assert!(is_text("application/json"));
+        assert!(is_text("application/vnd.api+json"));
+        assert!(is_text("application/x-ndjson"));
+        assert!(!is_text("application/octet-stream"));
+        assert!(!is_text("binary/octet-stream"));
+        assert!(!is_text("image/png"));
+        assert!(!is_text("application/gzip"));
+        assert!(!is_text("garbage"));
+    }
+
+    #[test]
+    fn test_check_terminal_output() {
+        assert!(check_terminal_output(&metadata(Some("text/plain")), false).is_ok());
+        assert!(check_terminal_output(&metadata(None), false).is_ok());
+
+        let error = check_terminal_output(&metadata(Some("application/zip")), false).unwrap_err();
+        assert!(matches!(error, S3FcpError::BinaryOutput(_)));
+        assert!(error.to_string().contains("application/zip"));
+        assert!(check_terminal_output(&metadata(Some("text/plain")), true).is_err());
+    }
+}
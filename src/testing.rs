@@ -0,0 +1,212 @@
+use crate::error::{Result, S3FcpError};
+use crate::s3_client::{DownloadClient, ObjectMetadata};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Misbehavior injected into one range request
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// Fail without returning any bytes
+    Error,
+    /// Fail with `NotFound`, as if the object was deleted, which isn't retried
+    NotFound,
+    /// Return the first half of the range, then fail as if the connection was reset
+    Reset,
+    /// Succeed with only the first half of the range
+    ShortRead,
+    /// Respond after the given delay
+    Delay(Duration),
+    /// Panic inside the request
+    Panic,
+}
+
+/// In-memory `DownloadClient` for exercising the download pipeline without S3 or an HTTP server
+///
+/// Faults are queued per range start offset and each one affects a single request, so a
+/// retried request that starts at the same offset sees the next queued fault, if any.
+pub struct MockClient {
+    content: Bytes,
+    supports_range: bool,
+    etag: Option<String>,
+    last_modified: Option<SystemTime>,
+    latency: Duration,
+    faults: Mutex<HashMap<u64, VecDeque<Fault>>>,
+    requests: Mutex<Vec<(u64, u64)>>,
+    full_requests: Mutex<usize>,
+}
+
+impl MockClient {
+    pub fn new(content: impl Into<Bytes>) -> Self {
+        Self {
+            content: content.into(),
+            supports_range: true,
+            etag: None,
+            last_modified: None,
+            latency: Duration::ZERO,
+            faults: Mutex::new(HashMap::new()),
+            requests: Mutex::new(Vec::new()),
+            full_requests: Mutex::new(0),
+        }
+    }
+
+    /// Report no range support, so the object can only be fetched with `get_full`
+    pub fn without_range_support(mut self) -> Self {
+        self.supports_range = false;
+        self
+    }
+
+    pub fn with_etag(mut self, etag: impl Into<String>) -> Self {
+        self.etag = Some(etag.into());
+        self
+    }
+
+    pub fn with_last_modified(mut self, last_modified: SystemTime) -> Self {
+        self.last_modified = Some(last_modified);
+        self
+    }
+
+    /// Delay every request by `latency`
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Queue `fault` for the next range request starting at byte `start`
+    pub fn with_fault(self, start: u64, fault: Fault) -> Self {
+        self.faults
+            .lock()
+            .unwrap()
+            .entry(start)
+            .or_default()
+            .push_back(fault);
+        self
+    }
+
+    /// Inclusive ranges requested so far, in request order
+    pub fn range_requests(&self) -> Vec<(u64, u64)> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Number of `get_full` calls so far
+    pub fn full_requests(&self) -> usize {
+        *self.full_requests.lock().unwrap()
+    }
+}
+
+#[async_trait]
+impl DownloadClient for MockClient {
+    async fn head(&self) -> Result<ObjectMetadata> {
+        tokio::time::sleep(self.latency).await;
+        Ok(ObjectMetadata {
+            content_length: self.content.len() as u64,
+            supports_range: self.supports_range,
+            etag: self.etag.clone(),
+            content_type: None,
+            content_md5: None,
+            last_modified: self.last_modified,
+        })
+    }
+
+    async fn get_range(&self, start: u64, end: u64) -> Result<Bytes> {
+        let mut buffer = BytesMut::new();
+        self.get_range_into(start, end, &mut buffer).await?;
+        Ok(buffer.freeze())
+    }
+
+    async fn get_full(&self) -> Result<Bytes> {
+        *self.full_requests.lock().unwrap() += 1;
+        tokio::time::sleep(self.latency).await;
+        Ok(self.content.clone())
+    }
+
+    async fn get_range_into(&self, start: u64, end: u64, buffer: &mut BytesMut) -> Result<()> {
+        self.requests.lock().unwrap().push((start, end));
+        if !self.supports_range {
+            return Err(S3FcpError::DownloadFailed(
+                "range requests are not supported".to_string(),
+            ));
+        }
+        if start > end || end >= self.content.len() as u64 {
+            return Err(S3FcpError::InvalidRange(format!(
+                "bytes={}-{} of a {}-byte object",
+                start,
+                end,
+                self.content.len()
+            )));
+        }
+        tokio::time::sleep(self.latency).await;
+
+        let fault = self
+            .faults
+            .lock()
+            .unwrap()
+            .get_mut(&start)
+            .and_then(VecDeque::pop_front);
+        let (start, end) = (start as usize, end as usize);
+        match fault {
+            None => buffer.extend_from_slice(&self.content[start..=end]),
+            Some(Fault::Error) => {
+                return Err(S3FcpError::DownloadFailed(format!(
+                    "injected failure at byte {}",
+                    start
+                )));
+            }
+            Some(Fault::NotFound) => {
+                return Err(S3FcpError::NotFound(format!(
+                    "injected missing object at byte {}",
+                    start
+                )));
+            }
+            Some(Fault::Reset) => {
+                let half = start + (end - start) / 2;
+                buffer.extend_from_slice(&self.content[start..half]);
+                return Err(S3FcpError::DownloadFailed("connection reset".to_string()));
+            }
+            Some(Fault::ShortRead) => {
+                let half = start + (end - start) / 2;
+                buffer.extend_from_slice(&self.content[start..=half]);
+            }
+            Some(Fault::Delay(delay)) => {
+                tokio::time::sleep(delay).await;
+                buffer.extend_from_slice(&self.content[start..=end]);
+            }
+            Some(Fault::Panic) => panic!("injected panic at byte {}", start),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_faults_apply_once_per_request() {
+        let client = MockClient::new(vec![7u8; 100])
+            .with_fault(0, Fault::ShortRead)
+            .with_fault(0, Fault::Error);
+
+        assert_eq!(client.get_range(0, 99).await.unwrap().len(), 50);
+        assert!(client.get_range(0, 99).await.is_err());
+        assert_eq!(client.get_range(0, 99).await.unwrap().len(), 100);
+        assert_eq!(client.range_requests(), vec![(0, 99); 3]);
+
+        let mut buffer = BytesMut::new();
+        let client = MockClient::new(vec![7u8; 100]).with_fault(10, Fault::Reset);
+        assert!(client.get_range_into(10, 19, &mut buffer).await.is_err());
+        assert_eq!(buffer.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_without_range_support() {
+        let client = MockClient::new(vec![7u8; 100]).without_range_support();
+
+        assert!(!client.head().await.unwrap().supports_range);
+        assert!(client.get_range(0, 9).await.is_err());
+        assert_eq!(client.get_full().await.unwrap().len(), 100);
+        assert_eq!(client.full_requests(), 1);
+    }
+}
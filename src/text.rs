@@ -0,0 +1,156 @@
+use crate::cli::TextNormalize;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Rewrites text as it streams through, holding back the few trailing bytes whose
+/// treatment depends on what follows them
+#[derive(Debug)]
+struct Normalizer {
+    mode: TextNormalize,
+    held: Vec<u8>,
+}
+
+impl Normalizer {
+    /// Append the normalized form of `buf` to `out`
+    fn push(&mut self, buf: &[u8], out: &mut Vec<u8>) {
+        let mut data = std::mem::take(&mut self.held);
+        data.extend_from_slice(buf);
+
+        let keep = match self.mode {
+            // A final `\r` may start a `\r\n` split across writes
+            TextNormalize::Lf => usize::from(data.ends_with(b"\r")),
+            TextNormalize::StripTrailingNewline if data.ends_with(b"\r\n") => 2,
+            TextNormalize::StripTrailingNewline => {
+                usize::from(data.ends_with(b"\n") || data.ends_with(b"\r"))
+            }
+        };
+        let (body, held) = data.split_at(data.len() - keep);
+        self.held = held.to_vec();
+
+        match self.mode {
+            TextNormalize::Lf => {
+                let mut bytes = body.iter().peekable();
+                while let Some(&byte) = bytes.next() {
+                    if byte != b'\r' || bytes.peek() != Some(&&b'\n') {
+                        out.push(byte);
+                    }
+                }
+            }
+            TextNormalize::StripTrailingNewline => out.extend_from_slice(body),
+        }
+    }
+
+    /// Append what is still held back once the text has ended
+    fn finish(&mut self, out: &mut Vec<u8>) {
+        let held = std::mem::take(&mut self.held);
+        let newline = held == b"\n" || held == b"\r\n";
+        if !(newline && self.mode == TextNormalize::StripTrailingNewline) {
+            out.extend_from_slice(&held);
+        }
+    }
+}
+
+/// Writer adapter applying `--text-normalize` to everything written through it
+/// `finish` must be called at the end, as the last bytes are only written then
+pub struct TextWriter<W> {
+    inner: W,
+    normalizer: Normalizer,
+    pending: Vec<u8>,
+    written: usize,
+}
+
+impl<W: AsyncWrite + Unpin> TextWriter<W> {
+    pub fn new(inner: W, mode: TextNormalize) -> Self {
+        Self {
+            inner,
+            normalizer: Normalizer {
+                mode,
+                held: Vec::new(),
+            },
+            pending: Vec::new(),
+            written: 0,
+        }
+    }
+
+    /// Write the held-back end of the text and return the inner writer
+    pub async fn finish(mut self) -> io::Result<W> {
+        self.flush().await?;
+        let mut tail = Vec::new();
+        self.normalizer.finish(&mut tail);
+        self.inner.write_all(&tail).await?;
+        self.inner.flush().await?;
+        Ok(self.inner)
+    }
+
+    /// Write out the normalized bytes of earlier writes
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.pending.len() {
+            let n =
+                ready!(Pin::new(&mut self.inner).poll_write(cx, &self.pending[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += n;
+        }
+        self.pending.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for TextWriter<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_drain(cx))?;
+        let this = &mut *self;
+        this.normalizer.push(buf, &mut this.pending);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_drain(cx))?;
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_drain(cx))?;
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn normalize(mode: TextNormalize, writes: &[&[u8]]) -> Vec<u8> {
+        let mut writer = TextWriter::new(Vec::new(), mode);
+        for buf in writes {
+            writer.write_all(buf).await.unwrap();
+        }
+        writer.finish().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_lf_converts_crlf_across_writes() {
+        let output = normalize(TextNormalize::Lf, &[b"a\r\nb\r", b"\nc\rd\r"]).await;
+        assert_eq!(output, b"a\nb\nc\rd\r");
+    }
+
+    #[tokio::test]
+    async fn test_strip_trailing_newline() {
+        let strip = TextNormalize::StripTrailingNewline;
+        assert_eq!(
+            normalize(strip, &[b"key=1\n", b"key=2\n"]).await,
+            b"key=1\nkey=2"
+        );
+        assert_eq!(normalize(strip, &[b"value\r", b"\n"]).await, b"value");
+        assert_eq!(normalize(strip, &[b"value\n\n"]).await, b"value\n");
+        assert_eq!(normalize(strip, &[b"value\r"]).await, b"value\r");
+        assert_eq!(normalize(strip, &[b"value"]).await, b"value");
+    }
+}
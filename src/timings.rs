@@ -0,0 +1,456 @@
+use crate::error::Result;
+use crate::events::{ChunkEvent, DownloadListener};
+use crate::s3_client::{DownloadClient, ObjectMetadata};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Where the time of a download went, collected for `--timings`
+///
+/// Worker times are measured by giving each worker its own `worker_client`, which
+/// counts the time spent inside requests as active and the rest of its life as idle.
+/// Chunk latencies and retries are collected by the `chunk_listener`.
+#[derive(Debug)]
+pub struct Timings {
+    started: Instant,
+    head: OnceLock<Duration>,
+    transfer_started: OnceLock<Instant>,
+    first_chunk: OnceLock<Instant>,
+    transfer_finished: Mutex<Option<Instant>>,
+    next_worker: AtomicUsize,
+    workers: Mutex<Vec<(usize, WorkerTime)>>,
+    /// First start and latest attempt of each chunk in flight
+    chunks_in_flight: Mutex<HashMap<usize, (Instant, usize)>>,
+    chunks: Mutex<Vec<ChunkTime>>,
+}
+
+/// How long a completed chunk took, from its first request until it was complete,
+/// and how many of its requests were retries
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkTime {
+    pub latency: Duration,
+    pub retries: usize,
+}
+
+/// Distribution of the chunk latencies and retries of a download
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkReport {
+    pub count: usize,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    /// Number of chunks by their number of retries
+    pub retries: BTreeMap<usize, usize>,
+}
+
+impl ChunkReport {
+    /// Summarize `chunks`, or `None` if there are none
+    pub fn new(chunks: &[ChunkTime]) -> Option<Self> {
+        if chunks.is_empty() {
+            return None;
+        }
+        let mut latencies: Vec<_> = chunks.iter().map(|chunk| chunk.latency).collect();
+        latencies.sort();
+        // Nearest-rank percentile
+        let percentile = |p: usize| latencies[(latencies.len() * p).div_ceil(100).max(1) - 1];
+
+        let mut retries = BTreeMap::new();
+        for chunk in chunks {
+            *retries.entry(chunk.retries).or_default() += 1;
+        }
+        Some(Self {
+            count: chunks.len(),
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            retries,
+        })
+    }
+}
+
+/// Time a worker spent in requests and waiting for work or for the output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorkerTime {
+    pub active: Duration,
+    pub idle: Duration,
+}
+
+/// Snapshot of `Timings`; phases that didn't happen are `None`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimingReport {
+    /// Duration of the HEAD request
+    pub head: Option<Duration>,
+    /// From the start of the transfer until the first request completed
+    pub first_chunk: Option<Duration>,
+    /// From the start of the transfer until the last worker finished
+    pub transfer: Option<Duration>,
+    /// Since the timings were created
+    pub total: Duration,
+    /// In the order the workers were started
+    pub workers: Vec<WorkerTime>,
+    /// Only for chunked downloads
+    pub chunks: Option<ChunkReport>,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            head: OnceLock::new(),
+            transfer_started: OnceLock::new(),
+            first_chunk: OnceLock::new(),
+            transfer_finished: Mutex::new(None),
+            next_worker: AtomicUsize::new(0),
+            workers: Mutex::new(Vec::new()),
+            chunks_in_flight: Mutex::new(HashMap::new()),
+            chunks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Record the duration of the object's HEAD request; only the first one counts
+    pub fn record_head(&self, elapsed: Duration) {
+        let _ = self.head.set(elapsed);
+    }
+
+    /// Client for one worker, timing its requests to `client`
+    /// The transfer starts with the first worker client
+    pub fn worker_client(
+        self: &Arc<Self>,
+        client: Arc<dyn DownloadClient>,
+    ) -> Arc<dyn DownloadClient> {
+        let created = Instant::now();
+        self.transfer_started.get_or_init(|| created);
+        Arc::new(TimedClient {
+            inner: client,
+            timings: self.clone(),
+            worker: self.next_worker.fetch_add(1, Ordering::Relaxed),
+            created,
+            active: Mutex::new(Duration::ZERO),
+        })
+    }
+
+    /// Listener recording the latency and retries of each chunk, passing every event
+    /// on to `inner`
+    pub fn chunk_listener(
+        self: &Arc<Self>,
+        inner: Option<Arc<dyn DownloadListener>>,
+    ) -> Arc<dyn DownloadListener> {
+        Arc::new(ChunkListener {
+            timings: self.clone(),
+            inner,
+        })
+    }
+
+    pub fn report(&self) -> TimingReport {
+        let transfer_started = self.transfer_started.get().copied();
+        let since_transfer = |end: Option<Instant>| Some(end? - transfer_started?);
+
+        let mut workers = self.workers.lock().unwrap().clone();
+        workers.sort_by_key(|(worker, _)| *worker);
+
+        TimingReport {
+            head: self.head.get().copied(),
+            first_chunk: since_transfer(self.first_chunk.get().copied()),
+            transfer: since_transfer(*self.transfer_finished.lock().unwrap()),
+            total: self.started.elapsed(),
+            workers: workers.into_iter().map(|(_, time)| time).collect(),
+            chunks: ChunkReport::new(&self.chunks.lock().unwrap()),
+        }
+    }
+
+    fn record_chunk_event(&self, event: &ChunkEvent) {
+        let mut in_flight = self.chunks_in_flight.lock().unwrap();
+        match *event {
+            ChunkEvent::Started { index, attempt } => {
+                in_flight
+                    .entry(index)
+                    .and_modify(|(_, latest)| *latest = attempt)
+                    .or_insert((Instant::now(), attempt));
+            }
+            ChunkEvent::Completed { index, .. } => {
+                if let Some((started, attempts)) = in_flight.remove(&index) {
+                    self.chunks.lock().unwrap().push(ChunkTime {
+                        latency: started.elapsed(),
+                        retries: attempts.saturating_sub(1),
+                    });
+                }
+            }
+            ChunkEvent::Failed { index, .. } => {
+                in_flight.remove(&index);
+            }
+            ChunkEvent::Queued { .. } | ChunkEvent::Retried { .. } => {}
+        }
+    }
+
+    fn record_worker(&self, worker: usize, time: WorkerTime) {
+        *self.transfer_finished.lock().unwrap() = Some(Instant::now());
+        self.workers.lock().unwrap().push((worker, time));
+    }
+}
+
+impl TimingReport {
+    /// Structured representation printed by `--timings json`, with durations in seconds
+    pub fn to_json(&self) -> serde_json::Value {
+        let secs = |d: Option<Duration>| d.map(|d| d.as_secs_f64());
+        let workers: Vec<_> = self
+            .workers
+            .iter()
+            .map(|w| {
+                serde_json::json!({
+                    "active": w.active.as_secs_f64(),
+                    "idle": w.idle.as_secs_f64(),
+                })
+            })
+            .collect();
+        let chunks = self.chunks.as_ref().map(|chunks| {
+            let retries: serde_json::Map<_, _> = chunks
+                .retries
+                .iter()
+                .map(|(retries, count)| (retries.to_string(), (*count).into()))
+                .collect();
+            serde_json::json!({
+                "count": chunks.count,
+                "latency": {
+                    "p50": chunks.p50.as_secs_f64(),
+                    "p90": chunks.p90.as_secs_f64(),
+                    "p99": chunks.p99.as_secs_f64(),
+                },
+                "retries": retries,
+            })
+        });
+        serde_json::json!({
+            "head": secs(self.head),
+            "first_chunk": secs(self.first_chunk),
+            "transfer": secs(self.transfer),
+            "total": self.total.as_secs_f64(),
+            "workers": workers,
+            "chunks": chunks,
+        })
+    }
+}
+
+/// One line per phase and per worker; phases that didn't happen are shown as `-`
+impl fmt::Display for TimingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = |d: Option<Duration>| match d {
+            Some(d) => format!("{:.3}s", d.as_secs_f64()),
+            None => "-".to_string(),
+        };
+        writeln!(f, "HEAD request:  {}", secs(self.head))?;
+        writeln!(f, "First chunk:   {}", secs(self.first_chunk))?;
+        writeln!(f, "Transfer:      {}", secs(self.transfer))?;
+        write!(f, "Total:         {}", secs(Some(self.total)))?;
+        for (worker, time) in self.workers.iter().enumerate() {
+            write!(
+                f,
+                "\n{:<15}active {}, idle {}",
+                format!("Worker {}:", worker),
+                secs(Some(time.active)),
+                secs(Some(time.idle))
+            )?;
+        }
+        if let Some(chunks) = &self.chunks {
+            write!(
+                f,
+                "\nChunk latency: p50 {}, p90 {}, p99 {} ({} chunks)",
+                secs(Some(chunks.p50)),
+                secs(Some(chunks.p90)),
+                secs(Some(chunks.p99)),
+                chunks.count
+            )?;
+            let retries: Vec<_> = chunks
+                .retries
+                .iter()
+                .map(|(retries, count)| format!("{} chunks with {}", count, retries))
+                .collect();
+            write!(f, "\nChunk retries: {}", retries.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Records chunk latencies in `Timings` before passing events on
+struct ChunkListener {
+    timings: Arc<Timings>,
+    inner: Option<Arc<dyn DownloadListener>>,
+}
+
+impl DownloadListener for ChunkListener {
+    fn on_event(&self, event: &ChunkEvent) {
+        self.timings.record_chunk_event(event);
+        if let Some(inner) = &self.inner {
+            inner.on_event(event);
+        }
+    }
+}
+
+/// Client of a single worker that adds the time spent in requests to its active time
+/// and reports the worker's totals when the worker drops it
+struct TimedClient {
+    inner: Arc<dyn DownloadClient>,
+    timings: Arc<Timings>,
+    worker: usize,
+    created: Instant,
+    active: Mutex<Duration>,
+}
+
+impl TimedClient {
+    async fn timed<T>(&self, request: impl Future<Output = Result<T>>) -> Result<T> {
+        let started = Instant::now();
+        let result = request.await;
+        *self.active.lock().unwrap() += started.elapsed();
+        if result.is_ok() {
+            self.timings.first_chunk.get_or_init(Instant::now);
+        }
+        result
+    }
+}
+
+impl Drop for TimedClient {
+    fn drop(&mut self) {
+        let active = *self.active.get_mut().unwrap();
+        let idle = self.created.elapsed().saturating_sub(active);
+        self.timings
+            .record_worker(self.worker, WorkerTime { active, idle });
+    }
+}
+
+#[async_trait]
+impl DownloadClient for TimedClient {
+    async fn head(&self) -> Result<ObjectMetadata> {
+        self.inner.head().await
+    }
+
+    async fn get_range(&self, start: u64, end: u64) -> Result<Bytes> {
+        self.timed(self.inner.get_range(start, end)).await
+    }
+
+    async fn get_full(&self) -> Result<Bytes> {
+        self.timed(self.inner.get_full()).await
+    }
+
+    async fn get_range_into(&self, start: u64, end: u64, buffer: &mut BytesMut) -> Result<()> {
+        self.timed(self.inner.get_range_into(start, end, buffer))
+            .await
+    }
+
+    async fn part_size(&self, part_number: u32) -> Result<Option<u64>> {
+        self.inner.part_size(part_number).await
+    }
+
+    async fn get_part(&self, part_number: u32) -> Result<Bytes> {
+        self.timed(self.inner.get_part(part_number)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::DownloadArgs;
+    use crate::downloader::download;
+    use crate::testing::{Fault, MockClient};
+
+    #[tokio::test]
+    async fn test_timings_cover_head_and_workers() {
+        let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let client = Arc::new(MockClient::new(content).with_latency(Duration::from_millis(20)));
+        let timings = Arc::new(Timings::new());
+        let args = DownloadArgs::builder()
+            .concurrency(2)
+            .chunk_size(100)
+            .quiet(true)
+            .timings(timings.clone())
+            .build();
+
+        download(client, args, Vec::new()).await.unwrap();
+
+        let report = timings.report();
+        assert!(report.head.unwrap() >= Duration::from_millis(20));
+        assert!(report.first_chunk.unwrap() >= Duration::from_millis(20));
+        assert!(report.transfer.unwrap() >= report.first_chunk.unwrap());
+        assert_eq!(report.workers.len(), 2);
+        // Ten chunks of 20ms each, shared by both workers
+        let active: Duration = report.workers.iter().map(|w| w.active).sum();
+        assert!(active >= Duration::from_millis(200));
+        let chunks = report.chunks.unwrap();
+        assert_eq!(chunks.count, 10);
+        assert!(chunks.p50 >= Duration::from_millis(20));
+        assert_eq!(chunks.retries, BTreeMap::from([(0, 10)]));
+    }
+
+    #[tokio::test]
+    async fn test_timings_count_chunk_retries() {
+        let content: Vec<u8> = (0..500).map(|i| (i % 256) as u8).collect();
+        let client = Arc::new(
+            MockClient::new(content)
+                .with_fault(100, Fault::Error)
+                .with_fault(100, Fault::Error)
+                .with_fault(300, Fault::Error),
+        );
+        let timings = Arc::new(Timings::new());
+        let args = DownloadArgs::builder()
+            .chunk_size(100)
+            .quiet(true)
+            .timings(timings.clone())
+            .build();
+
+        download(client, args, Vec::new()).await.unwrap();
+
+        let chunks = timings.report().chunks.unwrap();
+        assert_eq!(chunks.retries, BTreeMap::from([(0, 3), (1, 1), (2, 1)]));
+    }
+
+    #[test]
+    fn test_chunk_report_percentiles() {
+        let chunks: Vec<_> = (1..=100)
+            .map(|ms| ChunkTime {
+                latency: Duration::from_millis(ms),
+                retries: usize::from(ms > 95),
+            })
+            .collect();
+        let report = ChunkReport::new(&chunks).unwrap();
+
+        assert_eq!(report.p50, Duration::from_millis(50));
+        assert_eq!(report.p90, Duration::from_millis(90));
+        assert_eq!(report.p99, Duration::from_millis(99));
+        assert_eq!(report.retries, BTreeMap::from([(0, 95), (1, 5)]));
+        assert!(ChunkReport::new(&[]).is_none());
+    }
+
+    #[test]
+    fn test_timing_report_output() {
+        let report = TimingReport {
+            head: Some(Duration::from_millis(120)),
+            first_chunk: None,
+            transfer: Some(Duration::from_millis(2500)),
+            total: Duration::from_secs(3),
+            workers: vec![WorkerTime {
+                active: Duration::from_millis(2000),
+                idle: Duration::from_millis(500),
+            }],
+            chunks: None,
+        };
+
+        assert_eq!(
+            report.to_string(),
+            "HEAD request:  0.120s\n\
+             First chunk:   -\n\
+             Transfer:      2.500s\n\
+             Total:         3.000s\n\
+             Worker 0:      active 2.000s, idle 0.500s"
+        );
+        assert_eq!(report.to_json()["first_chunk"], serde_json::Value::Null);
+        assert_eq!(report.to_json()["workers"][0]["idle"], 0.5);
+    }
+}
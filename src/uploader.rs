@@ -0,0 +1,317 @@
+use crate::chunk::{create_chunks, Chunk};
+use crate::cli::UploadArgs;
+use crate::downloader::{queue_chunks, retry_policy};
+use crate::error::{Result, S3FcpError};
+use crate::progress::ProgressTracker;
+use crate::s3_client::s3_error;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use aws_sdk_s3::Client;
+use backon::Retryable;
+use bytes::Bytes;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::task::JoinSet;
+
+/// S3 requires every part except the last one to be at least 5 MiB
+const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// S3 allows at most 10,000 parts per multipart upload
+const MAX_PARTS: u64 = 10_000;
+
+/// S3 rejects parts larger than 5 GiB
+const MAX_PART_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+/// S3 rejects single PUTs larger than 5 GiB
+const MAX_PUT_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+pub struct S3Uploader {
+    client: Client,
+    bucket: String,
+    key: String,
+}
+
+impl S3Uploader {
+    pub fn new(client: Client, bucket: String, key: String) -> Self {
+        Self {
+            client,
+            bucket,
+            key,
+        }
+    }
+
+    async fn put_object(&self, path: &Path) -> Result<()> {
+        let body = ByteStream::from_path(path).await.map_err(|e| {
+            S3FcpError::S3Error(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| s3_error("PUT request failed", e))?;
+
+        Ok(())
+    }
+
+    async fn create_multipart_upload(&self) -> Result<String> {
+        let response = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .send()
+            .await
+            .map_err(|e| s3_error("CreateMultipartUpload failed", e))?;
+
+        response
+            .upload_id()
+            .map(str::to_string)
+            .ok_or_else(|| S3FcpError::S3Error("Upload ID missing from response".to_string()))
+    }
+
+    async fn upload_part(&self, upload_id: &str, part_number: i32, data: Bytes) -> Result<String> {
+        let response = self
+            .client
+            .upload_part()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .body(ByteStream::from(data))
+            .send()
+            .await
+            .map_err(|e| s3_error(&format!("UploadPart {} failed", part_number), e))?;
+
+        response
+            .e_tag()
+            .map(str::to_string)
+            .ok_or_else(|| S3FcpError::S3Error(format!("ETag missing for part {}", part_number)))
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        upload_id: &str,
+        parts: Vec<CompletedPart>,
+    ) -> Result<()> {
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(parts))
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|e| s3_error("CompleteMultipartUpload failed", e))?;
+
+        Ok(())
+    }
+
+    async fn abort_multipart_upload(&self, upload_id: &str) -> Result<()> {
+        self.client
+            .abort_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(upload_id)
+            .send()
+            .await
+            .map_err(|e| s3_error("AbortMultipartUpload failed", e))?;
+
+        Ok(())
+    }
+}
+
+/// Pick a part size that satisfies the S3 multipart limits
+/// The requested chunk size is raised to the 5 MiB minimum, and further if the
+/// file would otherwise need more than 10,000 parts, but never beyond 5 GiB
+fn effective_part_size(content_length: u64, chunk_size: usize) -> usize {
+    let min_for_max_parts = content_length.div_ceil(MAX_PARTS) as usize;
+    let part_size = chunk_size.max(MIN_PART_SIZE).max(min_for_max_parts);
+    (part_size as u64).min(MAX_PART_SIZE) as usize
+}
+
+async fn read_part(path: &Path, chunk: &Chunk) -> Result<Bytes> {
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(std::io::SeekFrom::Start(chunk.start)).await?;
+
+    let mut data = vec![0u8; (chunk.end - chunk.start + 1) as usize];
+    file.read_exact(&mut data).await?;
+
+    Ok(Bytes::from(data))
+}
+
+/// Upload worker
+/// Pulls chunks from the queue, reads them from the file and uploads them as parts
+async fn upload_worker(
+    uploader: Arc<S3Uploader>,
+    path: PathBuf,
+    upload_id: Arc<str>,
+    rx: flume::Receiver<Chunk>,
+    progress: Arc<ProgressTracker>,
+) -> Result<Vec<CompletedPart>> {
+    let mut completed = Vec::new();
+
+    while let Ok(chunk) = rx.recv_async().await {
+        let data = read_part(&path, &chunk).await?;
+        let data_len = data.len() as u64;
+        let part_number = chunk.index as i32 + 1;
+
+        let e_tag = (|| async {
+            uploader
+                .upload_part(&upload_id, part_number, data.clone())
+                .await
+        })
+        .retry(retry_policy())
+        .when(S3FcpError::is_retryable)
+        .await?;
+
+        progress.increment(data_len);
+        completed.push(
+            CompletedPart::builder()
+                .e_tag(e_tag)
+                .part_number(part_number)
+                .build(),
+        );
+    }
+
+    Ok(completed)
+}
+
+async fn upload_parts(
+    uploader: Arc<S3Uploader>,
+    path: &Path,
+    upload_id: &str,
+    chunks: Vec<Chunk>,
+    concurrency: usize,
+    progress: Arc<ProgressTracker>,
+) -> Result<Vec<CompletedPart>> {
+    let upload_id: Arc<str> = Arc::from(upload_id);
+    let concurrency = concurrency.max(1);
+    let (chunk_tx, chunk_rx) = flume::bounded(concurrency);
+
+    // The queue and the workers run in one set, so the first failure aborts the rest
+    // instead of letting them upload the remainder of the file
+    let mut tasks = JoinSet::new();
+    tasks.spawn(async move {
+        queue_chunks(chunks, chunk_tx, None, None).await?;
+        Ok(Vec::new())
+    });
+
+    for _ in 0..concurrency {
+        tasks.spawn(upload_worker(
+            uploader.clone(),
+            path.to_path_buf(),
+            upload_id.clone(),
+            chunk_rx.clone(),
+            progress.clone(),
+        ));
+    }
+    drop(chunk_rx);
+
+    let mut parts = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        match result {
+            Ok(Ok(worker_parts)) => parts.extend(worker_parts),
+            Ok(Err(e)) => {
+                tasks.abort_all();
+                return Err(e);
+            }
+            Err(e) => {
+                tasks.abort_all();
+                return Err(e.into());
+            }
+        }
+    }
+
+    parts.sort_by_key(|part| part.part_number());
+    Ok(parts)
+}
+
+/// Upload a local file to S3
+/// Files that fit into a single part are sent with one PUT, larger files use a
+/// multipart upload with concurrent part uploads
+pub async fn upload_file(uploader: Arc<S3Uploader>, path: &Path, args: UploadArgs) -> Result<()> {
+    let content_length = tokio::fs::metadata(path).await?.len();
+    let part_size = effective_part_size(content_length, args.chunk_size);
+
+    let progress = ProgressTracker::new(content_length, args.quiet, args.progress_delay);
+    progress.use_template(args.progress_template.clone());
+    if args.keep_progress {
+        progress.keep_on_finish();
+    }
+
+    if content_length <= part_size as u64 && content_length <= MAX_PUT_SIZE {
+        uploader.put_object(path).await?;
+        progress.increment(content_length);
+        progress.finish();
+        return Ok(());
+    }
+
+    let chunks = create_chunks(content_length, part_size);
+    let upload_id = uploader.create_multipart_upload().await?;
+
+    let result = upload_parts(
+        uploader.clone(),
+        path,
+        &upload_id,
+        chunks,
+        args.concurrency,
+        progress.clone(),
+    )
+    .await;
+
+    let parts = match result {
+        Ok(parts) => parts,
+        Err(e) => {
+            // Best effort: don't leave orphaned parts that keep incurring storage costs
+            let _ = uploader.abort_multipart_upload(&upload_id).await;
+            return Err(e);
+        }
+    };
+
+    uploader
+        .complete_multipart_upload(&upload_id, parts)
+        .await?;
+
+    progress.finish();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_part_size_respects_minimum() {
+        assert_eq!(effective_part_size(100 * 1024 * 1024, 1024), MIN_PART_SIZE);
+        assert_eq!(
+            effective_part_size(100 * 1024 * 1024, 8 * 1024 * 1024),
+            8 * 1024 * 1024
+        );
+    }
+
+    #[test]
+    fn test_effective_part_size_respects_max_parts() {
+        let content_length = 100 * 1024 * 1024 * 1024u64; // 100GiB
+        let part_size = effective_part_size(content_length, 8 * 1024 * 1024);
+        assert!(create_chunks(content_length, part_size).len() as u64 <= MAX_PARTS);
+    }
+
+    #[test]
+    fn test_effective_part_size_respects_maximum() {
+        let six_gib = 6 * 1024 * 1024 * 1024u64;
+        let part_size = effective_part_size(six_gib, six_gib as usize);
+        assert_eq!(part_size as u64, MAX_PART_SIZE);
+        // A 6 GiB file then needs two parts instead of one oversized PUT
+        assert_eq!(create_chunks(six_gib, part_size).len(), 2);
+    }
+}
@@ -4,6 +4,8 @@ use crate::error::{Result, S3FcpError};
 pub struct S3Uri {
     pub bucket: String,
     pub key: String,
+    /// Region of the bucket, from a `?region=` suffix
+    pub region: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -12,54 +14,195 @@ pub struct HttpUri {
 }
 
 impl HttpUri {
+    /// Parse an `http://` or `https://` URL, normalizing it (e.g. lowercasing the host)
     pub fn parse(url: &str) -> Result<Self> {
-        if !url.starts_with("http://") && !url.starts_with("https://") {
-            return Err(S3FcpError::InvalidUri(
-                "URL must start with http:// or https://".to_string(),
-            ));
+        let url = url.trim();
+        if url.is_empty() {
+            return Err(S3FcpError::InvalidUri("URL is empty".to_string()));
         }
+
+        let parsed = reqwest::Url::parse(url)
+            .map_err(|e| S3FcpError::InvalidUri(format!("Malformed URL '{}': {}", url, e)))?;
+
+        match parsed.scheme() {
+            "http" | "https" => {}
+            "s3" => {
+                return Err(S3FcpError::InvalidUri(format!(
+                    "'{}' is an S3 URI; use `s3fcp s3` to download it",
+                    url
+                )))
+            }
+            scheme => {
+                return Err(S3FcpError::InvalidUri(format!(
+                    "Unsupported scheme '{}': URL must start with http:// or https://",
+                    scheme
+                )))
+            }
+        }
+
+        if parsed.host_str().is_none_or(str::is_empty) {
+            return Err(S3FcpError::InvalidUri(format!("URL '{}' has no host", url)));
+        }
+
         Ok(HttpUri {
-            url: url.to_string(),
+            url: parsed.to_string(),
         })
     }
 }
 
 impl S3Uri {
+    /// Parse an S3 URI, validating the bucket name against the AWS naming rules
     pub fn parse(uri: &str) -> Result<Self> {
-        // Check for s3:// prefix
-        if !uri.starts_with("s3://") {
-            return Err(S3FcpError::InvalidUri(
-                "URI must start with s3://".to_string(),
-            ));
+        let uri = Self::parse_unvalidated(uri)?;
+        validate_bucket_name(&uri.bucket)?;
+        Ok(uri)
+    }
+
+    /// Parse an S3 URI without validating the bucket name
+    /// Some S3-compatible stores accept bucket names AWS would reject
+    pub fn parse_unvalidated(uri: &str) -> Result<Self> {
+        let uri = Self::parse_prefix(uri)?;
+
+        if uri.key.is_empty() {
+            return Err(S3FcpError::InvalidUri("Object key is missing".to_string()));
         }
 
-        // Remove s3:// prefix
-        let without_prefix = &uri[5..];
+        Ok(uri)
+    }
+
+    /// Parse an S3 URI whose key is a prefix, which may be empty (e.g. `s3://bucket/`)
+    /// The bucket name is not validated
+    ///
+    /// Everything after the first `/` is the key, byte for byte: it is not trimmed,
+    /// case-folded or percent-decoded, so `s3://bucket/a%20b` names the key `a%20b`.
+    /// The one exception is a trailing `?region=<region>` naming an AWS region, e.g.
+    /// `s3://bucket/key?region=eu-west-1`, which names the bucket's region instead. A
+    /// suffix that isn't shaped like an AWS region, as in `s3://bucket/report?region=emea`,
+    /// stays part of the key.
+    pub fn parse_prefix(uri: &str) -> Result<Self> {
+        // Check for and remove s3:// prefix
+        let without_prefix = uri
+            .strip_prefix("s3://")
+            .ok_or_else(|| S3FcpError::InvalidUri("URI must start with s3://".to_string()))?;
+
+        let (without_prefix, region) = match without_prefix.rsplit_once("?region=") {
+            Some((rest, region)) if is_aws_region(region) => (rest, Some(region.to_string())),
+            _ => (without_prefix, None),
+        };
 
         // Split into bucket and key
-        let parts: Vec<&str> = without_prefix.splitn(2, '/').collect();
+        let (bucket, key) = without_prefix
+            .split_once('/')
+            .unwrap_or((without_prefix, ""));
 
-        if parts.is_empty() || parts[0].is_empty() {
+        if bucket.is_empty() {
             return Err(S3FcpError::InvalidUri("Bucket name is missing".to_string()));
         }
 
-        let bucket = parts[0].to_string();
+        Ok(S3Uri {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            region,
+        })
+    }
+}
 
-        // Key is optional (can be empty for bucket root, though S3 doesn't allow downloading buckets)
-        let key = if parts.len() > 1 {
-            parts[1].to_string()
-        } else {
-            return Err(S3FcpError::InvalidUri("Object key is missing".to_string()));
-        };
+/// Whether `region` is shaped like an AWS region: `eu-west-1`, `us-gov-west-1`,
+/// `us-isob-east-1` and so on
+fn is_aws_region(region: &str) -> bool {
+    let word = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_lowercase());
+    let parts: Vec<&str> = region.split('-').collect();
+    let (country, partition, direction, number) = match parts[..] {
+        [country, direction, number] => (country, None, direction, number),
+        [country, partition, direction, number] => (country, Some(partition), direction, number),
+        _ => return false,
+    };
+    country.len() == 2
+        && word(country)
+        && partition.is_none_or(|p| p == "gov" || (p.starts_with("iso") && word(p)))
+        && word(direction)
+        && !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit())
+}
 
-        if key.is_empty() {
-            return Err(S3FcpError::InvalidUri(
-                "Object key cannot be empty".to_string(),
-            ));
-        }
+/// Check a bucket name against the AWS general purpose bucket naming rules
+pub fn validate_bucket_name(bucket: &str) -> Result<()> {
+    let invalid = |reason: &str| {
+        Err(S3FcpError::InvalidUri(format!(
+            "Invalid bucket name '{}': {}",
+            bucket, reason
+        )))
+    };
+
+    if bucket.len() < 3 || bucket.len() > 63 {
+        return invalid("must be between 3 and 63 characters long");
+    }
+    if bucket.chars().any(|c| c.is_ascii_uppercase()) {
+        return invalid(&format!(
+            "must not contain uppercase letters (did you mean '{}'?)",
+            bucket.to_ascii_lowercase()
+        ));
+    }
+    if bucket.contains('_') {
+        return invalid("must not contain underscores");
+    }
+    if let Some(c) = bucket
+        .chars()
+        .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '.' || *c == '-'))
+    {
+        return invalid(&format!("contains invalid character '{}'", c));
+    }
+    let is_alphanumeric = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+    if !is_alphanumeric(bucket.chars().next()) || !is_alphanumeric(bucket.chars().last()) {
+        return invalid("must begin and end with a letter or number");
+    }
+    if bucket.contains("..") {
+        return invalid("must not contain two adjacent periods");
+    }
+    if bucket.parse::<std::net::Ipv4Addr>().is_ok() {
+        return invalid("must not be formatted as an IP address");
+    }
+    if bucket.starts_with("xn--") || bucket.starts_with("sthree-") {
+        return invalid("must not start with a reserved prefix");
+    }
+    if bucket.ends_with("-s3alias") || bucket.ends_with("--ol-s3") {
+        return invalid("must not end with a reserved suffix");
+    }
+
+    Ok(())
+}
 
-        Ok(S3Uri { bucket, key })
+/// Check that a bucket can be reached through S3 Transfer Acceleration
+/// The accelerated endpoint only serves buckets whose names are valid DNS labels
+pub fn validate_accelerate_bucket(bucket: &str) -> Result<()> {
+    if bucket.contains('.') {
+        return Err(S3FcpError::InvalidUri(format!(
+            "Bucket '{}' can't use Transfer Acceleration because its name contains periods",
+            bucket
+        )));
     }
+    Ok(())
+}
+
+/// URL of `key` on a CDN in front of its bucket, from a `--cdn-url` template
+/// `{key}` in the template is replaced with the percent-encoded key; without it the
+/// key is appended as the URL's last path segments
+pub fn cdn_url(template: &str, key: &str) -> Result<HttpUri> {
+    let key: String = key
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    let url = if template.contains("{key}") {
+        template.replace("{key}", &key)
+    } else {
+        format!("{}/{}", template.trim_end_matches('/'), key)
+    };
+    HttpUri::parse(&url)
 }
 
 #[cfg(test)]
@@ -80,6 +223,25 @@ mod tests {
         assert_eq!(uri.key, "folder/");
     }
 
+    #[test]
+    fn test_key_is_preserved_exactly() {
+        for key in [
+            "with spaces/file name.txt",
+            "a+b=c&d",
+            "notes#1?draft",
+            "données/日本語/ü.txt",
+            "  leading and trailing  ",
+            "line\nbreak",
+            "a%20b",
+            "/leading//double/slash",
+            "MiXeD/CaSe",
+        ] {
+            let uri = S3Uri::parse(&format!("s3://my-bucket/{}", key)).unwrap();
+            assert_eq!(uri.bucket, "my-bucket");
+            assert_eq!(uri.key, key);
+        }
+    }
+
     #[test]
     fn test_invalid_uri_no_prefix() {
         let result = S3Uri::parse("my-bucket/key");
@@ -98,6 +260,120 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_valid_bucket_names() {
+        assert!(S3Uri::parse("s3://abc/key").is_ok());
+        assert!(S3Uri::parse("s3://my.bucket-123/key").is_ok());
+    }
+
+    #[test]
+    fn test_invalid_bucket_uppercase() {
+        let err = S3Uri::parse("s3://My-Bucket/key").unwrap_err().to_string();
+        assert!(err.contains("uppercase"));
+        assert!(err.contains("my-bucket"));
+    }
+
+    #[test]
+    fn test_invalid_bucket_underscore() {
+        let err = S3Uri::parse("s3://my_bucket/key").unwrap_err().to_string();
+        assert!(err.contains("underscores"));
+    }
+
+    #[test]
+    fn test_invalid_bucket_length() {
+        assert!(S3Uri::parse("s3://ab/key").is_err());
+        assert!(S3Uri::parse(&format!("s3://{}/key", "a".repeat(64))).is_err());
+    }
+
+    #[test]
+    fn test_invalid_bucket_format() {
+        assert!(S3Uri::parse("s3://-bucket/key").is_err());
+        assert!(S3Uri::parse("s3://bucket-/key").is_err());
+        assert!(S3Uri::parse("s3://my..bucket/key").is_err());
+        assert!(S3Uri::parse("s3://192.168.1.1/key").is_err());
+        assert!(S3Uri::parse("s3://bucket!/key").is_err());
+        assert!(S3Uri::parse("s3://xn--bucket/key").is_err());
+    }
+
+    #[test]
+    fn test_validate_accelerate_bucket() {
+        assert!(validate_accelerate_bucket("my-bucket").is_ok());
+        assert!(validate_accelerate_bucket("my.bucket").is_err());
+    }
+
+    #[test]
+    fn test_cdn_url() {
+        let url = |template, key| cdn_url(template, key).unwrap().url;
+        assert_eq!(
+            url("https://d111.cloudfront.net/{key}", "data/a b.bin"),
+            "https://d111.cloudfront.net/data/a%20b.bin"
+        );
+        assert_eq!(
+            url("https://cdn.example.com/mirror/", "x?y#z"),
+            "https://cdn.example.com/mirror/x%3Fy%23z"
+        );
+        assert!(cdn_url("s3://bucket/{key}", "key").is_err());
+    }
+
+    #[test]
+    fn test_parse_unvalidated_accepts_nonconforming_bucket() {
+        let uri = S3Uri::parse_unvalidated("s3://My_Bucket/key").unwrap();
+        assert_eq!(uri.bucket, "My_Bucket");
+        assert_eq!(uri.key, "key");
+    }
+
+    #[test]
+    fn test_parse_prefix_allows_empty_key() {
+        let uri = S3Uri::parse_prefix("s3://my-bucket").unwrap();
+        assert_eq!(uri.bucket, "my-bucket");
+        assert_eq!(uri.key, "");
+
+        let uri = S3Uri::parse_prefix("s3://my-bucket/logs/").unwrap();
+        assert_eq!(uri.key, "logs/");
+
+        assert!(S3Uri::parse_prefix("s3:///logs/").is_err());
+    }
+
+    #[test]
+    fn test_region_suffix() {
+        let uri = S3Uri::parse("s3://my-bucket/path/file.txt?region=eu-west-1").unwrap();
+        assert_eq!(uri.bucket, "my-bucket");
+        assert_eq!(uri.key, "path/file.txt");
+        assert_eq!(uri.region.as_deref(), Some("eu-west-1"));
+
+        let uri = S3Uri::parse_prefix("s3://my-bucket?region=us-east-2").unwrap();
+        assert_eq!(uri.bucket, "my-bucket");
+        assert_eq!(uri.key, "");
+        assert_eq!(uri.region.as_deref(), Some("us-east-2"));
+
+        let uri = S3Uri::parse("s3://my-bucket/notes#1?draft").unwrap();
+        assert_eq!(uri.key, "notes#1?draft");
+        assert_eq!(uri.region, None);
+
+        let uri = S3Uri::parse("s3://my-bucket/key?region=us-gov-west-1").unwrap();
+        assert_eq!(uri.region.as_deref(), Some("us-gov-west-1"));
+        let uri = S3Uri::parse("s3://my-bucket/key?region=us-isob-east-1").unwrap();
+        assert_eq!(uri.region.as_deref(), Some("us-isob-east-1"));
+
+        assert!(S3Uri::parse("s3://my-bucket?region=eu-west-1").is_err());
+    }
+
+    #[test]
+    fn test_key_containing_region_suffix() {
+        // Suffixes that aren't AWS regions are part of the key, byte for byte
+        for key in [
+            "report?region=emea",
+            "key?region=",
+            "key?region=EU West",
+            "key?region=eu-west",
+            "key?region=europe-west-1",
+        ] {
+            let uri = S3Uri::parse(&format!("s3://my-bucket/{}", key)).unwrap();
+            assert_eq!(uri.key, key);
+            assert_eq!(uri.region, None);
+        }
+    }
+
     #[test]
     fn test_http_uri_https() {
         let uri = HttpUri::parse("https://example.com/file.txt").unwrap();
@@ -115,4 +391,44 @@ mod tests {
         let result = HttpUri::parse("ftp://example.com/file.txt");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_http_uri_normalizes_url() {
+        let uri = HttpUri::parse("  HTTPS://Example.COM:443/a/../file.txt ").unwrap();
+        assert_eq!(uri.url, "https://example.com/file.txt");
+    }
+
+    #[test]
+    fn test_http_uri_rejects_unsupported_schemes() {
+        for url in [
+            "ftp://example.com/file.txt",
+            "file:///etc/passwd",
+            "s3://bucket/key",
+        ] {
+            assert!(
+                matches!(HttpUri::parse(url), Err(S3FcpError::InvalidUri(_))),
+                "{}",
+                url
+            );
+        }
+        let message = HttpUri::parse("s3://bucket/key").unwrap_err().to_string();
+        assert!(message.contains("s3fcp s3"), "{}", message);
+    }
+
+    #[test]
+    fn test_http_uri_rejects_malformed_urls() {
+        for url in [
+            "",
+            "   ",
+            "example.com/file.txt",
+            "http://",
+            "https://exa mple.com/",
+        ] {
+            assert!(
+                matches!(HttpUri::parse(url), Err(S3FcpError::InvalidUri(_))),
+                "{:?}",
+                url
+            );
+        }
+    }
 }
@@ -0,0 +1,339 @@
+use crate::error::{Result, S3FcpError};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use md5::{Digest, Md5};
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
+
+/// Writer adapter that computes a digest of everything written through it
+/// Chunks reach the writer in object order, so the digest covers the whole object
+pub struct DigestWriter<W, D> {
+    inner: W,
+    hasher: D,
+}
+
+/// Computes the MD5 used by `Content-MD5`
+pub type Md5Writer<W> = DigestWriter<W, Md5>;
+
+/// Computes the SHA-256 listed in `sha256sum` manifests
+pub type Sha256Writer<W> = DigestWriter<W, Sha256>;
+
+impl<W, D: Digest> DigestWriter<W, D> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: D::new(),
+        }
+    }
+
+    /// Return the inner writer and the digest of everything written
+    pub fn finish(self) -> (W, Vec<u8>) {
+        (self.inner, self.hasher.finalize().to_vec())
+    }
+}
+
+/// Lowercase hex encoding of a digest
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl<W> Sha256Writer<W> {
+    /// Compare the digest with a hex SHA-256 and return the inner writer
+    pub fn verify_hex(self, expected: &str) -> Result<W> {
+        let (inner, digest) = self.finish();
+        check_sha256(&to_hex(&digest), expected)?;
+        Ok(inner)
+    }
+}
+
+/// Compare a computed hex SHA-256 with the expected one, ignoring case
+pub(crate) fn check_sha256(actual: &str, expected: &str) -> Result<()> {
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(S3FcpError::IntegrityError(format!(
+            "SHA-256 mismatch: expected {}, got {}",
+            expected.trim(),
+            actual
+        )));
+    }
+    Ok(())
+}
+
+impl<W> Md5Writer<W> {
+    /// Compare the digest with a base64 `Content-MD5` value and return the inner writer
+    pub fn verify(self, expected: &str) -> Result<W> {
+        let (inner, digest) = self.finish();
+        check_content_md5(&digest, expected)?;
+        Ok(inner)
+    }
+}
+
+/// Compare a computed MD5 with a base64 `Content-MD5` value
+pub(crate) fn check_content_md5(digest: &[u8], expected: &str) -> Result<()> {
+    let actual = STANDARD.encode(digest);
+    if actual != expected.trim() {
+        return Err(S3FcpError::IntegrityError(format!(
+            "Content-MD5 mismatch: expected {}, got {}",
+            expected.trim(),
+            actual
+        )));
+    }
+    Ok(())
+}
+
+/// Digest the writer stage can compute while the object is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DigestAlgorithm {
+    Md5,
+    Sha256,
+}
+
+/// Digests of a download, computed incrementally as the ordered chunks are written,
+/// so they cost no second pass over the output
+///
+/// Digests needed for `--verify` or an expected SHA-256 are always recorded; others
+/// only when requested. Partial downloads record none, as digests cover the whole object.
+#[derive(Debug, Default)]
+pub struct DownloadStats {
+    requested: Vec<DigestAlgorithm>,
+    digests: Mutex<BTreeMap<DigestAlgorithm, String>>,
+}
+
+impl DownloadStats {
+    /// Stats that also compute `algorithms` when no verification needs them
+    pub fn new(algorithms: impl IntoIterator<Item = DigestAlgorithm>) -> Self {
+        Self {
+            requested: algorithms.into_iter().collect(),
+            digests: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Whether `algorithm` was requested
+    pub fn wants(&self, algorithm: DigestAlgorithm) -> bool {
+        self.requested.contains(&algorithm)
+    }
+
+    /// Lowercase hex digest of the downloaded object, if it was computed
+    pub fn digest(&self, algorithm: DigestAlgorithm) -> Option<String> {
+        self.digests.lock().unwrap().get(&algorithm).cloned()
+    }
+
+    pub(crate) fn record(&self, algorithm: DigestAlgorithm, hex: String) {
+        self.digests.lock().unwrap().insert(algorithm, hex);
+    }
+}
+
+impl<W: AsyncWrite + Unpin, D: Digest + Unpin> AsyncWrite for DigestWriter<W, D> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            this.hasher.update(&buf[..written]);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Writer adapter that counts the bytes the inner writer accepted
+pub struct CountingWriter<W> {
+    inner: W,
+    count: Arc<AtomicU64>,
+}
+
+impl<W> CountingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            count: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Bytes written so far
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// The count, still readable once the writer was moved or dropped, e.g. by a
+    /// failed download
+    pub fn counter(&self) -> Arc<AtomicU64> {
+        self.count.clone()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for CountingWriter<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            self.count.fetch_add(written as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// SHA-256 digests by file name, read from `sha256sum` output
+#[derive(Debug, Default, Clone)]
+pub struct ChecksumManifest {
+    digests: HashMap<String, String>,
+}
+
+impl ChecksumManifest {
+    /// Parse `<hexdigest>  <filename>` lines; `*` before the name (binary mode), a leading
+    /// `./`, blank lines and `#` comments are accepted
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let mut digests = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (digest, name) = line.split_once(' ').ok_or_else(|| {
+                format!("line {}: expected `<hexdigest>  <filename>`", number + 1)
+            })?;
+            let name = name.strip_prefix([' ', '*']).unwrap_or(name);
+            let name = name.strip_prefix("./").unwrap_or(name);
+            if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(format!(
+                    "line {}: expected a SHA-256 hex digest, got '{}'",
+                    number + 1,
+                    digest
+                ));
+            }
+            if name.is_empty() {
+                return Err(format!("line {}: missing file name", number + 1));
+            }
+            digests.insert(name.to_string(), digest.to_ascii_lowercase());
+        }
+        Ok(Self { digests })
+    }
+
+    /// Expected hex digest of the file at `name`, relative to the download directory
+    pub fn digest(&self, name: &str) -> Option<&str> {
+        self.digests.get(name).map(String::as_str)
+    }
+
+    /// File names listed in the manifest
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.digests.keys().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_md5_writer_verifies_content() {
+        let mut writer = Md5Writer::new(Vec::new());
+        writer.write_all(b"hello ").await.unwrap();
+        writer.write_all(b"world").await.unwrap();
+
+        // base64 of md5("hello world")
+        let output = writer.verify("XrY7u+Ae7tCTyyK7j1rNww==").unwrap();
+        assert_eq!(output, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn test_md5_writer_detects_mismatch() {
+        let mut writer = Md5Writer::new(Vec::new());
+        writer.write_all(b"hello world").await.unwrap();
+
+        let result = writer.verify("1B2M2Y8AsgTpgAmY7PhCfg==");
+        assert!(matches!(result, Err(S3FcpError::IntegrityError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_sha256_writer_verifies_content() {
+        let mut writer = Sha256Writer::new(Vec::new());
+        writer.write_all(b"hello world").await.unwrap();
+        let output = writer
+            .verify_hex("B94D27B9934D3E08A52E52D7DA7DABFAC484EFE37A5380EE9088F7ACE2EFCDE9")
+            .unwrap();
+        assert_eq!(output, b"hello world");
+
+        let mut writer = Sha256Writer::new(Vec::new());
+        writer.write_all(b"hello").await.unwrap();
+        let result =
+            writer.verify_hex("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9");
+        assert!(matches!(result, Err(S3FcpError::IntegrityError(_))));
+    }
+
+    #[test]
+    fn test_download_stats() {
+        let stats = DownloadStats::new([DigestAlgorithm::Md5]);
+        assert!(stats.wants(DigestAlgorithm::Md5));
+        assert!(!stats.wants(DigestAlgorithm::Sha256));
+
+        stats.record(DigestAlgorithm::Md5, "abc".to_string());
+        assert_eq!(stats.digest(DigestAlgorithm::Md5).as_deref(), Some("abc"));
+        assert_eq!(stats.digest(DigestAlgorithm::Sha256), None);
+    }
+
+    #[tokio::test]
+    async fn test_counting_writer() {
+        let mut writer = CountingWriter::new(Vec::new());
+        writer.write_all(b"hello ").await.unwrap();
+        writer.write_all(b"world").await.unwrap();
+
+        let counter = writer.counter();
+        assert_eq!(writer.count(), 11);
+        assert_eq!(writer.into_inner(), b"hello world");
+        assert_eq!(counter.load(Ordering::Relaxed), 11);
+    }
+
+    #[test]
+    fn test_parse_checksum_manifest() {
+        let hello = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+        let empty = "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855";
+        let text = format!(
+            "# generated by sha256sum\n{}  a.txt\n\n{} *./nested/b c.bin\n",
+            hello, empty
+        );
+
+        let manifest = ChecksumManifest::parse(&text).unwrap();
+        assert_eq!(manifest.digest("a.txt"), Some(hello));
+        assert_eq!(
+            manifest.digest("nested/b c.bin"),
+            Some(empty.to_ascii_lowercase().as_str())
+        );
+        assert_eq!(manifest.names().count(), 2);
+
+        assert!(ChecksumManifest::parse("abc  a.txt").is_err());
+        assert!(ChecksumManifest::parse(hello).is_err());
+        assert!(ChecksumManifest::parse(&format!("{}  ", hello)).is_err());
+    }
+}
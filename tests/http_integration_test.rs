@@ -1,26 +1,64 @@
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{HeaderValue, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
 use axum::Router;
+use futures::StreamExt;
+use s3fcp::batch::BatchSource;
 use s3fcp::cli::DownloadArgs;
-use s3fcp::downloader::download;
-use s3fcp::http_client::HttpClient;
+use s3fcp::downloader::{download, download_to_file};
+use s3fcp::error::S3FcpError;
+use s3fcp::http_client::{header_map, HeadMethod, HeaderArg, HttpClient};
+use s3fcp::reader::download_reader;
+use s3fcp::resolve::ResolveOverride;
+use s3fcp::s3_client::DownloadClient;
+use s3fcp::template::OutputTemplate;
 use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tempfile::TempDir;
+use tokio::io::AsyncReadExt;
 use tokio::net::TcpListener;
 use tower_http::services::ServeDir;
 
+/// Serve the given router on a random local port and return its base_url
+async fn serve(app: Router) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    format!("http://{}", addr)
+}
+
 /// Start a static file server and return (base_url, temp_dir)
 async fn start_file_server() -> (String, TempDir) {
     let temp_dir = TempDir::new().unwrap();
     let serve_dir = ServeDir::new(temp_dir.path());
     let app = Router::new().fallback_service(serve_dir);
 
-    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
-    let addr = listener.local_addr().unwrap();
-    tokio::spawn(async move {
-        axum::serve(listener, app).await.unwrap();
-    });
+    (serve(app).await, temp_dir)
+}
+
+async fn reject_head(request: Request, next: Next) -> Response {
+    if request.method() == Method::HEAD {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+    next.run(request).await
+}
 
-    (format!("http://{}", addr), temp_dir)
+/// Start a static file server that answers HEAD requests with 405
+async fn start_no_head_file_server() -> (String, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let serve_dir = ServeDir::new(temp_dir.path());
+    let app = Router::new()
+        .fallback_service(serve_dir)
+        .layer(middleware::from_fn(reject_head));
+
+    (serve(app).await, temp_dir)
 }
 
 /// Create a test file with given content
@@ -66,6 +104,532 @@ async fn test_http_download_chunked_with_range() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_http_download_chunked_over_http2() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_file_server().await;
+
+    let content: Vec<u8> = (0..1024 * 1024).map(|i| (i % 256) as u8).collect();
+    create_test_file(&temp_dir, "large.bin", &content);
+
+    let client = Arc::new(HttpClient::new(format!("{}/large.bin", base_url)).with_http2(true)?);
+    let args = DownloadArgs::builder()
+        .concurrency(4)
+        .chunk_size(256 * 1024)
+        .quiet(true)
+        .build();
+    let output = download(client, args, Vec::new()).await?;
+
+    assert_eq!(output, content);
+    Ok(())
+}
+
+/// Start a static file server that delays every response and records the most
+/// requests it was serving at once
+async fn start_slow_file_server() -> (String, TempDir, Arc<AtomicUsize>) {
+    let temp_dir = TempDir::new().unwrap();
+    let serve_dir = ServeDir::new(temp_dir.path());
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let peak_seen = peak.clone();
+    let app = Router::new()
+        .fallback_service(serve_dir)
+        .layer(middleware::from_fn(move |request: Request, next: Next| {
+            let in_flight = in_flight.clone();
+            let peak = peak.clone();
+            async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                let response = next.run(request).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                response
+            }
+        }));
+
+    (serve(app).await, temp_dir, peak_seen)
+}
+
+#[tokio::test]
+async fn test_http_max_connections_limits_requests_in_flight() -> anyhow::Result<()> {
+    let (base_url, temp_dir, peak) = start_slow_file_server().await;
+
+    let content: Vec<u8> = (0..64 * 1024).map(|i| (i % 256) as u8).collect();
+    create_test_file(&temp_dir, "large.bin", &content);
+
+    let client =
+        HttpClient::new(format!("{}/large.bin", base_url)).with_max_connections(Some(2))?;
+    let args = DownloadArgs::builder()
+        .concurrency(8)
+        .chunk_size(4 * 1024)
+        .quiet(true)
+        .build();
+    let output = download(Arc::new(client), args, Vec::new()).await?;
+
+    assert_eq!(output, content);
+    assert_eq!(peak.load(Ordering::SeqCst), 2);
+    Ok(())
+}
+
+/// Start a static file server whose first range response sends half its body and then
+/// stalls forever, and return the number of stalled responses
+async fn start_stalling_file_server() -> (String, TempDir, Arc<AtomicUsize>) {
+    let temp_dir = TempDir::new().unwrap();
+    let serve_dir = ServeDir::new(temp_dir.path());
+    let stalled = Arc::new(AtomicUsize::new(0));
+    let stalled_seen = stalled.clone();
+    let app = Router::new()
+        .fallback_service(serve_dir)
+        .layer(middleware::from_fn(move |request: Request, next: Next| {
+            let stalled = stalled.clone();
+            async move {
+                let ranged = request.headers().contains_key("range");
+                let response = next.run(request).await;
+                if !ranged || stalled.fetch_add(1, Ordering::SeqCst) > 0 {
+                    return response;
+                }
+
+                let (parts, body) = response.into_parts();
+                let mut data = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+                data.truncate(data.len() / 2);
+                let body = futures::stream::iter([Ok::<_, std::io::Error>(data)])
+                    .chain(futures::stream::pending());
+                Response::from_parts(parts, Body::from_stream(body))
+            }
+        }));
+
+    (serve(app).await, temp_dir, stalled_seen)
+}
+
+#[tokio::test]
+async fn test_http_stall_timeout_retries_stalled_chunk() -> anyhow::Result<()> {
+    let (base_url, temp_dir, stalled) = start_stalling_file_server().await;
+    let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+    create_test_file(&temp_dir, "test.bin", &content);
+
+    let client = HttpClient::new(format!("{}/test.bin", base_url))
+        .with_stall_timeout(Some(std::time::Duration::from_millis(200)))?;
+    let args = DownloadArgs::builder().chunk_size(100).quiet(true).build();
+    let output = tokio::time::timeout(
+        std::time::Duration::from_secs(10),
+        download(Arc::new(client), args, Vec::new()),
+    )
+    .await??;
+
+    assert_eq!(output, content);
+    assert!(stalled.load(Ordering::SeqCst) > 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_download_to_file_with_fsync() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_file_server().await;
+    let content: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+    create_test_file(&temp_dir, "synced.bin", &content);
+
+    let client = Arc::new(HttpClient::new(format!("{}/synced.bin", base_url)));
+    let args = DownloadArgs::builder()
+        .chunk_size(16 * 1024)
+        .quiet(true)
+        .fsync(true)
+        .build();
+    let out_dir = TempDir::new()?;
+    let path = out_dir.path().join("synced.bin");
+    download_to_file(client, args, &path).await?;
+
+    assert_eq!(std::fs::read(&path)?, content);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_download_split_into_segments() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_file_server().await;
+    let content: Vec<u8> = (0..100 * 1024).map(|i| (i % 249) as u8).collect();
+    create_test_file(&temp_dir, "split.bin", &content);
+
+    let client = Arc::new(HttpClient::new(format!("{}/split.bin", base_url)));
+    let args = DownloadArgs::builder()
+        .chunk_size(16 * 1024)
+        .quiet(true)
+        .split_size(40 * 1024)
+        .build();
+    let out_dir = TempDir::new()?;
+    download_to_file(client, args, &out_dir.path().join("split.bin")).await?;
+
+    let mut joined = Vec::new();
+    for (name, size) in [("000", 40), ("001", 40), ("002", 20)] {
+        let segment = std::fs::read(out_dir.path().join(format!("split.bin.{}", name)))?;
+        assert_eq!(segment.len(), size * 1024);
+        joined.extend(segment);
+    }
+    assert_eq!(joined, content);
+    assert!(!out_dir.path().join("split.bin").exists());
+    assert!(!out_dir.path().join("split.bin.003").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_resolve_pins_host_to_address() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_file_server().await;
+    let content = b"served through a pinned address";
+    create_test_file(&temp_dir, "pinned.txt", content);
+
+    let port = base_url.rsplit(':').next().unwrap();
+    let overrides = [ResolveOverride::parse(&format!("s3fcp.invalid:{}:127.0.0.1", port)).unwrap()];
+    let client = HttpClient::new(format!("http://s3fcp.invalid:{}/pinned.txt", port))
+        .with_resolve(&overrides)?;
+    let args = DownloadArgs::builder().quiet(true).build();
+    let output = download(Arc::new(client), args, Vec::new()).await?;
+
+    assert_eq!(output, content);
+    Ok(())
+}
+
+/// Start a static file server that only serves the virtual host `files.example`
+async fn start_virtual_host_server() -> (String, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let serve_dir = ServeDir::new(temp_dir.path());
+    let app = Router::new()
+        .fallback_service(serve_dir)
+        .layer(middleware::from_fn(
+            |request: Request, next: Next| async move {
+                if request.headers().get("host").map(|h| h.as_bytes()) != Some(b"files.example") {
+                    return StatusCode::MISDIRECTED_REQUEST.into_response();
+                }
+                next.run(request).await
+            },
+        ));
+
+    (serve(app).await, temp_dir)
+}
+
+#[tokio::test]
+async fn test_http_host_override_applies_to_every_request() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_virtual_host_server().await;
+    let content: Vec<u8> = (0..32 * 1024).map(|i| (i % 251) as u8).collect();
+    create_test_file(&temp_dir, "data.bin", &content);
+    let url = format!("{}/data.bin", base_url);
+
+    // --host wins over a Host header given with -H
+    let headers = header_map(&[HeaderArg::parse("Host: other.example").unwrap()])?;
+    let client = HttpClient::new(url.clone())
+        .with_headers(headers)
+        .with_host(Some("files.example"))?;
+    let args = DownloadArgs::builder()
+        .chunk_size(8 * 1024)
+        .quiet(true)
+        .build();
+    let output = download(Arc::new(client), args, Vec::new()).await?;
+    assert_eq!(output, content);
+
+    let client = HttpClient::new(url).with_host(Some("files.example"))?;
+    let args = DownloadArgs::builder()
+        .quiet(true)
+        .single_stream(true)
+        .build();
+    let output = download(Arc::new(client), args, Vec::new()).await?;
+    assert_eq!(output, content);
+
+    let client = HttpClient::new(format!("{}/data.bin", base_url));
+    let args = DownloadArgs::builder().quiet(true).build();
+    assert!(download(Arc::new(client), args, Vec::new()).await.is_err());
+
+    assert!(HttpClient::new(base_url)
+        .with_host(Some("bad\nhost"))
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_download_over_ipv6() -> anyhow::Result<()> {
+    // Skip on hosts without an IPv6 loopback
+    let Ok(listener) = TcpListener::bind("[::1]:0").await else {
+        return Ok(());
+    };
+    let addr = listener.local_addr()?;
+    let temp_dir = TempDir::new()?;
+    let app = Router::new().fallback_service(ServeDir::new(temp_dir.path()));
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let content: Vec<u8> = (0..64 * 1024).map(|i| (i % 253) as u8).collect();
+    create_test_file(&temp_dir, "v6.bin", &content);
+
+    let client = Arc::new(HttpClient::new(format!("http://{}/v6.bin", addr)));
+    let args = DownloadArgs::builder()
+        .chunk_size(16 * 1024)
+        .quiet(true)
+        .build();
+    let output = download(client, args, Vec::new()).await?;
+
+    assert_eq!(output, content);
+    Ok(())
+}
+
+/// Start a static file server that sends `content_md5` as the Content-MD5 header
+async fn start_md5_file_server(content_md5: &'static str) -> (String, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let serve_dir = ServeDir::new(temp_dir.path());
+    let app = Router::new()
+        .fallback_service(serve_dir)
+        .layer(middleware::map_response(
+            move |mut response: Response| async move {
+                response
+                    .headers_mut()
+                    .insert("content-md5", HeaderValue::from_static(content_md5));
+                response
+            },
+        ));
+
+    (serve(app).await, temp_dir)
+}
+
+#[tokio::test]
+async fn test_http_verify_content_md5() -> anyhow::Result<()> {
+    // base64 of md5("hello world")
+    let (base_url, temp_dir) = start_md5_file_server("XrY7u+Ae7tCTyyK7j1rNww==").await;
+    create_test_file(&temp_dir, "hello.txt", b"hello world");
+
+    let client = Arc::new(HttpClient::new(format!("{}/hello.txt", base_url)));
+    let args = DownloadArgs::builder()
+        .chunk_size(4)
+        .quiet(true)
+        .verify(true)
+        .build();
+    let output = download(client, args, Vec::new()).await?;
+
+    assert_eq!(output, b"hello world");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_verify_content_md5_mismatch() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_md5_file_server("1B2M2Y8AsgTpgAmY7PhCfg==").await;
+    create_test_file(&temp_dir, "hello.txt", b"hello world");
+
+    let client = Arc::new(HttpClient::new(format!("{}/hello.txt", base_url)));
+    let args = DownloadArgs::builder().quiet(true).verify(true).build();
+    let result = download(client, args, Vec::new()).await;
+
+    assert!(matches!(result, Err(S3FcpError::IntegrityError(_))));
+    Ok(())
+}
+
+/// Start a static file server that honors ranges but doesn't send Accept-Ranges
+/// Returns the number of range requests served so far along with the usual values
+async fn start_unadvertised_range_server() -> (String, TempDir, Arc<AtomicUsize>) {
+    let temp_dir = TempDir::new().unwrap();
+    let serve_dir = ServeDir::new(temp_dir.path());
+    let range_requests = Arc::new(AtomicUsize::new(0));
+    let counter = range_requests.clone();
+    let app = Router::new()
+        .fallback_service(serve_dir)
+        .layer(middleware::from_fn(move |request: Request, next: Next| {
+            if request.headers().contains_key("range") {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+            async move {
+                let mut response = next.run(request).await;
+                response.headers_mut().remove("accept-ranges");
+                response
+            }
+        }));
+
+    (serve(app).await, temp_dir, range_requests)
+}
+
+#[tokio::test]
+async fn test_http_force_chunked_uses_unadvertised_ranges() -> anyhow::Result<()> {
+    let (base_url, temp_dir, range_requests) = start_unadvertised_range_server().await;
+    let content: Vec<u8> = (0..64 * 1024).map(|i| (i % 253) as u8).collect();
+    create_test_file(&temp_dir, "data.bin", &content);
+
+    let client = Arc::new(HttpClient::new(format!("{}/data.bin", base_url)));
+    let args = DownloadArgs::builder()
+        .chunk_size(8 * 1024)
+        .quiet(true)
+        .force_chunked(true)
+        .build();
+    let output = download(client, args, Vec::new()).await?;
+
+    assert_eq!(output, content);
+    // One probe plus eight chunks
+    assert_eq!(range_requests.load(Ordering::SeqCst), 9);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_range_probe_detects_unadvertised_ranges() -> anyhow::Result<()> {
+    let (base_url, temp_dir, range_requests) = start_unadvertised_range_server().await;
+    let content: Vec<u8> = (0..64 * 1024).map(|i| (i % 253) as u8).collect();
+    create_test_file(&temp_dir, "data.bin", &content);
+
+    let client = HttpClient::new(format!("{}/data.bin", base_url)).with_range_probe(true);
+    let metadata = client.head().await?;
+    assert!(metadata.supports_range);
+    assert_eq!(metadata.content_length, content.len() as u64);
+
+    let args = DownloadArgs::builder()
+        .chunk_size(8 * 1024)
+        .quiet(true)
+        .build();
+    let output = download(Arc::new(client), args, Vec::new()).await?;
+
+    assert_eq!(output, content);
+    // Two probes, one per HEAD, plus eight chunks
+    assert_eq!(range_requests.load(Ordering::SeqCst), 10);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_range_probe_keeps_head_when_ranges_ignored() -> anyhow::Result<()> {
+    let content = b"this server always sends the whole body";
+    let app = Router::new().fallback(move || async move { content.as_slice() });
+    let base_url = serve(app).await;
+
+    let client = HttpClient::new(format!("{}/data.bin", base_url)).with_range_probe(true);
+    let metadata = client.head().await?;
+    assert!(!metadata.supports_range);
+    assert_eq!(metadata.content_length, content.len() as u64);
+
+    let args = DownloadArgs::builder().chunk_size(8).quiet(true).build();
+    let output = download(Arc::new(client), args, Vec::new()).await?;
+
+    assert_eq!(output, content);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_force_chunked_falls_back_when_ranges_ignored() -> anyhow::Result<()> {
+    let content = b"this server always sends the whole body";
+    let app = Router::new().fallback(move || async move { content.as_slice() });
+    let base_url = serve(app).await;
+
+    let client = Arc::new(HttpClient::new(format!("{}/data.bin", base_url)));
+    let args = DownloadArgs::builder()
+        .chunk_size(8)
+        .quiet(true)
+        .force_chunked(true)
+        .build();
+    let output = download(client, args, Vec::new()).await?;
+
+    assert_eq!(output, content);
+    Ok(())
+}
+
+/// Start a server without HEAD support that answers range requests with an unknown total,
+/// `Content-Range: bytes 0-0/*`, and sends the whole body otherwise
+async fn start_unknown_total_server(content: &'static [u8]) -> String {
+    let app = Router::new()
+        .fallback(move |request: Request| async move {
+            if request.headers().contains_key("range") {
+                let mut response = (StatusCode::PARTIAL_CONTENT, &content[..1]).into_response();
+                response
+                    .headers_mut()
+                    .insert("content-range", HeaderValue::from_static("bytes 0-0/*"));
+                return response;
+            }
+            content.into_response()
+        })
+        .layer(middleware::from_fn(reject_head));
+
+    serve(app).await
+}
+
+#[tokio::test]
+async fn test_http_unknown_total_falls_back_to_single_stream() -> anyhow::Result<()> {
+    let content = b"a stream whose length the server doesn't know up front";
+    let base_url = start_unknown_total_server(content).await;
+
+    let client = Arc::new(HttpClient::new(format!("{}/stream", base_url)));
+    let args = DownloadArgs::builder().chunk_size(8).quiet(true).build();
+    let output = download(client, args, Vec::new()).await?;
+
+    assert_eq!(output, content);
+    Ok(())
+}
+
+/// Start a static file server whose HEAD responses claim the files are one byte long
+async fn start_wrong_length_file_server() -> (String, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let serve_dir = ServeDir::new(temp_dir.path());
+    let app = Router::new()
+        .fallback_service(serve_dir)
+        .layer(middleware::from_fn(
+            |request: Request, next: Next| async move {
+                let head = request.method() == Method::HEAD;
+                let mut response = next.run(request).await;
+                if head {
+                    response
+                        .headers_mut()
+                        .insert("content-length", HeaderValue::from_static("1"));
+                }
+                response
+            },
+        ));
+
+    (serve(app).await, temp_dir)
+}
+
+#[tokio::test]
+async fn test_http_content_length_override() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_wrong_length_file_server().await;
+    let content: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+    create_test_file(&temp_dir, "test.bin", &content);
+
+    let client = HttpClient::new(format!("{}/test.bin", base_url)).with_content_length(Some(1000));
+    let client = Arc::new(client);
+    let args = DownloadArgs::builder().chunk_size(100).quiet(true).build();
+    let output = download(client.clone(), args, Vec::new()).await?;
+    assert_eq!(output, content);
+
+    let result = client.get_range(900, 1099).await;
+    assert!(matches!(result, Err(S3FcpError::InvalidRange(_))));
+    Ok(())
+}
+
+/// Start a static file server that answers 401 unless the Authorization header is `token`
+async fn start_auth_file_server(token: &'static str) -> (String, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let serve_dir = ServeDir::new(temp_dir.path());
+    let app = Router::new()
+        .fallback_service(serve_dir)
+        .layer(middleware::from_fn(
+            move |request: Request, next: Next| async move {
+                if request.headers().get("authorization").map(|v| v.as_bytes())
+                    != Some(token.as_bytes())
+                {
+                    return StatusCode::UNAUTHORIZED.into_response();
+                }
+                next.run(request).await
+            },
+        ));
+
+    (serve(app).await, temp_dir)
+}
+
+#[tokio::test]
+async fn test_http_headers_sent_with_every_request() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_auth_file_server("Bearer secret").await;
+    let content: Vec<u8> = (0..10_000).map(|i| (i % 256) as u8).collect();
+    create_test_file(&temp_dir, "private.bin", &content);
+    let token_path = temp_dir.path().join("token");
+    std::fs::write(&token_path, "Bearer secret\n")?;
+
+    let url = format!("{}/private.bin", base_url);
+    let args = || DownloadArgs::builder().chunk_size(1000).quiet(true).build();
+
+    let result = download(Arc::new(HttpClient::new(url.clone())), args(), Vec::new()).await;
+    assert!(matches!(result, Err(S3FcpError::AccessDenied(_))));
+
+    let header = HeaderArg::parse(&format!("Authorization: @{}", token_path.display())).unwrap();
+    let client = HttpClient::new(url).with_headers(header_map(&[header])?);
+    let output = download(Arc::new(client), args(), Vec::new()).await?;
+
+    assert_eq!(output, content);
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_http_download_empty_file() -> anyhow::Result<()> {
     let (base_url, temp_dir) = start_file_server().await;
@@ -100,6 +664,172 @@ async fn test_http_download_404() -> anyhow::Result<()> {
     let args = DownloadArgs::builder().quiet(true).build();
     let result = download(client, args, Vec::new()).await;
 
+    assert!(matches!(result, Err(S3FcpError::NotFound(_))));
+    Ok(())
+}
+
+/// Start a server that answers every request with `status`
+async fn start_status_server(status: StatusCode) -> String {
+    serve(Router::new().fallback(move || async move { status })).await
+}
+
+#[tokio::test]
+async fn test_http_download_403() -> anyhow::Result<()> {
+    let base_url = start_status_server(StatusCode::FORBIDDEN).await;
+
+    let client = Arc::new(HttpClient::new(format!("{}/secret.bin", base_url)));
+    let args = DownloadArgs::builder().quiet(true).build();
+    let result = download(client, args, Vec::new()).await;
+
+    assert!(matches!(result, Err(S3FcpError::AccessDenied(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_download_500() -> anyhow::Result<()> {
+    let base_url = start_status_server(StatusCode::INTERNAL_SERVER_ERROR).await;
+
+    let client = Arc::new(HttpClient::new(format!("{}/broken.bin", base_url)));
+    let args = DownloadArgs::builder().quiet(true).build();
+    let result = download(client, args, Vec::new()).await;
+
+    assert!(matches!(result, Err(S3FcpError::HttpError(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_batch_template_uses_last_modified() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_file_server().await;
+    create_test_file(&temp_dir, "report.csv", b"a,b\n");
+    // 2021-03-04T00:00:00Z
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_614_816_000);
+    std::fs::File::options()
+        .write(true)
+        .open(temp_dir.path().join("report.csv"))?
+        .set_modified(modified)?;
+
+    let url = format!("{}/report.csv", base_url);
+    let template = OutputTemplate::parse("{year}/{month}/{day}/{basename}").unwrap();
+    let source = BatchSource::parse(&url, false)?;
+
+    let client = HttpClient::new(url.clone());
+    let last_modified = client.head().await?.last_modified;
+    assert_eq!(last_modified, Some(modified));
+    assert_eq!(
+        source.render(&template, last_modified)?,
+        std::path::Path::new("2021/03/04/report.csv")
+    );
+
+    // Servers without HEAD support report it in the GET probe
+    let client = HttpClient::new(url).with_head_method(HeadMethod::Get);
+    assert_eq!(client.head().await?.last_modified, Some(modified));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_download_reader() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_file_server().await;
+
+    let content: Vec<u8> = (0..1024 * 1024).map(|i| (i % 256) as u8).collect();
+    create_test_file(&temp_dir, "reader.bin", &content);
+
+    let client = Arc::new(HttpClient::new(format!("{}/reader.bin", base_url)));
+    let args = DownloadArgs::builder()
+        .concurrency(4)
+        .chunk_size(128 * 1024)
+        .quiet(true)
+        .build();
+
+    let mut reader = download_reader(client, args);
+    let mut output = Vec::new();
+    reader.read_to_end(&mut output).await?;
+
+    assert_eq!(output, content);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_download_reader_reports_errors() -> anyhow::Result<()> {
+    let (base_url, _temp_dir) = start_file_server().await;
+
+    let client = Arc::new(HttpClient::new(format!("{}/not-found.txt", base_url)));
+    let args = DownloadArgs::builder().quiet(true).build();
+
+    let mut reader = download_reader(client, args);
+    let mut output = Vec::new();
+    let result = reader.read_to_end(&mut output).await;
+
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_head_method_auto_falls_back_to_get() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_no_head_file_server().await;
+
+    let content: Vec<u8> = (0..1024 * 1024).map(|i| (i % 256) as u8).collect();
+    create_test_file(&temp_dir, "no-head.bin", &content);
+
+    let client = Arc::new(HttpClient::new(format!("{}/no-head.bin", base_url)));
+    let args = DownloadArgs::builder()
+        .concurrency(4)
+        .chunk_size(256 * 1024)
+        .quiet(true)
+        .build();
+    let output = download(client, args, Vec::new()).await?;
+
+    assert_eq!(output, content);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_head_method_head_fails_without_head_support() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_no_head_file_server().await;
+    create_test_file(&temp_dir, "no-head.txt", b"content");
+
+    let client = Arc::new(
+        HttpClient::new(format!("{}/no-head.txt", base_url)).with_head_method(HeadMethod::Head),
+    );
+    let args = DownloadArgs::builder().quiet(true).build();
+    let result = download(client, args, Vec::new()).await;
+
     assert!(result.is_err());
     Ok(())
 }
+
+#[tokio::test]
+async fn test_http_head_method_get_empty_file() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_file_server().await;
+    create_test_file(&temp_dir, "empty.txt", b"");
+
+    let client = Arc::new(
+        HttpClient::new(format!("{}/empty.txt", base_url)).with_head_method(HeadMethod::Get),
+    );
+    let args = DownloadArgs::builder().quiet(true).build();
+    let output = download(client, args, Vec::new()).await?;
+
+    assert!(output.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_ping_reports_endpoint() -> anyhow::Result<()> {
+    let (base_url, temp_dir) = start_no_head_file_server().await;
+    create_test_file(&temp_dir, "test.txt", b"pong");
+
+    // HEAD is rejected, so the ping falls back to a ranged GET like the download would
+    let client = HttpClient::new(format!("{}/test.txt", base_url));
+    let endpoint = client.ping().await?;
+
+    assert_eq!(endpoint.url, base_url);
+    assert_eq!(
+        endpoint.address.map(|a| a.to_string()),
+        base_url.strip_prefix("http://").map(str::to_string)
+    );
+
+    let missing = HttpClient::new(format!("{}/missing.txt", base_url))
+        .ping()
+        .await;
+    assert!(matches!(missing, Err(S3FcpError::NotFound(_))));
+    Ok(())
+}
@@ -1,10 +1,17 @@
 use aws_config::BehaviorVersion;
 use aws_sdk_s3::config::{Credentials, Region};
 use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{BucketVersioningStatus, Tag, Tagging, VersioningConfiguration};
 use aws_sdk_s3::Client;
-use s3fcp::cli::DownloadArgs;
+use s3fcp::cli::{DownloadArgs, OnError, UploadArgs};
 use s3fcp::downloader::download;
-use s3fcp::s3_client::S3Client;
+use s3fcp::error::S3FcpError;
+use s3fcp::recursive::{download_glob, download_prefix, PrefixOptions};
+use s3fcp::s3_client::{version_ago, S3Client};
+use s3fcp::uploader::{upload_file, S3Uploader};
+use s3fcp::uri::S3Uri;
+use s3fcp::verify::ChecksumManifest;
+use std::io::Write;
 use std::sync::Arc;
 use testcontainers::{runners::AsyncRunner, ContainerAsync, ImageExt};
 use testcontainers_modules::localstack::LocalStack;
@@ -120,6 +127,28 @@ async fn test_download_small_file() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_download_key_with_special_characters() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-special-key-bucket";
+    let key = " reports/q3 final+v2#draft ü.txt ";
+    let test_content = b"special key".to_vec();
+
+    upload_test_file(&client, bucket, key, test_content.clone()).await?;
+
+    // The key reaches S3 exactly as written after the bucket
+    let uri = S3Uri::parse(&format!("s3://{}/{}", bucket, key))?;
+    assert_eq!(uri.key, key);
+    let s3fcp_client = create_s3fcp_client(&endpoint, uri.bucket, uri.key).await;
+
+    let args = DownloadArgs::builder().quiet(true).build();
+    let output = download(s3fcp_client, args, Vec::new()).await?;
+
+    assert_eq!(output, test_content);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_download_large_file_with_chunks() -> anyhow::Result<()> {
     let (client, endpoint) = create_test_client().await;
@@ -198,3 +227,558 @@ async fn test_download_single_byte() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Fetch an object's full content with the plain SDK client
+async fn fetch_object(client: &Client, bucket: &str, key: &str) -> anyhow::Result<Vec<u8>> {
+    let response = client.get_object().bucket(bucket).key(key).send().await?;
+    Ok(response.body.collect().await?.into_bytes().to_vec())
+}
+
+#[tokio::test]
+async fn test_upload_small_file() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-upload-small-bucket";
+    let key = "uploaded.txt";
+    let test_content = b"Hello from s3fcp upload test!".to_vec();
+
+    client.create_bucket().bucket(bucket).send().await?;
+
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(&test_content)?;
+
+    let config = aws_config::defaults(BehaviorVersion::latest())
+        .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+        .region(Region::new("us-east-1"))
+        .endpoint_url(&endpoint)
+        .load()
+        .await;
+    let uploader = Arc::new(S3Uploader::new(
+        Client::new(&config),
+        bucket.to_string(),
+        key.to_string(),
+    ));
+
+    let args = UploadArgs::builder().quiet(true).build();
+    upload_file(uploader, file.path(), args).await?;
+
+    assert_eq!(fetch_object(&client, bucket, key).await?, test_content);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_upload_multipart_file() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-upload-multipart-bucket";
+    let key = "uploaded.bin";
+
+    // 12MB file with 5MiB parts = 3 parts
+    let test_content: Vec<u8> = (0..12 * 1024 * 1024).map(|i| (i % 256) as u8).collect();
+
+    client.create_bucket().bucket(bucket).send().await?;
+
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(&test_content)?;
+
+    let config = aws_config::defaults(BehaviorVersion::latest())
+        .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+        .region(Region::new("us-east-1"))
+        .endpoint_url(&endpoint)
+        .load()
+        .await;
+    let uploader = Arc::new(S3Uploader::new(
+        Client::new(&config),
+        bucket.to_string(),
+        key.to_string(),
+    ));
+
+    let args = UploadArgs::builder()
+        .concurrency(3)
+        .chunk_size(5 * 1024 * 1024)
+        .quiet(true)
+        .build();
+    upload_file(uploader, file.path(), args).await?;
+
+    assert_eq!(fetch_object(&client, bucket, key).await?, test_content);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_download_by_part() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-by-part-bucket";
+    let key = "parts.bin";
+
+    // 12MB file with 5MiB parts = 3 parts
+    let test_content: Vec<u8> = (0..12 * 1024 * 1024).map(|i| (i % 253) as u8).collect();
+
+    client.create_bucket().bucket(bucket).send().await?;
+
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(&test_content)?;
+    let uploader = Arc::new(S3Uploader::new(
+        create_sdk_client(&endpoint).await,
+        bucket.to_string(),
+        key.to_string(),
+    ));
+    let args = UploadArgs::builder()
+        .chunk_size(5 * 1024 * 1024)
+        .quiet(true)
+        .build();
+    upload_file(uploader, file.path(), args).await?;
+
+    let s3fcp_client = create_s3fcp_client(&endpoint, bucket.to_string(), key.to_string()).await;
+    let args = DownloadArgs::builder()
+        .concurrency(3)
+        .quiet(true)
+        .by_part(true)
+        .build();
+    let output = download(s3fcp_client, args, Vec::new()).await?;
+
+    assert_eq!(output, test_content);
+
+    Ok(())
+}
+
+/// Create an SDK client for LocalStack the way s3fcp's CLI would
+async fn create_sdk_client(endpoint: &str) -> Client {
+    let config = aws_config::defaults(BehaviorVersion::latest())
+        .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+        .region(Region::new("us-east-1"))
+        .endpoint_url(endpoint)
+        .load()
+        .await;
+    Client::new(&config)
+}
+
+#[tokio::test]
+async fn test_recursive_download() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-recursive-bucket";
+
+    client.create_bucket().bucket(bucket).send().await?;
+    let objects = [
+        ("data/a.txt", b"first".to_vec()),
+        ("data/nested/b.txt", b"second".to_vec()),
+        ("data/nested/", Vec::new()),
+        ("other/c.txt", b"not included".to_vec()),
+    ];
+    for (key, content) in &objects {
+        client
+            .put_object()
+            .bucket(bucket)
+            .key(*key)
+            .body(ByteStream::from(content.clone()))
+            .send()
+            .await?;
+    }
+
+    let output_dir = tempfile::tempdir()?;
+    let args = DownloadArgs::builder().quiet(true).build();
+    download_prefix(
+        create_sdk_client(&endpoint).await,
+        bucket,
+        "data/",
+        output_dir.path(),
+        args,
+        PrefixOptions::default(),
+    )
+    .await?;
+
+    assert_eq!(std::fs::read(output_dir.path().join("a.txt"))?, b"first");
+    assert_eq!(
+        std::fs::read(output_dir.path().join("nested/b.txt"))?,
+        b"second"
+    );
+    assert!(!output_dir.path().join("c.txt").exists());
+    assert!(!output_dir.path().join(".s3fcp-listing").exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_recursive_dry_run_downloads_nothing() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-recursive-dry-run-bucket";
+
+    client.create_bucket().bucket(bucket).send().await?;
+    client
+        .put_object()
+        .bucket(bucket)
+        .key("data/a.txt")
+        .body(ByteStream::from(b"first".to_vec()))
+        .send()
+        .await?;
+
+    let output_dir = tempfile::tempdir()?;
+    let target = output_dir.path().join("mirror");
+    download_prefix(
+        create_sdk_client(&endpoint).await,
+        bucket,
+        "data/",
+        &target,
+        DownloadArgs::builder().quiet(true).build(),
+        PrefixOptions::builder().dry_run(true).build(),
+    )
+    .await?;
+
+    assert!(!target.exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_recursive_download_continues_past_failures() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-recursive-on-error-bucket";
+
+    client.create_bucket().bucket(bucket).send().await?;
+    for key in ["data/a.txt", "data/b.txt", "data/c.txt"] {
+        client
+            .put_object()
+            .bucket(bucket)
+            .key(key)
+            .body(ByteStream::from(key.as_bytes().to_vec()))
+            .send()
+            .await?;
+    }
+
+    // A directory in the way makes b.txt fail to download
+    let output_dir = tempfile::tempdir()?;
+    std::fs::create_dir(output_dir.path().join("b.txt"))?;
+    let sdk_client = create_sdk_client(&endpoint).await;
+    let download = |on_error| {
+        download_prefix(
+            sdk_client.clone(),
+            bucket,
+            "data/",
+            output_dir.path(),
+            DownloadArgs::builder().quiet(true).build(),
+            PrefixOptions::builder().on_error(on_error).build(),
+        )
+    };
+
+    download(OnError::Abort).await.unwrap_err();
+    assert!(!output_dir.path().join("c.txt").exists());
+
+    match download(OnError::Continue).await {
+        Err(S3FcpError::DownloadFailed(message)) => {
+            assert!(message.starts_with("1 of 3 objects failed\n  b.txt: "))
+        }
+        other => panic!("expected a download failure, got {:?}", other),
+    }
+    assert_eq!(
+        std::fs::read(output_dir.path().join("a.txt"))?,
+        b"data/a.txt"
+    );
+    assert_eq!(
+        std::fs::read(output_dir.path().join("c.txt"))?,
+        b"data/c.txt"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_glob_download_fetches_matching_keys() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-glob-bucket";
+
+    client.create_bucket().bucket(bucket).send().await?;
+    for key in [
+        "logs/app.log",
+        "logs/db.log",
+        "logs/app.log.gz",
+        "logs/2024/old.log",
+    ] {
+        client
+            .put_object()
+            .bucket(bucket)
+            .key(key)
+            .body(ByteStream::from(key.as_bytes().to_vec()))
+            .send()
+            .await?;
+    }
+
+    let output_dir = tempfile::tempdir()?;
+    let sdk_client = create_sdk_client(&endpoint).await;
+    let args = DownloadArgs::builder().quiet(true).build();
+    download_glob(
+        sdk_client.clone(),
+        bucket,
+        "logs/*.log",
+        output_dir.path(),
+        args.clone(),
+        OnError::Abort,
+        None,
+    )
+    .await?;
+
+    assert_eq!(
+        std::fs::read(output_dir.path().join("app.log"))?,
+        b"logs/app.log"
+    );
+    assert_eq!(
+        std::fs::read(output_dir.path().join("db.log"))?,
+        b"logs/db.log"
+    );
+    assert!(!output_dir.path().join("app.log.gz").exists());
+    assert!(!output_dir.path().join("2024").exists());
+
+    let result = download_glob(
+        sdk_client,
+        bucket,
+        "logs/*.txt",
+        output_dir.path(),
+        args,
+        OnError::Abort,
+        None,
+    )
+    .await;
+    assert!(matches!(result, Err(S3FcpError::NotFound(_))));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_recursive_download_checks_manifest() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-recursive-checksum-bucket";
+
+    client.create_bucket().bucket(bucket).send().await?;
+    for (key, content) in [
+        ("sums/good.txt", "hello world"),
+        ("sums/bad.txt", "tampered"),
+    ] {
+        client
+            .put_object()
+            .bucket(bucket)
+            .key(key)
+            .body(ByteStream::from(content.as_bytes().to_vec()))
+            .send()
+            .await?;
+    }
+
+    // sha256("hello world") for both files, so only bad.txt mismatches
+    let digest = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+    let manifest = ChecksumManifest::parse(&format!("{0}  good.txt\n{0}  bad.txt\n", digest))
+        .map_err(anyhow::Error::msg)?;
+
+    let output_dir = tempfile::tempdir()?;
+    let args = DownloadArgs::builder().quiet(true).build();
+    let result = download_prefix(
+        create_sdk_client(&endpoint).await,
+        bucket,
+        "sums/",
+        output_dir.path(),
+        args,
+        PrefixOptions::builder().checksums(manifest).build(),
+    )
+    .await;
+
+    match result {
+        Err(S3FcpError::IntegrityError(message)) => {
+            assert_eq!(message, "checksum mismatch for bad.txt")
+        }
+        other => panic!("expected an integrity error, got {:?}", other),
+    }
+    // The other files are still downloaded
+    assert_eq!(
+        std::fs::read(output_dir.path().join("good.txt"))?,
+        b"hello world"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_recursive_download_resume_skips_complete_files() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-recursive-resume-bucket";
+
+    client.create_bucket().bucket(bucket).send().await?;
+    client
+        .put_object()
+        .bucket(bucket)
+        .key("logs/a.log")
+        .body(ByteStream::from(b"remote".to_vec()))
+        .send()
+        .await?;
+
+    // A local file with the listed size counts as already downloaded
+    let output_dir = tempfile::tempdir()?;
+    std::fs::write(output_dir.path().join("a.log"), b"local!")?;
+
+    let args = DownloadArgs::builder().quiet(true).build();
+    download_prefix(
+        create_sdk_client(&endpoint).await,
+        bucket,
+        "logs/",
+        output_dir.path(),
+        args,
+        PrefixOptions::builder().resume_listing(true).build(),
+    )
+    .await?;
+
+    assert_eq!(std::fs::read(output_dir.path().join("a.log"))?, b"local!");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_recursive_download_overwrite_if_newer() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-recursive-newer-bucket";
+
+    client.create_bucket().bucket(bucket).send().await?;
+    client
+        .put_object()
+        .bucket(bucket)
+        .key("logs/a.log")
+        .body(ByteStream::from(b"remote".to_vec()))
+        .send()
+        .await?;
+
+    let output_dir = tempfile::tempdir()?;
+    let path = output_dir.path().join("a.log");
+    let sdk_client = create_sdk_client(&endpoint).await;
+    let sync = || {
+        download_prefix(
+            sdk_client.clone(),
+            bucket,
+            "logs/",
+            output_dir.path(),
+            DownloadArgs::builder().quiet(true).build(),
+            PrefixOptions::builder()
+                .overwrite_if_newer(true)
+                .preserve_mtime(true)
+                .build(),
+        )
+    };
+
+    sync().await?;
+    assert_eq!(std::fs::read(&path)?, b"remote");
+    let mtime = std::fs::metadata(&path)?.modified()?;
+
+    // The local copy carries the object's Last-Modified, so it isn't downloaded again
+    std::fs::write(&path, b"local!")?;
+    std::fs::File::options()
+        .write(true)
+        .open(&path)?
+        .set_modified(mtime)?;
+    sync().await?;
+    assert_eq!(std::fs::read(&path)?, b"local!");
+
+    // An older local copy is replaced
+    std::fs::File::options()
+        .write(true)
+        .open(&path)?
+        .set_modified(mtime - std::time::Duration::from_secs(60))?;
+    sync().await?;
+    assert_eq!(std::fs::read(&path)?, b"remote");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_object_tags() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-tags-bucket";
+    let key = "tagged.txt";
+
+    upload_test_file(&client, bucket, key, b"tagged".to_vec()).await?;
+    client
+        .put_object_tagging()
+        .bucket(bucket)
+        .key(key)
+        .tagging(
+            Tagging::builder()
+                .tag_set(Tag::builder().key("owner").value("data-team").build()?)
+                .build()?,
+        )
+        .send()
+        .await?;
+
+    let s3fcp_client = create_s3fcp_client(&endpoint, bucket.to_string(), key.to_string()).await;
+    let tags = s3fcp_client.tags().await?;
+
+    assert_eq!(tags, vec![("owner".to_string(), "data-team".to_string())]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stored_metadata() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-metadata-bucket";
+    let key = "page.html";
+
+    client.create_bucket().bucket(bucket).send().await?;
+    client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .body(ByteStream::from(b"<html></html>".to_vec()))
+        .content_type("text/html")
+        .cache_control("max-age=60")
+        .metadata("owner", "web")
+        .send()
+        .await?;
+
+    let s3fcp_client = create_s3fcp_client(&endpoint, bucket.to_string(), key.to_string()).await;
+    let metadata = s3fcp_client.stored_metadata().await?;
+
+    assert_eq!(metadata.content_type.as_deref(), Some("text/html"));
+    assert_eq!(metadata.cache_control.as_deref(), Some("max-age=60"));
+    assert_eq!(metadata.content_encoding, None);
+    assert_eq!(metadata.user_metadata["owner"], "web");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_versions() -> anyhow::Result<()> {
+    let (client, endpoint) = create_test_client().await;
+    let bucket = "test-versions-bucket";
+    let key = "versioned.txt";
+
+    client.create_bucket().bucket(bucket).send().await?;
+    client
+        .put_bucket_versioning()
+        .bucket(bucket)
+        .versioning_configuration(
+            VersioningConfiguration::builder()
+                .status(BucketVersioningStatus::Enabled)
+                .build(),
+        )
+        .send()
+        .await?;
+    for body in ["first", "second!"] {
+        client
+            .put_object()
+            .bucket(bucket)
+            .key(key)
+            .body(ByteStream::from(body.as_bytes().to_vec()))
+            .send()
+            .await?;
+    }
+
+    let s3fcp_client = create_s3fcp_client(&endpoint, bucket.to_string(), key.to_string()).await;
+    let versions = s3fcp_client.versions().await?;
+
+    let sizes: Vec<_> = versions.iter().map(|v| (v.size, v.is_latest)).collect();
+    assert_eq!(sizes, vec![(7, true), (5, false)]);
+
+    let previous = S3Client::new(
+        create_sdk_client(&endpoint).await,
+        bucket.to_string(),
+        key.to_string(),
+        Some(version_ago(&versions, 1)?),
+    );
+    let args = DownloadArgs::builder().quiet(true).build();
+    let output = download(Arc::new(previous), args, Vec::new()).await?;
+    assert_eq!(output, b"first");
+
+    Ok(())
+}